@@ -56,6 +56,9 @@ CREATE TABLE IF NOT EXISTS sentiment_analyses (
     raw_llm_response TEXT,
     analyzed_at TEXT DEFAULT (datetime('now')),
     analysis_duration_ms INTEGER,
+    model_version TEXT, -- llm_models.version the analysis was produced by
+    analysis_version INTEGER DEFAULT 1, -- 1 for the first analysis of a tweet by a model, then 2, 3, ...
+    is_current INTEGER DEFAULT 1, -- only the newest analysis per tweet and model; older rows are history
     FOREIGN KEY (tweet_id) REFERENCES tweets(id) ON DELETE CASCADE,
     FOREIGN KEY (llm_model_id) REFERENCES llm_models(id)
 );
@@ -93,11 +96,17 @@ CREATE TABLE IF NOT EXISTS crawler_runs (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
     started_at TEXT DEFAULT (datetime('now')),
     completed_at TEXT,
-    status TEXT DEFAULT 'running', -- running, completed, failed
+    status TEXT DEFAULT 'running', -- running, completed, failed, cancelled
     tweets_fetched INTEGER DEFAULT 0,
     tweets_analyzed INTEGER DEFAULT 0,
     errors_count INTEGER DEFAULT 0,
-    error_details TEXT -- JSON array
+    error_details TEXT, -- JSON array
+    users_total INTEGER DEFAULT 0,
+    users_completed INTEGER DEFAULT 0,
+    current_user TEXT, -- username being crawled while running
+    jobs_queued INTEGER DEFAULT 0,
+    eta_at TEXT,
+    progress_updated_at TEXT
 );
 
 -- API Errors
@@ -140,11 +149,41 @@ CREATE TABLE IF NOT EXISTS reanalysis_requests (
     request_type TEXT NOT NULL, -- tweet, user, all
     tweet_id INTEGER,
     twitter_user_id INTEGER,
-    status TEXT NOT NULL DEFAULT 'pending', -- pending, processing, completed
+    llm_model_id INTEGER, -- only this model; NULL for every enabled model
+    tweets_from TEXT, -- tweet timestamp range, inclusive start
+    tweets_to TEXT, -- exclusive end
+    only_failed INTEGER DEFAULT 0, -- only tweet/model pairs whose last job failed
+    missing_emotion TEXT, -- only pairs whose current analysis has no score for this emotion
+    dry_run INTEGER DEFAULT 0, -- estimate the jobs only; confirm by setting it back to pending
+    jobs_estimate INTEGER,
+    jobs_enqueued INTEGER DEFAULT 0,
+    cursor_tweet_id INTEGER,
+    tweets_total INTEGER,
+    tweets_processed INTEGER DEFAULT 0,
+    progress_percent REAL DEFAULT 0,
+    error_message TEXT,
+    lease_expires_at TEXT, -- a crawler is walking the request until then
+    status TEXT NOT NULL DEFAULT 'pending', -- pending, estimated, processing, completed, failed, cancelled
     requested_at TEXT DEFAULT (datetime('now')),
     processed_at TEXT
 );
 
+-- Database backups to S3-compatible storage, performed by the crawler
+CREATE TABLE IF NOT EXISTS backups (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    status TEXT NOT NULL DEFAULT 'requested', -- requested, running, completed, failed, expired
+    triggered_by TEXT NOT NULL DEFAULT 'admin', -- admin, schedule, manual
+    requested_at TEXT DEFAULT (datetime('now')),
+    started_at TEXT,
+    completed_at TEXT,
+    progress_percent INTEGER DEFAULT 0,
+    object_key TEXT,
+    database_bytes INTEGER,
+    compressed_bytes INTEGER,
+    sha256 TEXT, -- of the compressed object
+    error_message TEXT
+);
+
 -- Configurations (key-value store)
 CREATE TABLE IF NOT EXISTS configurations (
     key TEXT PRIMARY KEY,
@@ -175,6 +214,7 @@ CREATE INDEX IF NOT EXISTS idx_api_errors_time ON api_errors(occurred_at);
 CREATE INDEX IF NOT EXISTS idx_analysis_queue_status ON analysis_queue(status);
 CREATE INDEX IF NOT EXISTS idx_analysis_queue_tweet ON analysis_queue(tweet_id);
 CREATE INDEX IF NOT EXISTS idx_reanalysis_status ON reanalysis_requests(status);
+CREATE INDEX IF NOT EXISTS idx_backups_status ON backups(status);
 
 -- Default configuration values
 INSERT OR IGNORE INTO configurations (key, value) VALUES
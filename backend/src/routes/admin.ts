@@ -8,11 +8,17 @@ interface CrawlerRun {
   id: number;
   started_at: string;
   completed_at: string | null;
-  status: 'running' | 'completed' | 'failed';
+  status: 'running' | 'completed' | 'failed' | 'cancelled';
   tweets_fetched: number;
   tweets_analyzed: number;
   errors_count: number;
   error_details: string | null;
+  users_total?: number;
+  users_completed?: number;
+  current_user?: string | null;
+  jobs_queued?: number;
+  eta_at?: string | null;
+  progress_updated_at?: string | null;
 }
 
 // Type definitions for models
@@ -355,9 +361,21 @@ router.get('/crawler/status', (_req, res) => {
       LIMIT 5
     `).all() as CrawlerRun[];
 
+    // Prefer the next run persisted by the crawler daemon (table absent until it first runs)
+    let scheduledRun: { next_scheduled_run: string | null } | undefined;
+    try {
+      scheduledRun = db.prepare(`
+        SELECT next_scheduled_run FROM crawler_schedule WHERE id = 1
+      `).get() as { next_scheduled_run: string | null } | undefined;
+    } catch {
+      scheduledRun = undefined;
+    }
+
     // Calculate next run time
     let nextRun: string | null = null;
-    if (latestRun?.completed_at) {
+    if (scheduledRun) {
+      nextRun = scheduledRun.next_scheduled_run;
+    } else if (latestRun?.completed_at) {
       const lastCompleted = new Date(latestRun.completed_at);
       const nextRunTime = new Date(lastCompleted.getTime() + config.intervalHours * 60 * 60 * 1000);
       nextRun = nextRunTime.toISOString();
@@ -369,6 +387,16 @@ router.get('/crawler/status', (_req, res) => {
     res.json({
       status: runningCrawler ? 'running' : 'idle',
       isRunning: !!runningCrawler,
+      progress: runningCrawler ? {
+        usersTotal: runningCrawler.users_total ?? 0,
+        usersCompleted: runningCrawler.users_completed ?? 0,
+        currentUser: runningCrawler.current_user ?? null,
+        tweetsFetched: runningCrawler.tweets_fetched,
+        jobsQueued: runningCrawler.jobs_queued ?? 0,
+        errorsCount: runningCrawler.errors_count,
+        etaAt: runningCrawler.eta_at ?? null,
+        updatedAt: runningCrawler.progress_updated_at ?? null,
+      } : null,
       lastRun: latestRun ? {
         id: latestRun.id,
         startedAt: latestRun.started_at,
@@ -427,10 +455,32 @@ function generateMockTweetsForUser(userId: number, username: string): number {
     SELECT id FROM llm_models WHERE is_enabled = 1
   `).all() as { id: number }[];
 
+  // Same versioning as the crawler's complete_analysis_job: the previous analysis
+  // for the tweet and model is kept as history and the new one becomes current
+  const clearCurrentAnalysis = db.prepare(`
+    UPDATE sentiment_analyses
+    SET is_current = 0
+    WHERE tweet_id = ? AND llm_model_id = ? AND is_current = 1
+  `);
   const insertAnalysis = db.prepare(`
-    INSERT INTO sentiment_analyses (tweet_id, llm_model_id, emotion_scores, analysis_duration_ms)
-    VALUES (?, ?, ?, ?)
+    INSERT INTO sentiment_analyses
+      (tweet_id, llm_model_id, emotion_scores, analysis_duration_ms,
+       model_version, analysis_version, is_current)
+    VALUES (?1, ?2, ?3, ?4,
+            (SELECT version FROM llm_models WHERE id = ?2),
+            COALESCE(
+              (SELECT MAX(analysis_version) FROM sentiment_analyses
+               WHERE tweet_id = ?1 AND llm_model_id = ?2),
+              0
+            ) + 1,
+            1)
   `);
+  const recordAnalysis = db.transaction(
+    (tweetId: number, modelId: number, emotionScores: string, durationMs: number) => {
+      clearCurrentAnalysis.run(tweetId, modelId);
+      insertAnalysis.run(tweetId, modelId, emotionScores, durationMs);
+    }
+  );
 
   let tweetsCreated = 0;
   const numTweets = Math.floor(Math.random() * 5) + 3; // 3-7 tweets per user
@@ -479,7 +529,7 @@ function generateMockTweetsForUser(userId: number, username: string): number {
               inspirational: Math.floor(Math.random() * 60),
               anxious: Math.floor(Math.random() * 35),
             });
-            insertAnalysis.run(newTweetId, model.id, emotionScores, Math.floor(Math.random() * 2000) + 500);
+            recordAnalysis(newTweetId, model.id, emotionScores, Math.floor(Math.random() * 2000) + 500);
           }
         }
       }
@@ -961,11 +1011,13 @@ router.put('/settings', (req, res) => {
         rateLimitPer15Min: crawler.rateLimitPer15Min ?? existingCrawler.rateLimitPer15Min ?? 450,
       };
 
+      // updated_by tells the crawler the settings were saved by an admin, not seeded
+      const updatedBy = req.session.adminUsername || 'admin';
       db.prepare(`
-        INSERT INTO configurations (key, value, updated_at)
-        VALUES ('crawler', ?, datetime('now'))
-        ON CONFLICT(key) DO UPDATE SET value = ?, updated_at = datetime('now')
-      `).run(JSON.stringify(newCrawlerConfig), JSON.stringify(newCrawlerConfig));
+        INSERT INTO configurations (key, value, updated_at, updated_by)
+        VALUES ('crawler', ?, datetime('now'), ?)
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = datetime('now'), updated_by = excluded.updated_by
+      `).run(JSON.stringify(newCrawlerConfig), updatedBy);
     }
 
     // Update S3 backup settings if provided
@@ -1436,7 +1488,7 @@ router.put('/models/:id', (req, res) => {
 
 // POST /api/admin/reanalyze
 router.post('/reanalyze', (req, res) => {
-  const { tweetId, userId, all } = req.body;
+  const { tweetId, userId, all, modelId, from, to, onlyFailed, missingEmotion, dryRun } = req.body;
 
   try {
     // Check if reanalysis is already running
@@ -1495,17 +1547,69 @@ router.post('/reanalyze', (req, res) => {
       return res.status(400).json({ error: 'Must specify tweetId, userId, or all=true' });
     }
 
+    // Optional scope; the crawler narrows the jobs it queues to these
+    let requestModelId: number | null = null;
+    if (modelId !== undefined && modelId !== null && modelId !== '') {
+      const model = db.prepare('SELECT id, is_enabled FROM llm_models WHERE id = ?').get(modelId) as
+        | { id: number; is_enabled: number }
+        | undefined;
+      if (!model) {
+        return res.status(404).json({ error: 'Model not found' });
+      }
+      if (!model.is_enabled) {
+        return res.status(400).json({ error: 'Model is not enabled' });
+      }
+      requestModelId = model.id;
+    }
+
+    const parseBound = (value: unknown): string | null | undefined => {
+      if (value === undefined || value === null || value === '') return null;
+      if (typeof value !== 'string' || isNaN(Date.parse(value))) return undefined;
+      return new Date(value).toISOString();
+    };
+    const tweetsFrom = parseBound(from);
+    const tweetsTo = parseBound(to);
+    if (tweetsFrom === undefined || tweetsTo === undefined) {
+      return res.status(400).json({ error: 'from and to must be dates' });
+    }
+    if (tweetsFrom && tweetsTo && tweetsFrom >= tweetsTo) {
+      return res.status(400).json({ error: 'from must be before to' });
+    }
+
+    const hasMissingEmotion = missingEmotion !== undefined && missingEmotion !== null;
+    if (hasMissingEmotion && (typeof missingEmotion !== 'string' || !missingEmotion.trim())) {
+      return res.status(400).json({ error: 'missingEmotion must be an emotion name' });
+    }
+    const requestMissingEmotion = hasMissingEmotion ? String(missingEmotion).trim() : null;
+
     const request = db.prepare(`
-      INSERT INTO reanalysis_requests (request_type, tweet_id, twitter_user_id)
-      VALUES (?, ?, ?)
+      INSERT INTO reanalysis_requests
+        (request_type, tweet_id, twitter_user_id, llm_model_id, tweets_from, tweets_to,
+         only_failed, missing_emotion, dry_run)
+      VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
       RETURNING *
-    `).get(requestType, requestTweetId, requestUserId) as { id: number; requested_at: string };
+    `).get(
+      requestType,
+      requestTweetId,
+      requestUserId,
+      requestModelId,
+      tweetsFrom,
+      tweetsTo,
+      onlyFailed ? 1 : 0,
+      requestMissingEmotion,
+      dryRun ? 1 : 0
+    ) as { id: number; requested_at: string };
+
+    if (dryRun) {
+      message = `Dry run queued; the crawler will estimate the jobs for request #${request.id} before you confirm it`;
+    }
 
     res.json({
       success: true,
       message,
       requestId: request.id,
       tweetsToAnalyze,
+      dryRun: Boolean(dryRun),
       requestedAt: request.requested_at,
     });
   } catch (error) {
@@ -1519,6 +1623,15 @@ interface ReanalysisRequestRow {
   request_type: string;
   tweet_id: number | null;
   twitter_user_id: number | null;
+  llm_model_id: number | null;
+  tweets_from: string | null;
+  tweets_to: string | null;
+  only_failed: number | null;
+  missing_emotion: string | null;
+  dry_run: number | null;
+  jobs_estimate: number | null;
+  jobs_enqueued: number | null;
+  error_message: string | null;
   status: string;
   requested_at: string;
   processed_at: string | null;
@@ -1532,7 +1645,9 @@ router.get('/reanalyze/requests', (req, res) => {
     const requestStatus = typeof status === 'string' ? status : 'pending';
 
     const rows = db.prepare(`
-      SELECT id, request_type, tweet_id, twitter_user_id, status, requested_at, processed_at
+      SELECT id, request_type, tweet_id, twitter_user_id, llm_model_id, tweets_from, tweets_to,
+             only_failed, missing_emotion, dry_run, jobs_estimate, jobs_enqueued, error_message,
+             status, requested_at, processed_at
       FROM reanalysis_requests
       WHERE status = ?
       ORDER BY requested_at ASC
@@ -1545,6 +1660,15 @@ router.get('/reanalyze/requests', (req, res) => {
         requestType: row.request_type,
         tweetId: row.tweet_id,
         userId: row.twitter_user_id,
+        modelId: row.llm_model_id,
+        from: row.tweets_from,
+        to: row.tweets_to,
+        onlyFailed: Boolean(row.only_failed),
+        missingEmotion: row.missing_emotion,
+        dryRun: Boolean(row.dry_run),
+        jobsEstimate: row.jobs_estimate,
+        jobsEnqueued: row.jobs_enqueued,
+        errorMessage: row.error_message,
         status: row.status,
         requestedAt: row.requested_at,
         processedAt: row.processed_at,
@@ -1574,9 +1698,9 @@ router.post('/reanalyze/cancel/:id', (req, res) => {
       return res.status(404).json({ error: 'Reanalysis request not found' });
     }
 
-    if (request.status !== 'pending') {
+    if (request.status !== 'pending' && request.status !== 'estimated') {
       return res.status(409).json({
-        error: 'Only pending requests can be cancelled',
+        error: 'Only pending or estimated requests can be cancelled',
         status: request.status,
       });
     }
@@ -1594,6 +1718,45 @@ router.post('/reanalyze/cancel/:id', (req, res) => {
   }
 });
 
+// POST /api/admin/reanalyze/confirm/:id
+// Queues a dry run the crawler has estimated, so it enqueues its jobs for real
+router.post('/reanalyze/confirm/:id', (req, res) => {
+  const { id } = req.params;
+  const requestId = parseInt(id, 10);
+
+  if (isNaN(requestId)) {
+    return res.status(400).json({ error: 'Invalid request id' });
+  }
+
+  try {
+    const request = db.prepare(`
+      SELECT id, status, jobs_estimate FROM reanalysis_requests WHERE id = ?
+    `).get(requestId) as { id: number; status: string; jobs_estimate: number | null } | undefined;
+
+    if (!request) {
+      return res.status(404).json({ error: 'Reanalysis request not found' });
+    }
+
+    if (request.status !== 'estimated') {
+      return res.status(409).json({
+        error: 'Only estimated dry runs can be confirmed',
+        status: request.status,
+      });
+    }
+
+    db.prepare(`
+      UPDATE reanalysis_requests
+      SET status = 'pending', dry_run = 0, processed_at = NULL
+      WHERE id = ? AND status = 'estimated'
+    `).run(requestId);
+
+    res.json({ success: true, requestId, status: 'pending', jobsEstimate: request.jobs_estimate });
+  } catch (error) {
+    console.error('Error confirming reanalysis request:', error);
+    res.status(500).json({ error: 'Failed to confirm reanalysis request' });
+  }
+});
+
 // Type definitions for API errors
 interface ApiError {
   id: number;
@@ -1944,10 +2107,10 @@ router.post('/config/import', (req, res) => {
         errors.push(...crawlerErrors);
       } else {
         db.prepare(`
-          INSERT INTO configurations (key, value, updated_at)
-          VALUES ('crawler', ?, datetime('now'))
-          ON CONFLICT(key) DO UPDATE SET value = ?, updated_at = datetime('now')
-        `).run(JSON.stringify(crawler), JSON.stringify(crawler));
+          INSERT INTO configurations (key, value, updated_at, updated_by)
+          VALUES ('crawler', ?, datetime('now'), ?)
+          ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = datetime('now'), updated_by = excluded.updated_by
+        `).run(JSON.stringify(crawler), req.session.adminUsername || 'admin');
         importedKeys.push('crawler');
       }
     }
@@ -2075,31 +2238,59 @@ router.post('/config/import', (req, res) => {
   }
 });
 
-// In-memory store for backup progress and history (keyed by backup ID)
-interface BackupProgress {
-  id: string;
-  status: 'in_progress' | 'completed' | 'failed';
-  progress: number; // 0-100
-  startedAt: Date;
-  completedAt?: Date;
-  fileSize?: number;
-  fileName?: string;
-  error?: string;
+// Backups are performed by the crawler; the API only reads and requests them
+// through the `backups` table.
+interface BackupRow {
+  id: number;
+  status: 'requested' | 'running' | 'completed' | 'failed' | 'expired';
+  triggered_by: string;
+  requested_at: string;
+  started_at: string | null;
+  completed_at: string | null;
+  progress_percent: number | null;
+  object_key: string | null;
+  compressed_bytes: number | null;
+  error_message: string | null;
 }
 
-interface BackupRecord {
-  id: string;
-  fileName: string;
-  fileSize: number;
-  createdAt: string;
-  status: 'completed' | 'failed';
+const BACKUP_COLUMNS = `id, status, triggered_by, requested_at, started_at, completed_at,
+  progress_percent, object_key, compressed_bytes, error_message`;
+
+function backupFileName(row: BackupRow): string | undefined {
+  return row.object_key ? row.object_key.split('/').pop() : undefined;
+}
+
+function formatBackup(row: BackupRow) {
+  return {
+    id: String(row.id),
+    status: row.status === 'requested' || row.status === 'running' ? 'in_progress' : row.status,
+    progress: row.progress_percent ?? 0,
+    triggeredBy: row.triggered_by,
+    startedAt: row.started_at ?? row.requested_at,
+    completedAt: row.completed_at ?? undefined,
+    fileSize: row.compressed_bytes ?? undefined,
+    fileName: backupFileName(row),
+    error: row.error_message ?? undefined,
+  };
 }
 
-// Store for current backup operation
-let currentBackup: BackupProgress | null = null;
+function formatBackupRecord(row: BackupRow) {
+  return {
+    id: String(row.id),
+    fileName: backupFileName(row) ?? '',
+    fileSize: row.compressed_bytes ?? 0,
+    createdAt: row.completed_at ?? row.requested_at,
+    status: row.status,
+  };
+}
 
-// Store for backup history
-const backupHistory: BackupRecord[] = [];
+function activeBackup(): BackupRow | undefined {
+  return db.prepare(`
+    SELECT ${BACKUP_COLUMNS} FROM backups
+    WHERE status IN ('requested', 'running')
+    ORDER BY id DESC LIMIT 1
+  `).get() as BackupRow | undefined;
+}
 
 // GET /api/admin/backup/status
 router.get('/backup/status', (_req, res) => {
@@ -2113,6 +2304,20 @@ router.get('/backup/status', (_req, res) => {
     // Check if S3 is configured
     const isConfigured = s3Config?.enabled && s3Config?.bucketName;
 
+    const latest = db.prepare(
+      `SELECT ${BACKUP_COLUMNS} FROM backups ORDER BY id DESC LIMIT 1`
+    ).get() as BackupRow | undefined;
+    const history = db.prepare(`
+      SELECT ${BACKUP_COLUMNS} FROM backups
+      WHERE status IN ('completed', 'failed')
+      ORDER BY id DESC LIMIT 10
+    `).all() as BackupRow[];
+    const lastBackup = db.prepare(`
+      SELECT ${BACKUP_COLUMNS} FROM backups
+      WHERE status = 'completed'
+      ORDER BY id DESC LIMIT 1
+    `).get() as BackupRow | undefined;
+
     // Return backup status
     res.json({
       isConfigured,
@@ -2123,24 +2328,10 @@ router.get('/backup/status', (_req, res) => {
             schedule: s3Config.schedule,
           }
         : null,
-      inProgress: currentBackup?.status === 'in_progress',
-      currentBackup: currentBackup
-        ? {
-            id: currentBackup.id,
-            status: currentBackup.status,
-            progress: currentBackup.progress,
-            startedAt: currentBackup.startedAt.toISOString(),
-            completedAt: currentBackup.completedAt?.toISOString(),
-            fileSize: currentBackup.fileSize,
-            fileName: currentBackup.fileName,
-            error: currentBackup.error,
-          }
-        : null,
-      lastBackup:
-        backupHistory.length > 0
-          ? backupHistory[backupHistory.length - 1]
-          : null,
-      backupHistory: backupHistory.slice(-10).reverse(), // Last 10 backups, most recent first
+      inProgress: Boolean(activeBackup()),
+      currentBackup: latest ? formatBackup(latest) : null,
+      lastBackup: lastBackup ? formatBackupRecord(lastBackup) : null,
+      backupHistory: history.map(formatBackupRecord), // Last 10 backups, most recent first
     });
   } catch (error) {
     console.error('Error getting backup status:', error);
@@ -2152,14 +2343,15 @@ router.get('/backup/status', (_req, res) => {
 router.post('/backup/trigger', (_req, res) => {
   try {
     // Check if a backup is already in progress
-    if (currentBackup?.status === 'in_progress') {
+    const current = activeBackup();
+    if (current) {
       return res.status(409).json({
         success: false,
         error: 'A backup is already in progress',
         currentBackup: {
-          id: currentBackup.id,
-          progress: currentBackup.progress,
-          startedAt: currentBackup.startedAt.toISOString(),
+          id: String(current.id),
+          progress: current.progress_percent ?? 0,
+          startedAt: current.started_at ?? current.requested_at,
         },
       });
     }
@@ -2178,65 +2370,16 @@ router.post('/backup/trigger', (_req, res) => {
       });
     }
 
-    // Generate backup ID and filename
-    const backupId = `backup_${Date.now()}`;
-    const timestamp = new Date().toISOString().replace(/[:.]/g, '-');
-    const fileName = `twitter-feels-${timestamp}.db`;
-
-    // Initialize backup progress
-    currentBackup = {
-      id: backupId,
-      status: 'in_progress',
-      progress: 0,
-      startedAt: new Date(),
-      fileName,
-    };
+    // The crawler's backup stage picks the request up on its next poll
+    const result = db.prepare(
+      `INSERT INTO backups (status, triggered_by) VALUES ('requested', 'admin')`
+    ).run();
 
-    // Respond immediately with backup started
     res.json({
       success: true,
-      message: 'Backup started',
-      backupId,
-      fileName,
+      message: 'Backup requested',
+      backupId: String(result.lastInsertRowid),
     });
-
-    // Simulate backup progress (in production, this would be actual S3 upload)
-    const backupDuration = 5000; // 5 seconds
-    const updateInterval = 500; // Update every 500ms
-    const totalUpdates = backupDuration / updateInterval;
-    let currentUpdate = 0;
-
-    const progressInterval = setInterval(() => {
-      currentUpdate++;
-      const progressPercent = Math.min((currentUpdate / totalUpdates) * 100, 100);
-
-      if (currentBackup && currentBackup.id === backupId) {
-        currentBackup.progress = Math.round(progressPercent);
-
-        // Complete backup when we reach 100%
-        if (currentUpdate >= totalUpdates) {
-          clearInterval(progressInterval);
-
-          // Simulate file size (between 50KB and 500KB)
-          const fileSize = Math.floor(Math.random() * 450000) + 50000;
-
-          currentBackup.status = 'completed';
-          currentBackup.completedAt = new Date();
-          currentBackup.fileSize = fileSize;
-
-          // Add to backup history
-          backupHistory.push({
-            id: backupId,
-            fileName,
-            fileSize,
-            createdAt: currentBackup.completedAt.toISOString(),
-            status: 'completed',
-          });
-
-          console.log(`Backup ${backupId} completed: ${fileName} (${fileSize} bytes)`);
-        }
-      }
-    }, updateInterval);
   } catch (error) {
     console.error('Error triggering backup:', error);
     res.status(500).json({ error: 'Failed to trigger backup' });
@@ -2256,22 +2399,25 @@ router.post('/backup/restore', (req, res) => {
     }
 
     // Check if a backup is in progress (can't restore while backing up)
-    if (currentBackup?.status === 'in_progress') {
+    if (activeBackup()) {
       return res.status(409).json({
         success: false,
         error: 'Cannot restore while a backup is in progress',
       });
     }
 
-    // Find the backup in history
-    const backup = backupHistory.find((b) => b.id === backupId);
-    if (!backup) {
+    // Find the backup
+    const row = db.prepare(
+      `SELECT ${BACKUP_COLUMNS} FROM backups WHERE id = ?`
+    ).get(Number(backupId)) as BackupRow | undefined;
+    if (!row) {
       return res.status(404).json({
         success: false,
         error: 'Backup not found',
       });
     }
 
+    const backup = formatBackupRecord(row);
     if (backup.status !== 'completed') {
       return res.status(400).json({
         success: false,
@@ -2279,23 +2425,12 @@ router.post('/backup/restore', (req, res) => {
       });
     }
 
-    // In production, this would:
-    // 1. Download the backup from S3
-    // 2. Stop the application
-    // 3. Replace the database file
-    // 4. Restart the application
-
-    // For demo purposes, we'll simulate a successful restore
-    console.log(`Restore initiated from backup: ${backup.fileName}`);
-
-    res.json({
-      success: true,
-      message: `Restore from backup "${backup.fileName}" completed successfully`,
-      restoredFrom: {
-        id: backup.id,
-        fileName: backup.fileName,
-        createdAt: backup.createdAt,
-      },
+    // The database cannot be replaced underneath this process, so restores
+    // run through the crawler CLI with both the crawler and the API stopped.
+    res.status(409).json({
+      success: false,
+      error: `Restores cannot run while the API is up. Stop the crawler and the API, then run: twitter-feels-crawler restore ${backup.fileName}`,
+      command: `twitter-feels-crawler restore ${backup.fileName}`,
     });
   } catch (error) {
     console.error('Error restoring backup:', error);
@@ -2319,17 +2454,23 @@ router.get('/backup/list', (_req, res) => {
       });
     }
 
-    // Return backup history (in production, this would list objects from S3)
+    // Completed backups still in the bucket (older ones are marked expired)
+    const rows = db.prepare(`
+      SELECT ${BACKUP_COLUMNS} FROM backups
+      WHERE status = 'completed'
+      ORDER BY id DESC
+    `).all() as BackupRow[];
+
     res.json({
-      backups: backupHistory
-        .filter((b) => b.status === 'completed')
-        .map((b) => ({
-          id: b.id,
-          fileName: b.fileName,
-          fileSize: b.fileSize,
-          createdAt: b.createdAt,
-        }))
-        .reverse(), // Most recent first
+      backups: rows.map((row) => {
+        const backup = formatBackupRecord(row);
+        return {
+          id: backup.id,
+          fileName: backup.fileName,
+          fileSize: backup.fileSize,
+          createdAt: backup.createdAt,
+        };
+      }), // Most recent first
       bucketName: s3Config.bucketName,
     });
   } catch (error) {
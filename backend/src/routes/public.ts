@@ -78,7 +78,7 @@ router.get('/dashboard', (req, res) => {
     // Get stats from database
     const userCount = db.prepare('SELECT COUNT(*) as count FROM twitter_users WHERE is_active = 1').get() as { count: number };
     const tweetCount = db.prepare('SELECT COUNT(*) as count FROM tweets').get() as { count: number };
-    const analysisCount = db.prepare('SELECT COUNT(*) as count FROM sentiment_analyses').get() as { count: number };
+    const analysisCount = db.prepare('SELECT COUNT(*) as count FROM sentiment_analyses WHERE is_current = 1').get() as { count: number };
 
     // Get gauge configuration
     const gaugeConfigRow = db.prepare("SELECT value FROM configurations WHERE key = 'gauges'").get() as { value: string } | undefined;
@@ -89,7 +89,8 @@ router.get('/dashboard', (req, res) => {
 
     // Calculate emotion averages from sentiment analyses with time filtering
     // If modelId is specified, filter by model; otherwise use all models
-    const queryConditions: string[] = [];
+    // Only the current analysis per tweet and model; older rows are reanalysis history
+    const queryConditions: string[] = ['sa.is_current = 1'];
     const queryParams: unknown[] = [];
 
     // Add time bucket filter
@@ -104,7 +105,7 @@ router.get('/dashboard', (req, res) => {
       queryParams.push(modelId);
     }
 
-    const whereClause = queryConditions.length > 0 ? ' WHERE ' + queryConditions.join(' AND ') : '';
+    const whereClause = ' WHERE ' + queryConditions.join(' AND ');
 
     const emotionQuery = `
       SELECT emotion_scores
@@ -195,7 +196,7 @@ router.get('/dashboard', (req, res) => {
         SELECT sa.emotion_scores
         FROM sentiment_analyses sa
         JOIN tweets t ON sa.tweet_id = t.id
-        WHERE t.twitter_user_id = ?${userFilters}
+        WHERE t.twitter_user_id = ? AND sa.is_current = 1${userFilters}
       `).all(...userQueryParams) as Array<{ emotion_scores: string }>;
 
       let topEmotion = 'none';
@@ -278,7 +279,7 @@ router.get('/dashboard', (req, res) => {
             SELECT sa.emotion_scores
             FROM sentiment_analyses sa
             JOIN tweets t ON sa.tweet_id = t.id
-            WHERE t.twitter_user_id = ?${leaderboardFilters}
+            WHERE t.twitter_user_id = ? AND sa.is_current = 1${leaderboardFilters}
           `).all(...leaderboardQueryParams) as Array<{ emotion_scores: string }>;
 
           let emotionSum = 0;
@@ -413,7 +414,7 @@ router.get('/users/:id', (req, res) => {
       SELECT sa.emotion_scores
       FROM sentiment_analyses sa
       JOIN tweets t ON sa.tweet_id = t.id
-      WHERE t.twitter_user_id = ?
+      WHERE t.twitter_user_id = ? AND sa.is_current = 1
     `).all(id) as Array<{ emotion_scores: string }>;
 
     // Aggregate emotion scores - collect all values for median calculation
@@ -544,7 +545,7 @@ router.get('/users/:id/tweets', (req, res) => {
           m.name as model_name, m.version as model_version
         FROM sentiment_analyses sa
         JOIN llm_models m ON sa.llm_model_id = m.id
-        WHERE sa.tweet_id = ?
+        WHERE sa.tweet_id = ? AND sa.is_current = 1
         ORDER BY sa.analyzed_at DESC
       `).all(tweet.id) as Array<{
         emotion_scores: string;
@@ -665,7 +666,7 @@ router.get('/tweets/:id', (req, res) => {
         m.id as model_id, m.name as model_name, m.version as model_version, m.provider
       FROM sentiment_analyses sa
       JOIN llm_models m ON sa.llm_model_id = m.id
-      WHERE sa.tweet_id = ?
+      WHERE sa.tweet_id = ? AND sa.is_current = 1
       ORDER BY sa.analyzed_at DESC
     `).all(id) as Array<{
       id: number;
@@ -799,7 +800,7 @@ router.get('/users/:id/trends', (req, res) => {
         sa.emotion_scores
       FROM tweets t
       JOIN sentiment_analyses sa ON t.id = sa.tweet_id
-      WHERE t.twitter_user_id = ?
+      WHERE t.twitter_user_id = ? AND sa.is_current = 1
       ORDER BY t.tweet_timestamp ASC
     `).all(id) as Array<{
       tweet_id: number;
@@ -0,0 +1,147 @@
+-- Baseline: the crawler's own tables plus the Node-owned tables it reads and
+-- writes, so the crawler can start against an empty database. Definitions of
+-- Node-owned tables mirror backend/src/db/schema.sql.
+
+CREATE TABLE IF NOT EXISTS twitter_users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    twitter_id TEXT UNIQUE NOT NULL,
+    username TEXT NOT NULL,
+    display_name TEXT NOT NULL,
+    bio TEXT,
+    avatar_url TEXT,
+    follower_count INTEGER DEFAULT 0,
+    following_count INTEGER DEFAULT 0,
+    is_active INTEGER DEFAULT 1,
+    created_at TEXT DEFAULT (datetime('now')),
+    updated_at TEXT DEFAULT (datetime('now'))
+);
+
+CREATE TABLE IF NOT EXISTS tweets (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    twitter_user_id INTEGER NOT NULL,
+    tweet_id TEXT UNIQUE NOT NULL,
+    content TEXT NOT NULL,
+    tweet_timestamp TEXT NOT NULL,
+    engagement_metrics TEXT,
+    is_retweet INTEGER DEFAULT 0,
+    is_reply INTEGER DEFAULT 0,
+    created_at TEXT DEFAULT (datetime('now')),
+    updated_at TEXT DEFAULT (datetime('now')),
+    FOREIGN KEY (twitter_user_id) REFERENCES twitter_users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS llm_models (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    version TEXT,
+    provider TEXT,
+    huggingface_model_id TEXT,
+    is_local INTEGER DEFAULT 1,
+    is_enabled INTEGER DEFAULT 0,
+    download_status TEXT DEFAULT 'not_downloaded',
+    download_progress INTEGER DEFAULT 0,
+    disk_size_bytes INTEGER,
+    created_at TEXT DEFAULT (datetime('now')),
+    updated_at TEXT DEFAULT (datetime('now'))
+);
+
+CREATE TABLE IF NOT EXISTS sentiment_analyses (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tweet_id INTEGER NOT NULL,
+    llm_model_id INTEGER NOT NULL,
+    emotion_scores TEXT NOT NULL,
+    raw_llm_response TEXT,
+    analyzed_at TEXT DEFAULT (datetime('now')),
+    analysis_duration_ms INTEGER,
+    FOREIGN KEY (tweet_id) REFERENCES tweets(id) ON DELETE CASCADE,
+    FOREIGN KEY (llm_model_id) REFERENCES llm_models(id)
+);
+
+CREATE TABLE IF NOT EXISTS user_aggregations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    twitter_user_id INTEGER NOT NULL,
+    llm_model_id INTEGER,
+    time_bucket TEXT NOT NULL,
+    bucket_start_date TEXT,
+    emotion_averages TEXT NOT NULL,
+    emotion_medians TEXT,
+    emotion_modes TEXT,
+    tweet_count INTEGER DEFAULT 0,
+    computed_at TEXT DEFAULT (datetime('now')),
+    FOREIGN KEY (twitter_user_id) REFERENCES twitter_users(id) ON DELETE CASCADE,
+    FOREIGN KEY (llm_model_id) REFERENCES llm_models(id)
+);
+
+CREATE TABLE IF NOT EXISTS global_aggregations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    llm_model_id INTEGER,
+    time_bucket TEXT NOT NULL,
+    bucket_start_date TEXT,
+    emotion_averages TEXT NOT NULL,
+    gauge_values TEXT,
+    computed_at TEXT DEFAULT (datetime('now')),
+    FOREIGN KEY (llm_model_id) REFERENCES llm_models(id)
+);
+
+CREATE TABLE IF NOT EXISTS crawler_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at TEXT DEFAULT (datetime('now')),
+    completed_at TEXT,
+    status TEXT DEFAULT 'running',
+    tweets_fetched INTEGER DEFAULT 0,
+    tweets_analyzed INTEGER DEFAULT 0,
+    errors_count INTEGER DEFAULT 0,
+    error_details TEXT
+);
+
+CREATE TABLE IF NOT EXISTS api_errors (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    error_type TEXT NOT NULL,
+    error_message TEXT,
+    error_code TEXT,
+    endpoint TEXT,
+    occurred_at TEXT DEFAULT (datetime('now')),
+    resolved INTEGER DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS configurations (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TEXT DEFAULT (datetime('now')),
+    updated_by TEXT
+);
+
+CREATE TABLE IF NOT EXISTS analysis_queue (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tweet_id INTEGER NOT NULL,
+    llm_model_id INTEGER,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempt_count INTEGER DEFAULT 0,
+    last_error TEXT,
+    enqueued_at TEXT DEFAULT (datetime('now')),
+    updated_at TEXT DEFAULT (datetime('now')),
+    UNIQUE(tweet_id, llm_model_id),
+    FOREIGN KEY (tweet_id) REFERENCES tweets(id) ON DELETE CASCADE,
+    FOREIGN KEY (llm_model_id) REFERENCES llm_models(id)
+);
+
+CREATE TABLE IF NOT EXISTS crawler_checkpoints (
+    twitter_user_id INTEGER PRIMARY KEY,
+    last_tweet_timestamp TEXT NOT NULL,
+    updated_at TEXT DEFAULT (datetime('now')),
+    FOREIGN KEY (twitter_user_id) REFERENCES twitter_users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS reanalysis_requests (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    request_type TEXT NOT NULL,
+    tweet_id INTEGER,
+    twitter_user_id INTEGER,
+    status TEXT NOT NULL DEFAULT 'pending',
+    requested_at TEXT DEFAULT (datetime('now')),
+    processed_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_analysis_queue_status ON analysis_queue(status);
+CREATE INDEX IF NOT EXISTS idx_analysis_queue_tweet ON analysis_queue(tweet_id);
+CREATE INDEX IF NOT EXISTS idx_reanalysis_status ON reanalysis_requests(status);
@@ -0,0 +1,18 @@
+-- Resumable per-user fetches and the daemon's next scheduled run
+
+CREATE TABLE IF NOT EXISTS crawler_fetch_progress (
+    twitter_user_id INTEGER PRIMARY KEY,
+    start_time TEXT NOT NULL,
+    end_time TEXT,
+    pagination_token TEXT NOT NULL,
+    newest_timestamp TEXT,
+    updated_at TEXT DEFAULT (datetime('now')),
+    FOREIGN KEY (twitter_user_id) REFERENCES twitter_users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS crawler_schedule (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    schedule TEXT NOT NULL,
+    next_scheduled_run TEXT,
+    updated_at TEXT DEFAULT (datetime('now'))
+);
@@ -0,0 +1,8 @@
+-- Live progress written while a crawl cycle runs
+
+ALTER TABLE crawler_runs ADD COLUMN users_total INTEGER DEFAULT 0;
+ALTER TABLE crawler_runs ADD COLUMN users_completed INTEGER DEFAULT 0;
+ALTER TABLE crawler_runs ADD COLUMN current_user TEXT;
+ALTER TABLE crawler_runs ADD COLUMN jobs_queued INTEGER DEFAULT 0;
+ALTER TABLE crawler_runs ADD COLUMN eta_at TEXT;
+ALTER TABLE crawler_runs ADD COLUMN progress_updated_at TEXT;
@@ -0,0 +1,21 @@
+-- Per-user outcome of each crawl run
+
+CREATE TABLE IF NOT EXISTS crawler_run_users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_id INTEGER NOT NULL,
+    twitter_user_id INTEGER NOT NULL,
+    status TEXT NOT NULL, -- completed, failed, not_found, cancelled
+    tweets_fetched INTEGER DEFAULT 0,
+    pages_fetched INTEGER DEFAULT 0,
+    api_calls INTEGER DEFAULT 0,
+    duration_ms INTEGER DEFAULT 0,
+    api_error_id INTEGER,
+    error_message TEXT,
+    completed_at TEXT DEFAULT (datetime('now')),
+    UNIQUE(run_id, twitter_user_id),
+    FOREIGN KEY (run_id) REFERENCES crawler_runs(id) ON DELETE CASCADE,
+    FOREIGN KEY (twitter_user_id) REFERENCES twitter_users(id) ON DELETE CASCADE,
+    FOREIGN KEY (api_error_id) REFERENCES api_errors(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_crawler_run_users_user ON crawler_run_users(twitter_user_id, run_id);
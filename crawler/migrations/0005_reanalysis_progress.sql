@@ -0,0 +1,6 @@
+-- Chunked, resumable processing of `user` and `all` reanalysis requests
+
+ALTER TABLE reanalysis_requests ADD COLUMN cursor_tweet_id INTEGER;
+ALTER TABLE reanalysis_requests ADD COLUMN tweets_total INTEGER;
+ALTER TABLE reanalysis_requests ADD COLUMN tweets_processed INTEGER DEFAULT 0;
+ALTER TABLE reanalysis_requests ADD COLUMN progress_percent REAL DEFAULT 0;
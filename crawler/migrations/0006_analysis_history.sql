@@ -0,0 +1,21 @@
+-- Reanalysis keeps earlier analyses as history, and only the newest per tweet and model is current
+
+ALTER TABLE sentiment_analyses ADD COLUMN model_version TEXT;
+ALTER TABLE sentiment_analyses ADD COLUMN analysis_version INTEGER DEFAULT 1;
+ALTER TABLE sentiment_analyses ADD COLUMN is_current INTEGER DEFAULT 1;
+
+CREATE INDEX IF NOT EXISTS idx_sentiment_analyses_tweet_model ON sentiment_analyses(tweet_id, llm_model_id);
+
+UPDATE sentiment_analyses
+SET analysis_version = (
+        SELECT COUNT(*) FROM sentiment_analyses earlier
+        WHERE earlier.tweet_id = sentiment_analyses.tweet_id
+          AND earlier.llm_model_id = sentiment_analyses.llm_model_id
+          AND earlier.id <= sentiment_analyses.id
+    ),
+    is_current = NOT EXISTS (
+        SELECT 1 FROM sentiment_analyses later
+        WHERE later.tweet_id = sentiment_analyses.tweet_id
+          AND later.llm_model_id = sentiment_analyses.llm_model_id
+          AND later.id > sentiment_analyses.id
+    );
@@ -0,0 +1,9 @@
+-- Reanalysis requests narrowed by model, tweet time range, failed jobs or a missing emotion, and dry runs that only estimate
+
+ALTER TABLE reanalysis_requests ADD COLUMN llm_model_id INTEGER;
+ALTER TABLE reanalysis_requests ADD COLUMN tweets_from TEXT;
+ALTER TABLE reanalysis_requests ADD COLUMN tweets_to TEXT;
+ALTER TABLE reanalysis_requests ADD COLUMN only_failed INTEGER DEFAULT 0;
+ALTER TABLE reanalysis_requests ADD COLUMN missing_emotion TEXT;
+ALTER TABLE reanalysis_requests ADD COLUMN dry_run INTEGER DEFAULT 0;
+ALTER TABLE reanalysis_requests ADD COLUMN jobs_estimate INTEGER;
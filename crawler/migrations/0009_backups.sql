@@ -0,0 +1,18 @@
+-- Database snapshots uploaded to S3-compatible storage, including the ones requested from the admin panel
+
+CREATE TABLE IF NOT EXISTS backups (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    status TEXT NOT NULL DEFAULT 'requested', -- requested, running, completed, failed, expired
+    triggered_by TEXT NOT NULL DEFAULT 'admin', -- admin, schedule, manual
+    requested_at TEXT DEFAULT (datetime('now')),
+    started_at TEXT,
+    completed_at TEXT,
+    progress_percent INTEGER DEFAULT 0,
+    object_key TEXT,
+    database_bytes INTEGER,
+    compressed_bytes INTEGER,
+    sha256 TEXT, -- of the compressed object
+    error_message TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_backups_status ON backups(status);
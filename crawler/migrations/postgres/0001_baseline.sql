@@ -0,0 +1,177 @@
+-- Baseline PostgreSQL schema, equivalent to SQLite migrations 0001-0004.
+-- The Node backend only speaks SQLite, so on PostgreSQL the crawler owns every
+-- table, including the ones Node creates from backend/src/db/schema.sql.
+
+CREATE TABLE IF NOT EXISTS twitter_users (
+    id BIGSERIAL PRIMARY KEY,
+    twitter_id TEXT UNIQUE NOT NULL,
+    username TEXT NOT NULL,
+    display_name TEXT NOT NULL,
+    bio TEXT,
+    avatar_url TEXT,
+    follower_count BIGINT DEFAULT 0,
+    following_count BIGINT DEFAULT 0,
+    is_active BOOLEAN DEFAULT TRUE,
+    created_at TIMESTAMPTZ DEFAULT now(),
+    updated_at TIMESTAMPTZ DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS tweets (
+    id BIGSERIAL PRIMARY KEY,
+    twitter_user_id BIGINT NOT NULL REFERENCES twitter_users(id) ON DELETE CASCADE,
+    tweet_id TEXT UNIQUE NOT NULL,
+    content TEXT NOT NULL,
+    tweet_timestamp TIMESTAMPTZ NOT NULL,
+    engagement_metrics TEXT,
+    is_retweet BOOLEAN DEFAULT FALSE,
+    is_reply BOOLEAN DEFAULT FALSE,
+    created_at TIMESTAMPTZ DEFAULT now(),
+    updated_at TIMESTAMPTZ DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS llm_models (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    version TEXT,
+    provider TEXT,
+    huggingface_model_id TEXT,
+    is_local BOOLEAN DEFAULT TRUE,
+    is_enabled BOOLEAN DEFAULT FALSE,
+    download_status TEXT DEFAULT 'not_downloaded',
+    download_progress BIGINT DEFAULT 0,
+    disk_size_bytes BIGINT,
+    created_at TIMESTAMPTZ DEFAULT now(),
+    updated_at TIMESTAMPTZ DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS sentiment_analyses (
+    id BIGSERIAL PRIMARY KEY,
+    tweet_id BIGINT NOT NULL REFERENCES tweets(id) ON DELETE CASCADE,
+    llm_model_id BIGINT NOT NULL REFERENCES llm_models(id),
+    emotion_scores TEXT NOT NULL,
+    raw_llm_response TEXT,
+    analyzed_at TIMESTAMPTZ DEFAULT now(),
+    analysis_duration_ms BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS user_aggregations (
+    id BIGSERIAL PRIMARY KEY,
+    twitter_user_id BIGINT NOT NULL REFERENCES twitter_users(id) ON DELETE CASCADE,
+    llm_model_id BIGINT REFERENCES llm_models(id),
+    time_bucket TEXT NOT NULL,
+    bucket_start_date TEXT,
+    emotion_averages TEXT NOT NULL,
+    emotion_medians TEXT,
+    emotion_modes TEXT,
+    tweet_count BIGINT DEFAULT 0,
+    computed_at TIMESTAMPTZ DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS global_aggregations (
+    id BIGSERIAL PRIMARY KEY,
+    llm_model_id BIGINT REFERENCES llm_models(id),
+    time_bucket TEXT NOT NULL,
+    bucket_start_date TEXT,
+    emotion_averages TEXT NOT NULL,
+    gauge_values TEXT,
+    computed_at TIMESTAMPTZ DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS crawler_runs (
+    id BIGSERIAL PRIMARY KEY,
+    started_at TIMESTAMPTZ DEFAULT now(),
+    completed_at TIMESTAMPTZ,
+    status TEXT DEFAULT 'running',
+    tweets_fetched BIGINT DEFAULT 0,
+    tweets_analyzed BIGINT DEFAULT 0,
+    errors_count BIGINT DEFAULT 0,
+    error_details TEXT,
+    users_total BIGINT DEFAULT 0,
+    users_completed BIGINT DEFAULT 0,
+    "current_user" TEXT,
+    jobs_queued BIGINT DEFAULT 0,
+    eta_at TIMESTAMPTZ,
+    progress_updated_at TIMESTAMPTZ
+);
+
+CREATE TABLE IF NOT EXISTS api_errors (
+    id BIGSERIAL PRIMARY KEY,
+    error_type TEXT NOT NULL,
+    error_message TEXT,
+    error_code TEXT,
+    endpoint TEXT,
+    occurred_at TIMESTAMPTZ DEFAULT now(),
+    resolved BOOLEAN DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS configurations (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TIMESTAMPTZ DEFAULT now(),
+    updated_by TEXT
+);
+
+CREATE TABLE IF NOT EXISTS analysis_queue (
+    id BIGSERIAL PRIMARY KEY,
+    tweet_id BIGINT NOT NULL REFERENCES tweets(id) ON DELETE CASCADE,
+    llm_model_id BIGINT REFERENCES llm_models(id),
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempt_count BIGINT DEFAULT 0,
+    last_error TEXT,
+    enqueued_at TIMESTAMPTZ DEFAULT now(),
+    updated_at TIMESTAMPTZ DEFAULT now(),
+    UNIQUE (tweet_id, llm_model_id)
+);
+
+CREATE TABLE IF NOT EXISTS crawler_checkpoints (
+    twitter_user_id BIGINT PRIMARY KEY REFERENCES twitter_users(id) ON DELETE CASCADE,
+    last_tweet_timestamp TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS reanalysis_requests (
+    id BIGSERIAL PRIMARY KEY,
+    request_type TEXT NOT NULL,
+    tweet_id BIGINT,
+    twitter_user_id BIGINT,
+    status TEXT NOT NULL DEFAULT 'pending',
+    requested_at TIMESTAMPTZ DEFAULT now(),
+    processed_at TIMESTAMPTZ
+);
+
+CREATE TABLE IF NOT EXISTS crawler_fetch_progress (
+    twitter_user_id BIGINT PRIMARY KEY REFERENCES twitter_users(id) ON DELETE CASCADE,
+    start_time TIMESTAMPTZ NOT NULL,
+    end_time TIMESTAMPTZ,
+    pagination_token TEXT NOT NULL,
+    newest_timestamp TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS crawler_schedule (
+    id BIGINT PRIMARY KEY CHECK (id = 1),
+    schedule TEXT NOT NULL,
+    next_scheduled_run TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS crawler_run_users (
+    id BIGSERIAL PRIMARY KEY,
+    run_id BIGINT NOT NULL REFERENCES crawler_runs(id) ON DELETE CASCADE,
+    twitter_user_id BIGINT NOT NULL REFERENCES twitter_users(id) ON DELETE CASCADE,
+    status TEXT NOT NULL,
+    tweets_fetched BIGINT DEFAULT 0,
+    pages_fetched BIGINT DEFAULT 0,
+    api_calls BIGINT DEFAULT 0,
+    duration_ms BIGINT DEFAULT 0,
+    api_error_id BIGINT REFERENCES api_errors(id) ON DELETE SET NULL,
+    error_message TEXT,
+    completed_at TIMESTAMPTZ DEFAULT now(),
+    UNIQUE (run_id, twitter_user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_twitter_users_username ON twitter_users (lower(username));
+CREATE INDEX IF NOT EXISTS idx_analysis_queue_status ON analysis_queue (status);
+CREATE INDEX IF NOT EXISTS idx_analysis_queue_tweet ON analysis_queue (tweet_id);
+CREATE INDEX IF NOT EXISTS idx_reanalysis_status ON reanalysis_requests (status);
+CREATE INDEX IF NOT EXISTS idx_crawler_run_users_user ON crawler_run_users (twitter_user_id, run_id);
@@ -0,0 +1,6 @@
+-- Chunked, resumable processing of `user` and `all` reanalysis requests
+
+ALTER TABLE reanalysis_requests ADD COLUMN IF NOT EXISTS cursor_tweet_id BIGINT;
+ALTER TABLE reanalysis_requests ADD COLUMN IF NOT EXISTS tweets_total BIGINT;
+ALTER TABLE reanalysis_requests ADD COLUMN IF NOT EXISTS tweets_processed BIGINT DEFAULT 0;
+ALTER TABLE reanalysis_requests ADD COLUMN IF NOT EXISTS progress_percent DOUBLE PRECISION DEFAULT 0;
@@ -0,0 +1,18 @@
+-- Reanalysis keeps earlier analyses as history, and only the newest per tweet and model is current
+
+ALTER TABLE sentiment_analyses ADD COLUMN IF NOT EXISTS model_version TEXT;
+ALTER TABLE sentiment_analyses ADD COLUMN IF NOT EXISTS analysis_version BIGINT DEFAULT 1;
+ALTER TABLE sentiment_analyses ADD COLUMN IF NOT EXISTS is_current BOOLEAN DEFAULT TRUE;
+
+CREATE INDEX IF NOT EXISTS idx_sentiment_analyses_tweet_model ON sentiment_analyses (tweet_id, llm_model_id);
+
+UPDATE sentiment_analyses sa
+SET analysis_version = ranked.version,
+    is_current = ranked.version = ranked.versions
+FROM (
+    SELECT id,
+           ROW_NUMBER() OVER (PARTITION BY tweet_id, llm_model_id ORDER BY id) AS version,
+           COUNT(*) OVER (PARTITION BY tweet_id, llm_model_id) AS versions
+    FROM sentiment_analyses
+) ranked
+WHERE ranked.id = sa.id;
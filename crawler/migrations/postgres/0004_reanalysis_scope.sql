@@ -0,0 +1,9 @@
+-- Reanalysis requests narrowed by model, tweet time range, failed jobs or a missing emotion, and dry runs that only estimate
+
+ALTER TABLE reanalysis_requests ADD COLUMN IF NOT EXISTS llm_model_id BIGINT;
+ALTER TABLE reanalysis_requests ADD COLUMN IF NOT EXISTS tweets_from TIMESTAMPTZ;
+ALTER TABLE reanalysis_requests ADD COLUMN IF NOT EXISTS tweets_to TIMESTAMPTZ;
+ALTER TABLE reanalysis_requests ADD COLUMN IF NOT EXISTS only_failed BOOLEAN DEFAULT FALSE;
+ALTER TABLE reanalysis_requests ADD COLUMN IF NOT EXISTS missing_emotion TEXT;
+ALTER TABLE reanalysis_requests ADD COLUMN IF NOT EXISTS dry_run BOOLEAN DEFAULT FALSE;
+ALTER TABLE reanalysis_requests ADD COLUMN IF NOT EXISTS jobs_estimate BIGINT;
@@ -0,0 +1,4 @@
+-- Reanalysis requests that could not be processed end as `failed` with the reason, and every request counts the jobs it queued
+
+ALTER TABLE reanalysis_requests ADD COLUMN IF NOT EXISTS error_message TEXT;
+ALTER TABLE reanalysis_requests ADD COLUMN IF NOT EXISTS jobs_enqueued BIGINT DEFAULT 0;
@@ -0,0 +1,3 @@
+-- Reanalysis requests are claimed with a lease, so a daemon and a one-shot crawl never walk the same request
+
+ALTER TABLE reanalysis_requests ADD COLUMN IF NOT EXISTS lease_expires_at TIMESTAMPTZ;
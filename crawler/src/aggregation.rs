@@ -0,0 +1,273 @@
+//! Pre-computed aggregations for the dashboard
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Utc};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::storage::Storage;
+use crate::error::CrawlerError;
+use crate::models::{AnalysisScoreRow, GaugeConfig, GlobalAggregation, UserAggregation};
+
+const TIME_BUCKETS: &[&str] = &["weekly", "monthly", "yearly", "all_time"];
+
+#[derive(Debug, Default)]
+pub struct AggregationReport {
+    pub user_rows: usize,
+    pub global_rows: usize,
+}
+
+/// Scores collected for one (scope, model, bucket)
+#[derive(Default)]
+struct BucketScores {
+    tweet_ids: BTreeSet<i64>,
+    values: BTreeMap<String, Vec<f64>>,
+}
+
+impl BucketScores {
+    fn add(&mut self, tweet_id: i64, scores: &BTreeMap<String, f64>) {
+        self.tweet_ids.insert(tweet_id);
+        for (emotion, value) in scores {
+            self.values.entry(emotion.clone()).or_default().push(*value);
+        }
+    }
+}
+
+type BucketKey = (Option<i64>, &'static str, Option<String>);
+
+pub fn recompute_aggregations(database: &dyn Storage) -> Result<AggregationReport, CrawlerError> {
+    let rows = database.load_analysis_scores()?;
+    let gauges = database.load_gauges()?;
+    let (user_rows, global_rows) = build_aggregations(&rows, &gauges);
+
+    database.replace_aggregations(&user_rows, &global_rows)?;
+
+    let report = AggregationReport {
+        user_rows: user_rows.len(),
+        global_rows: global_rows.len(),
+    };
+    info!(
+        "Aggregations recomputed: {} user rows, {} global rows",
+        report.user_rows, report.global_rows
+    );
+    Ok(report)
+}
+
+fn build_aggregations(
+    rows: &[AnalysisScoreRow],
+    gauges: &[GaugeConfig],
+) -> (Vec<UserAggregation>, Vec<GlobalAggregation>) {
+    let mut per_user: BTreeMap<(i64, BucketKey), BucketScores> = BTreeMap::new();
+    let mut global: BTreeMap<BucketKey, BucketScores> = BTreeMap::new();
+
+    for row in rows {
+        let Some(timestamp) = parse_timestamp(&row.tweet_timestamp) else {
+            warn!("Skipping analysis with unparseable timestamp for tweet {}", row.tweet_id);
+            continue;
+        };
+        let Ok(scores) = serde_json::from_str::<BTreeMap<String, f64>>(&row.emotion_scores) else {
+            warn!("Skipping analysis with invalid scores for tweet {}", row.tweet_id);
+            continue;
+        };
+
+        for bucket in TIME_BUCKETS {
+            let start = bucket_start(bucket, timestamp);
+            for model in [Some(row.llm_model_id), None] {
+                let key = (model, *bucket, start.clone());
+                per_user
+                    .entry((row.twitter_user_id, key.clone()))
+                    .or_default()
+                    .add(row.tweet_id, &scores);
+                global.entry(key).or_default().add(row.tweet_id, &scores);
+            }
+        }
+    }
+
+    let user_rows = per_user
+        .into_iter()
+        .map(|((twitter_user_id, (llm_model_id, time_bucket, bucket_start_date)), scores)| {
+            UserAggregation {
+                twitter_user_id,
+                llm_model_id,
+                time_bucket,
+                bucket_start_date,
+                emotion_averages: json!(map_values(&scores.values, average)).to_string(),
+                emotion_medians: json!(map_values(&scores.values, median)).to_string(),
+                emotion_modes: json!(map_values(&scores.values, mode)).to_string(),
+                tweet_count: scores.tweet_ids.len() as i64,
+            }
+        })
+        .collect();
+
+    let global_rows = global
+        .into_iter()
+        .map(|((llm_model_id, time_bucket, bucket_start_date), scores)| {
+            let averages = map_values(&scores.values, average);
+            let gauge_values: BTreeMap<&str, i64> = gauges
+                .iter()
+                .map(|gauge| (gauge.name.as_str(), gauge_value(gauge, &averages)))
+                .collect();
+            GlobalAggregation {
+                llm_model_id,
+                time_bucket,
+                bucket_start_date,
+                emotion_averages: json!(averages).to_string(),
+                gauge_values: json!(gauge_values).to_string(),
+            }
+        })
+        .collect();
+
+    (user_rows, global_rows)
+}
+
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(|naive| naive.and_utc())
+        })
+}
+
+/// First day of the bucket containing `timestamp`; `None` for `all_time`
+fn bucket_start(bucket: &str, timestamp: DateTime<Utc>) -> Option<String> {
+    let date = timestamp.date_naive();
+    let start = match bucket {
+        "weekly" => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+        "monthly" => NaiveDate::from_ymd_opt(date.year(), date.month(), 1)?,
+        "yearly" => NaiveDate::from_ymd_opt(date.year(), 1, 1)?,
+        _ => return None,
+    };
+    Some(start.format("%Y-%m-%d").to_string())
+}
+
+fn map_values(
+    values: &BTreeMap<String, Vec<f64>>,
+    reduce: fn(&[f64]) -> i64,
+) -> BTreeMap<String, i64> {
+    values
+        .iter()
+        .map(|(emotion, scores)| (emotion.clone(), reduce(scores)))
+        .collect()
+}
+
+fn average(values: &[f64]) -> i64 {
+    if values.is_empty() {
+        return 0;
+    }
+    (values.iter().sum::<f64>() / values.len() as f64).round() as i64
+}
+
+fn median(values: &[f64]) -> i64 {
+    if values.is_empty() {
+        return 0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        sorted[mid].round() as i64
+    } else {
+        ((sorted[mid - 1] + sorted[mid]) / 2.0).round() as i64
+    }
+}
+
+/// Most common rounded score; ties go to the lowest score
+fn mode(values: &[f64]) -> i64 {
+    let mut counts: HashMap<i64, usize> = HashMap::new();
+    for value in values {
+        *counts.entry(value.round() as i64).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by(|(a_value, a_count), (b_value, b_count)| {
+            a_count.cmp(b_count).then(b_value.cmp(a_value))
+        })
+        .map(|(value, _)| value)
+        .unwrap_or(0)
+}
+
+/// Mirrors `calculateGaugeValue` in the backend's public routes
+fn gauge_value(gauge: &GaugeConfig, averages: &BTreeMap<String, i64>) -> i64 {
+    let mut sum = 0;
+    let mut count = 0;
+    for emotion in &gauge.emotions {
+        if let Some(value) = averages.get(emotion) {
+            sum += value;
+            count += 1;
+        }
+    }
+    for emotion in &gauge.inverted_emotions {
+        if let Some(value) = averages.get(emotion) {
+            sum += 100 - value;
+            count += 1;
+        }
+    }
+    if count == 0 {
+        return 50;
+    }
+    (sum as f64 / count as f64).round() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn row(user: i64, tweet: i64, model: i64, timestamp: &str, scores: &str) -> AnalysisScoreRow {
+        AnalysisScoreRow {
+            twitter_user_id: user,
+            tweet_id: tweet,
+            llm_model_id: model,
+            tweet_timestamp: timestamp.to_string(),
+            emotion_scores: scores.to_string(),
+        }
+    }
+
+    #[test]
+    fn bucket_starts_align_to_calendar() {
+        let timestamp = Utc.with_ymd_and_hms(2024, 1, 10, 12, 0, 0).unwrap();
+        assert_eq!(bucket_start("weekly", timestamp).as_deref(), Some("2024-01-08"));
+        assert_eq!(bucket_start("monthly", timestamp).as_deref(), Some("2024-01-01"));
+        assert_eq!(bucket_start("yearly", timestamp).as_deref(), Some("2024-01-01"));
+        assert_eq!(bucket_start("all_time", timestamp), None);
+    }
+
+    #[test]
+    fn statistics_match_backend_rounding() {
+        assert_eq!(average(&[10.0, 20.0, 25.0]), 18);
+        assert_eq!(median(&[10.0, 25.0, 20.0, 40.0]), 23);
+        assert_eq!(mode(&[10.0, 20.0, 20.0, 10.0, 5.0]), 10);
+    }
+
+    #[test]
+    fn build_combines_models_and_computes_gauges() {
+        let rows = vec![
+            row(1, 1, 1, "2024-01-10T12:00:00+00:00", r#"{"happy":80,"sad":20}"#),
+            row(1, 1, 2, "2024-01-10T12:00:00+00:00", r#"{"happy":60,"sad":40}"#),
+        ];
+        let gauges = vec![GaugeConfig {
+            name: "Mood Gauge".to_string(),
+            emotions: vec!["happy".to_string()],
+            inverted_emotions: vec!["sad".to_string()],
+        }];
+
+        let (user_rows, global_rows) = build_aggregations(&rows, &gauges);
+
+        let combined = user_rows
+            .iter()
+            .find(|row| row.llm_model_id.is_none() && row.time_bucket == "all_time")
+            .expect("combined all_time row");
+        assert_eq!(combined.tweet_count, 1);
+        assert_eq!(combined.emotion_averages, r#"{"happy":70,"sad":30}"#);
+
+        let global = global_rows
+            .iter()
+            .find(|row| row.llm_model_id.is_none() && row.time_bucket == "all_time")
+            .expect("global all_time row");
+        assert_eq!(global.gauge_values, r#"{"Mood Gauge":70}"#);
+    }
+}
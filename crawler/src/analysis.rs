@@ -0,0 +1,343 @@
+//! Analysis worker: drains `analysis_queue` through the configured LLMs
+
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::db_pool::DbPool;
+use crate::error::CrawlerError;
+use crate::models::AnalysisJob;
+use crate::shutdown::Shutdown;
+use crate::telemetry;
+
+const HF_INFERENCE_URL: &str = "https://api-inference.huggingface.co/models";
+
+/// Emotions scored when the `emotions` configuration is missing
+const DEFAULT_EMOTIONS: &[&str] = &[
+    "happy",
+    "sad",
+    "angry",
+    "fearful",
+    "hatred",
+    "thankful",
+    "excited",
+    "hopeful",
+    "frustrated",
+    "sarcastic",
+    "inspirational",
+    "anxious",
+];
+
+/// Attempts before a job is left in `failed`
+const MAX_ATTEMPTS: i64 = 3;
+
+#[derive(Debug, Clone)]
+pub struct AnalysisOptions {
+    pub batch_size: i64,
+    /// Stop after this many jobs (all pending jobs when `None`)
+    pub max_jobs: Option<usize>,
+    /// Only process jobs for this model
+    pub llm_model_id: Option<i64>,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 20,
+            max_jobs: None,
+            llm_model_id: None,
+        }
+    }
+}
+
+enum JobOutcome {
+    Completed,
+    Failed,
+    /// Handed back to the queue because the drain timeout expired
+    Released,
+}
+
+#[derive(Debug, Default)]
+pub struct AnalysisReport {
+    pub completed: u64,
+    pub failed: u64,
+}
+
+pub struct AnalysisOutput {
+    pub emotion_scores: BTreeMap<String, i64>,
+    pub raw_response: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeneratedText {
+    generated_text: String,
+}
+
+/// Sends prompts to the Hugging Face inference API
+pub struct HuggingFaceAnalyzer {
+    client: Client,
+    token: Option<String>,
+}
+
+impl HuggingFaceAnalyzer {
+    pub fn new(token: Option<String>) -> Result<Self, CrawlerError> {
+        let client = Client::builder()
+            .user_agent("twitter-feels-crawler/0.1")
+            .build()?;
+
+        Ok(Self { client, token })
+    }
+
+    pub async fn analyze(
+        &self,
+        model: &str,
+        text: &str,
+        emotions: &[String],
+    ) -> Result<AnalysisOutput, CrawlerError> {
+        let mut request = self
+            .client
+            .post(format!("{HF_INFERENCE_URL}/{model}"))
+            .json(&json!({
+                "inputs": build_prompt(text, emotions),
+                "parameters": { "max_new_tokens": 256, "return_full_text": false },
+            }));
+
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        if !status.is_success() {
+            return Err(match status {
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => CrawlerError::Authentication(
+                    format!("Hugging Face auth error for {model}"),
+                ),
+                _ => CrawlerError::LlmInference(format!("{model} returned {status}: {body}")),
+            });
+        }
+
+        let generated: Vec<GeneratedText> = serde_json::from_str(&body).map_err(|err| {
+            CrawlerError::LlmInference(format!("Failed to parse inference response: {err}"))
+        })?;
+        let raw_response = generated
+            .into_iter()
+            .next()
+            .map(|item| item.generated_text)
+            .unwrap_or_default();
+
+        let emotion_scores = parse_emotion_scores(&raw_response, emotions)?;
+        Ok(AnalysisOutput {
+            emotion_scores,
+            raw_response,
+        })
+    }
+}
+
+pub async fn run_analysis_worker(
+    pool: &DbPool,
+    analyzer: &HuggingFaceAnalyzer,
+    options: &AnalysisOptions,
+    shutdown: &Shutdown,
+) -> Result<AnalysisReport, CrawlerError> {
+    let mut emotions = pool.run(|database| database.load_emotion_names()).await?;
+    if emotions.is_empty() {
+        emotions = DEFAULT_EMOTIONS.iter().map(|name| name.to_string()).collect();
+    }
+
+    let mut report = AnalysisReport::default();
+    loop {
+        let processed = (report.completed + report.failed) as usize;
+        let remaining = options.max_jobs.map(|max| max.saturating_sub(processed));
+        if remaining == Some(0) || shutdown.is_requested() {
+            break;
+        }
+
+        let limit = remaining.map_or(options.batch_size, |left| {
+            options.batch_size.min(left as i64)
+        });
+        let model_id = options.llm_model_id;
+        let jobs = pool
+            .run(move |database| database.claim_analysis_jobs(limit, model_id))
+            .await?;
+        if jobs.is_empty() {
+            break;
+        }
+
+        for job in jobs {
+            // Finish the job in flight when shutdown arrives; hand back the rest.
+            if shutdown.is_requested() {
+                pool.run(move |database| database.release_analysis_job(job.id))
+                    .await?;
+                continue;
+            }
+
+            let span = info_span!(
+                "analysis_job",
+                job_id = job.id,
+                tweet_id = job.tweet_id,
+                model_id = job.llm_model_id
+            );
+            let outcome = process_job(pool, analyzer, &job, &emotions, shutdown)
+                .instrument(span)
+                .await?;
+            match outcome {
+                JobOutcome::Completed => report.completed += 1,
+                JobOutcome::Failed => report.failed += 1,
+                JobOutcome::Released => {}
+            }
+        }
+    }
+
+    info!(
+        "Analysis worker finished: {} completed, {} failed",
+        report.completed, report.failed
+    );
+
+    Ok(report)
+}
+
+async fn process_job(
+    pool: &DbPool,
+    analyzer: &HuggingFaceAnalyzer,
+    job: &AnalysisJob,
+    emotions: &[String],
+    shutdown: &Shutdown,
+) -> Result<JobOutcome, CrawlerError> {
+    let started = Instant::now();
+    let result = tokio::select! {
+        biased;
+        _ = shutdown.drain_expired() => {
+            let job_id = job.id;
+            pool.run(move |database| database.release_analysis_job(job_id))
+                .await?;
+            return Ok(JobOutcome::Released);
+        }
+        result = analyzer.analyze(&job.model_name, &job.content, emotions) => result,
+    };
+
+    match result {
+        Ok(output) => {
+            let scores = serde_json::to_string(&output.emotion_scores).map_err(|err| {
+                CrawlerError::LlmInference(format!("Failed to encode scores: {err}"))
+            })?;
+            let job_done = job.clone();
+            let processing_time_ms = started.elapsed().as_millis() as i64;
+            pool.run(move |database| {
+                database.complete_analysis_job(
+                    &job_done,
+                    &scores,
+                    &output.raw_response,
+                    processing_time_ms,
+                )
+            })
+            .await?;
+            telemetry::record_job_completed(job.llm_model_id, started.elapsed());
+            Ok(JobOutcome::Completed)
+        }
+        Err(error) => {
+            warn!("Analysis job {} failed: {}", job.id, error);
+            let retry = job.attempt_count < MAX_ATTEMPTS;
+            let (job_id, message) = (job.id, error.to_string());
+            pool.run(move |database| database.fail_analysis_job(job_id, &message, retry))
+                .await?;
+            telemetry::record_job_failed(job.llm_model_id);
+            Ok(JobOutcome::Failed)
+        }
+    }
+}
+
+fn build_prompt(text: &str, emotions: &[String]) -> String {
+    format!(
+        "Rate how strongly the following tweet expresses each emotion on a scale from 0 to 100.\n\
+         Emotions: {}\n\
+         Respond with a single JSON object mapping each emotion to an integer score.\n\n\
+         Tweet: {text}\n\nJSON:",
+        emotions.join(", ")
+    )
+}
+
+/// Pull the first JSON object out of a completion and clamp each score to 0-100
+fn parse_emotion_scores(
+    response: &str,
+    emotions: &[String],
+) -> Result<BTreeMap<String, i64>, CrawlerError> {
+    let start = response.find('{');
+    let end = response.rfind('}');
+    let (Some(start), Some(end)) = (start, end) else {
+        return Err(CrawlerError::LlmInference(
+            "No JSON object in model response".to_string(),
+        ));
+    };
+    if end < start {
+        return Err(CrawlerError::LlmInference(
+            "No JSON object in model response".to_string(),
+        ));
+    }
+
+    let parsed: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(&response[start..=end]).map_err(|err| {
+            CrawlerError::LlmInference(format!("Invalid JSON in model response: {err}"))
+        })?;
+
+    let mut scores = BTreeMap::new();
+    for emotion in emotions {
+        let value = parsed
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(emotion))
+            .and_then(|(_, value)| value.as_f64());
+        if let Some(value) = value {
+            scores.insert(emotion.clone(), value.round().clamp(0.0, 100.0) as i64);
+        }
+    }
+
+    if scores.is_empty() {
+        return Err(CrawlerError::LlmInference(
+            "Model response did not score any configured emotion".to_string(),
+        ));
+    }
+
+    for emotion in emotions {
+        scores.entry(emotion.clone()).or_insert(0);
+    }
+
+    Ok(scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emotions() -> Vec<String> {
+        vec!["happy".to_string(), "sad".to_string(), "angry".to_string()]
+    }
+
+    #[test]
+    fn parse_scores_from_noisy_completion() {
+        let response = "Sure! Here you go:\n{\"Happy\": 82.6, \"sad\": -4, \"angry\": 140}\nDone.";
+        let scores = parse_emotion_scores(response, &emotions()).expect("parse scores");
+
+        assert_eq!(scores["happy"], 83);
+        assert_eq!(scores["sad"], 0);
+        assert_eq!(scores["angry"], 100);
+    }
+
+    #[test]
+    fn parse_scores_fills_missing_emotions() {
+        let scores = parse_emotion_scores("{\"happy\": 10}", &emotions()).expect("parse scores");
+        assert_eq!(scores.len(), 3);
+        assert_eq!(scores["angry"], 0);
+    }
+
+    #[test]
+    fn parse_scores_rejects_unrelated_json() {
+        assert!(parse_emotion_scores("{\"joy\": 10}", &emotions()).is_err());
+        assert!(parse_emotion_scores("no json here", &emotions()).is_err());
+    }
+}
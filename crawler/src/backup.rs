@@ -0,0 +1,750 @@
+//! Online backups of the SQLite database to S3-compatible storage
+//!
+//! A backup copies the live database with SQLite's online backup API a few
+//! hundred pages at a time, so the crawler and the Node backend keep writing
+//! while it runs. The copy is gzip-compressed, uploaded, and recorded in the
+//! `backups` table, which the admin panel reads for progress and inserts
+//! `requested` rows into to trigger a backup. Afterwards only the newest
+//! `keep` archives under the prefix are kept in the bucket.
+//!
+//! Settings come from the admin panel's `s3_backup` configuration, with any
+//! `BACKUP_*` environment variable taking precedence. Backups need the SQLite
+//! backend; PostgreSQL has `pg_dump`.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::db_pool::DbPool;
+use crate::error::CrawlerError;
+use crate::s3::{S3Client, S3Object, S3Settings};
+use crate::schedule::CrawlSchedule;
+use crate::shutdown::Shutdown;
+use crate::telemetry;
+
+/// `configurations` key the admin panel stores backup settings under
+pub const CONFIGURATION_KEY: &str = "s3_backup";
+
+/// Archives are named `{prefix}twitter-feels-{timestamp}-{id}.db.gz`
+const ARCHIVE_NAME: &str = "twitter-feels-";
+const ARCHIVE_SUFFIX: &str = ".db.gz";
+
+const DEFAULT_PREFIX: &str = "backups/";
+const DEFAULT_KEEP: usize = 7;
+
+/// Pages copied per backup step; the write lock is free between steps
+const PAGES_PER_STEP: i32 = 256;
+const STEP_PAUSE: Duration = Duration::from_millis(10);
+const BUSY_PAUSE: Duration = Duration::from_millis(250);
+
+/// Share of `progress_percent` spent copying; compressing and uploading take the rest
+const SNAPSHOT_PERCENT: i64 = 80;
+
+/// `BACKUP_*` environment variables; each one overrides the admin panel's setting
+#[derive(Debug, Clone, Default)]
+pub struct BackupOverrides {
+    pub endpoint: Option<String>,
+    pub bucket: Option<String>,
+    pub region: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub prefix: Option<String>,
+    pub keep: Option<usize>,
+    /// `0` disables scheduled backups
+    pub interval_minutes: Option<u32>,
+}
+
+/// Resolved backup settings
+#[derive(Debug, Clone)]
+pub struct BackupSettings {
+    pub s3: S3Settings,
+    /// Key prefix inside the bucket, ending in `/` unless empty
+    pub prefix: String,
+    /// Archives kept in the bucket; `0` keeps every one
+    pub keep: usize,
+    /// `None` only backs up on request
+    pub schedule: Option<CrawlSchedule>,
+}
+
+impl BackupSettings {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} to s3://{}/{} via {}, keeping {}",
+            self.schedule
+                .as_ref()
+                .map_or_else(|| "on request".to_string(), |schedule| schedule.describe()),
+            self.s3.bucket,
+            self.prefix,
+            self.s3.endpoint,
+            if self.keep == 0 {
+                "every archive".to_string()
+            } else {
+                format!("the newest {}", self.keep)
+            }
+        )
+    }
+}
+
+/// Combine the admin panel's `s3_backup` settings with the environment
+///
+/// Returns `None` when backups are disabled or no bucket is configured.
+pub fn resolve_settings(
+    overrides: &BackupOverrides,
+    stored: Option<&Value>,
+) -> Result<Option<BackupSettings>, CrawlerError> {
+    let stored_text = |key: &str| {
+        stored
+            .and_then(|stored| stored.get(key))
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+    let pick = |value: &Option<String>, key: &str| value.clone().or_else(|| stored_text(key));
+
+    let enabled = overrides.bucket.is_some()
+        || stored
+            .and_then(|stored| stored.get("enabled"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+    let Some(bucket) = pick(&overrides.bucket, "bucketName").filter(|_| enabled) else {
+        return Ok(None);
+    };
+
+    let region = pick(&overrides.region, "region").unwrap_or_else(|| "us-east-1".to_string());
+    let endpoint = pick(&overrides.endpoint, "endpoint")
+        .unwrap_or_else(|| format!("https://s3.{region}.amazonaws.com"));
+    let (Some(access_key_id), Some(secret_access_key)) = (
+        pick(&overrides.access_key_id, "accessKeyId"),
+        pick(&overrides.secret_access_key, "secretAccessKey"),
+    ) else {
+        return Err(CrawlerError::Config(format!(
+            "S3 backup to bucket {bucket} has no access key id or secret access key"
+        )));
+    };
+
+    let mut prefix =
+        pick(&overrides.prefix, "prefix").unwrap_or_else(|| DEFAULT_PREFIX.to_string());
+    if !prefix.is_empty() && !prefix.ends_with('/') {
+        prefix.push('/');
+    }
+    let keep = overrides
+        .keep
+        .or_else(|| {
+            stored
+                .and_then(|stored| stored.get("retentionCount"))
+                .and_then(Value::as_u64)
+                .map(|keep| keep as usize)
+        })
+        .unwrap_or(DEFAULT_KEEP);
+
+    let schedule = match overrides.interval_minutes {
+        Some(0) => None,
+        Some(minutes) => Some(CrawlSchedule::every_minutes(minutes)?),
+        None => match stored_text("schedule").as_deref().unwrap_or("daily") {
+            "manual" => None,
+            "hourly" => Some(CrawlSchedule::every_minutes(60)?),
+            "daily" => Some(CrawlSchedule::every_minutes(24 * 60)?),
+            "weekly" => Some(CrawlSchedule::every_minutes(7 * 24 * 60)?),
+            other => {
+                return Err(CrawlerError::Config(format!(
+                    "Unknown backup schedule {other:?}, expected hourly, daily, weekly or manual"
+                )))
+            }
+        },
+    };
+
+    Ok(Some(BackupSettings {
+        s3: S3Settings {
+            endpoint,
+            bucket,
+            region,
+            access_key_id,
+            secret_access_key,
+        },
+        prefix,
+        keep,
+        schedule,
+    }))
+}
+
+/// Read the admin panel's settings and resolve them against `overrides`
+pub async fn load_settings(
+    pool: &DbPool,
+    overrides: &BackupOverrides,
+) -> Result<Option<BackupSettings>, CrawlerError> {
+    let stored = pool
+        .run(|database| database.load_configuration(CONFIGURATION_KEY))
+        .await?;
+    resolve_settings(overrides, stored.as_ref())
+}
+
+#[derive(Debug)]
+pub struct BackupReport {
+    pub backup_id: i64,
+    pub object_key: String,
+    pub database_bytes: u64,
+    pub compressed_bytes: u64,
+}
+
+/// Back up the database now
+///
+/// Claims the oldest `requested` row if there is one, otherwise records a new
+/// backup triggered by `triggered_by`. A failure marks the row `failed` with
+/// the reason. Only an expired shutdown drain interrupts the copy.
+pub async fn run_backup(
+    database_path: &Path,
+    busy_timeout: Duration,
+    settings: &BackupSettings,
+    triggered_by: &str,
+    shutdown: &Shutdown,
+) -> Result<BackupReport, CrawlerError> {
+    let path = database_path.to_path_buf();
+    let trigger = triggered_by.to_string();
+    let backup_id =
+        blocking(move || start_backup(&mut connect(&path, busy_timeout)?, &trigger)).await?;
+
+    let result = perform_backup(backup_id, database_path, busy_timeout, settings, shutdown).await;
+    match &result {
+        Ok(report) => {
+            telemetry::record_backup("completed", report.compressed_bytes);
+            info!(
+                "Backup #{} uploaded to s3://{}/{} ({} bytes, {} compressed)",
+                report.backup_id,
+                settings.s3.bucket,
+                report.object_key,
+                report.database_bytes,
+                report.compressed_bytes
+            );
+        }
+        Err(error) => {
+            telemetry::record_backup("failed", 0);
+            let path = database_path.to_path_buf();
+            let message = error.to_string();
+            let recorded =
+                blocking(move || fail_backup(&connect(&path, busy_timeout)?, backup_id, &message))
+                    .await;
+            if let Err(record_error) = recorded {
+                warn!(
+                    "Could not mark backup #{} failed: {}",
+                    backup_id, record_error
+                );
+            }
+        }
+    }
+    result
+}
+
+async fn perform_backup(
+    backup_id: i64,
+    database_path: &Path,
+    busy_timeout: Duration,
+    settings: &BackupSettings,
+    shutdown: &Shutdown,
+) -> Result<BackupReport, CrawlerError> {
+    let client = S3Client::new(settings.s3.clone())?;
+    let snapshot =
+        TempFile(std::env::temp_dir().join(format!("twitter-feels-backup-{backup_id}.db")));
+    let archive = TempFile(snapshot.0.with_extension("db.gz"));
+
+    // The copy runs on a blocking thread, which checks this flag between steps.
+    let drain_expired = Arc::new(AtomicBool::new(false));
+    let path = database_path.to_path_buf();
+    let (snapshot_path, archive_path) = (snapshot.0.clone(), archive.0.clone());
+    let copy_drain = drain_expired.clone();
+    let copy = blocking(move || {
+        let conn = connect(&path, busy_timeout)?;
+        copy_database(&conn, backup_id, &snapshot_path, &copy_drain)?;
+        let compressed = compress(&snapshot_path, &archive_path)?;
+        set_progress(&conn, backup_id, 90)?;
+        Ok(compressed)
+    });
+    tokio::pin!(copy);
+    let compressed = tokio::select! {
+        compressed = &mut copy => compressed?,
+        _ = shutdown.drain_expired() => {
+            drain_expired.store(true, Ordering::SeqCst);
+            copy.await?
+        }
+    };
+    drop(snapshot);
+
+    let object_key = format!(
+        "{}{ARCHIVE_NAME}{}-{backup_id}{ARCHIVE_SUFFIX}",
+        settings.prefix,
+        Utc::now().format("%Y%m%dT%H%M%SZ")
+    );
+    client
+        .put_file(&object_key, &archive.0, &compressed.sha256)
+        .await?;
+    drop(archive);
+
+    let path = database_path.to_path_buf();
+    let key = object_key.clone();
+    let recorded = compressed.clone();
+    blocking(move || complete_backup(&connect(&path, busy_timeout)?, backup_id, &key, &recorded))
+        .await?;
+
+    // The backup itself succeeded; a failed cleanup is retried after the next one.
+    let expired = match expire_archives(&client, settings).await {
+        Ok(expired) => expired,
+        Err(error) => {
+            warn!("Could not remove old backups: {}", error);
+            Vec::new()
+        }
+    };
+    if !expired.is_empty() {
+        let path = database_path.to_path_buf();
+        let keys = expired.clone();
+        blocking(move || mark_expired(&connect(&path, busy_timeout)?, &keys)).await?;
+        info!("Removed {} old backup(s) from the bucket", expired.len());
+    }
+
+    Ok(BackupReport {
+        backup_id,
+        object_key,
+        database_bytes: compressed.database_bytes,
+        compressed_bytes: compressed.compressed_bytes,
+    })
+}
+
+/// Backup archives under the prefix, newest first
+pub async fn list_archives(
+    client: &S3Client,
+    settings: &BackupSettings,
+) -> Result<Vec<S3Object>, CrawlerError> {
+    let mut archives: Vec<S3Object> = client
+        .list(&format!("{}{ARCHIVE_NAME}", settings.prefix))
+        .await?
+        .into_iter()
+        .filter(|object| object.key.ends_with(ARCHIVE_SUFFIX))
+        .collect();
+    // Keys start with a fixed-width UTC timestamp, so they sort by age.
+    archives.sort_unstable_by(|a, b| b.key.cmp(&a.key));
+    Ok(archives)
+}
+
+/// Delete all but the newest `settings.keep` archives; returns the deleted keys
+async fn expire_archives(
+    client: &S3Client,
+    settings: &BackupSettings,
+) -> Result<Vec<String>, CrawlerError> {
+    if settings.keep == 0 {
+        return Ok(Vec::new());
+    }
+    let mut keys: Vec<String> = list_archives(client, settings)
+        .await?
+        .into_iter()
+        .map(|object| object.key)
+        .collect();
+    let expired = keys.split_off(settings.keep.min(keys.len()));
+    for key in &expired {
+        client.delete(key).await?;
+    }
+    Ok(expired)
+}
+
+/// Whether the admin panel has asked for a backup
+pub async fn has_requested_backup(
+    database_path: &Path,
+    busy_timeout: Duration,
+) -> Result<bool, CrawlerError> {
+    let path = database_path.to_path_buf();
+    blocking(move || {
+        let requested = connect(&path, busy_timeout)?
+            .query_row(
+                "SELECT 1 FROM backups WHERE status = 'requested' LIMIT 1",
+                [],
+                |_| Ok(()),
+            )
+            .optional()?;
+        Ok(requested.is_some())
+    })
+    .await
+}
+
+/// Fail every `requested` backup with `message`, e.g. when backups are not configured
+pub async fn fail_requested_backups(
+    database_path: &Path,
+    busy_timeout: Duration,
+    message: &str,
+) -> Result<usize, CrawlerError> {
+    let path = database_path.to_path_buf();
+    let message = message.to_string();
+    blocking(move || {
+        Ok(connect(&path, busy_timeout)?.execute(
+            "UPDATE backups SET status = 'failed', error_message = ?1, completed_at = datetime('now')
+             WHERE status = 'requested'",
+            params![message],
+        )?)
+    })
+    .await
+}
+
+/// Fail backups left `running` by a crawler that died mid-backup, so they do
+/// not block new requests from the admin panel
+pub async fn fail_abandoned_backups(
+    database_path: &Path,
+    busy_timeout: Duration,
+) -> Result<usize, CrawlerError> {
+    let path = database_path.to_path_buf();
+    blocking(move || fail_abandoned(&connect(&path, busy_timeout)?)).await
+}
+
+pub(crate) fn fail_abandoned(conn: &Connection) -> Result<usize, CrawlerError> {
+    Ok(conn.execute(
+        "UPDATE backups SET status = 'failed', error_message = 'Interrupted before completion',
+             completed_at = datetime('now')
+         WHERE status = 'running'",
+        [],
+    )?)
+}
+
+pub(crate) fn connect(path: &Path, busy_timeout: Duration) -> Result<Connection, CrawlerError> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(busy_timeout)?;
+    Ok(conn)
+}
+
+pub(crate) async fn blocking<T, F>(f: F) -> Result<T, CrawlerError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, CrawlerError> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+        Err(error) => Err(CrawlerError::Backup(error.to_string())),
+    }
+}
+
+/// Claim the oldest requested backup, or record a new one, as `running`
+fn start_backup(conn: &mut Connection, triggered_by: &str) -> Result<i64, CrawlerError> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let requested: Option<i64> = tx
+        .query_row(
+            "SELECT id FROM backups WHERE status = 'requested' ORDER BY id LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let backup_id = match requested {
+        Some(backup_id) => {
+            tx.execute(
+                "UPDATE backups SET status = 'running', started_at = datetime('now') WHERE id = ?1",
+                params![backup_id],
+            )?;
+            backup_id
+        }
+        None => {
+            tx.execute(
+                "INSERT INTO backups (status, triggered_by, started_at)
+                 VALUES ('running', ?1, datetime('now'))",
+                params![triggered_by],
+            )?;
+            tx.last_insert_rowid()
+        }
+    };
+    tx.commit()?;
+    Ok(backup_id)
+}
+
+fn set_progress(conn: &Connection, backup_id: i64, percent: i64) -> Result<(), CrawlerError> {
+    conn.execute(
+        "UPDATE backups SET progress_percent = ?2 WHERE id = ?1",
+        params![backup_id, percent],
+    )?;
+    Ok(())
+}
+
+fn complete_backup(
+    conn: &Connection,
+    backup_id: i64,
+    object_key: &str,
+    archive: &Archive,
+) -> Result<(), CrawlerError> {
+    conn.execute(
+        "UPDATE backups
+         SET status = 'completed', progress_percent = 100, completed_at = datetime('now'),
+             object_key = ?2, database_bytes = ?3, compressed_bytes = ?4, sha256 = ?5
+         WHERE id = ?1",
+        params![
+            backup_id,
+            object_key,
+            archive.database_bytes as i64,
+            archive.compressed_bytes as i64,
+            archive.sha256
+        ],
+    )?;
+    Ok(())
+}
+
+fn fail_backup(conn: &Connection, backup_id: i64, message: &str) -> Result<(), CrawlerError> {
+    conn.execute(
+        "UPDATE backups SET status = 'failed', error_message = ?2, completed_at = datetime('now')
+         WHERE id = ?1",
+        params![backup_id, message],
+    )?;
+    Ok(())
+}
+
+fn mark_expired(conn: &Connection, object_keys: &[String]) -> Result<(), CrawlerError> {
+    for key in object_keys {
+        conn.execute(
+            "UPDATE backups SET status = 'expired' WHERE object_key = ?1 AND status = 'completed'",
+            params![key],
+        )?;
+    }
+    Ok(())
+}
+
+/// Copy the live database into `target` with the online backup API
+///
+/// Progress is written through the same connection the backup reads from, so
+/// SQLite applies those writes to the copy instead of restarting it.
+fn copy_database(
+    conn: &Connection,
+    backup_id: i64,
+    target: &Path,
+    drain_expired: &AtomicBool,
+) -> Result<(), CrawlerError> {
+    let _ = std::fs::remove_file(target);
+    let mut destination = Connection::open(target)?;
+    {
+        let backup = Backup::new(conn, &mut destination)?;
+        let mut reported = -1;
+        loop {
+            if drain_expired.load(Ordering::SeqCst) {
+                return Err(CrawlerError::Backup("Interrupted by shutdown".to_string()));
+            }
+            let step = backup.step(PAGES_PER_STEP)?;
+            let progress = backup.progress();
+            let total = i64::from(progress.pagecount.max(1));
+            let copied = total - i64::from(progress.remaining);
+            let percent = copied * SNAPSHOT_PERCENT / total;
+            if percent != reported {
+                set_progress(conn, backup_id, percent)?;
+                reported = percent;
+            }
+            match step {
+                StepResult::Done => break,
+                StepResult::More => std::thread::sleep(STEP_PAUSE),
+                _ => std::thread::sleep(BUSY_PAUSE),
+            }
+        }
+    }
+    // The copy inherits WAL mode; a rollback journal keeps the restored file self-contained.
+    destination.pragma_update(None, "journal_mode", "DELETE")?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+struct Archive {
+    database_bytes: u64,
+    compressed_bytes: u64,
+    /// Hex SHA-256 of the compressed file
+    sha256: String,
+}
+
+fn compress(source: &Path, target: &Path) -> Result<Archive, CrawlerError> {
+    let file_error = |path: &Path, error: io::Error| {
+        CrawlerError::Backup(format!("Could not write {}: {error}", path.display()))
+    };
+    let mut input = File::open(source).map_err(|error| file_error(source, error))?;
+    let output = HashingWriter {
+        inner: File::create(target).map_err(|error| file_error(target, error))?,
+        hasher: Sha256::new(),
+        written: 0,
+    };
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    let database_bytes =
+        io::copy(&mut input, &mut encoder).map_err(|error| file_error(target, error))?;
+    let output = encoder
+        .finish()
+        .map_err(|error| file_error(target, error))?;
+    output
+        .inner
+        .sync_all()
+        .map_err(|error| file_error(target, error))?;
+
+    Ok(Archive {
+        database_bytes,
+        compressed_bytes: output.written,
+        sha256: hex::encode(output.hasher.finalize()),
+    })
+}
+
+/// Hashes and counts everything written through it
+struct HashingWriter {
+    inner: File,
+    hasher: Sha256,
+    written: u64,
+}
+
+impl Write for HashingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Removed when dropped
+pub(crate) struct TempFile(pub(crate) PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use crate::db::SqliteDatabase;
+    use crate::s3::fake;
+    use crate::storage::Storage;
+
+    #[test]
+    fn environment_overrides_the_admin_settings() -> Result<(), CrawlerError> {
+        let stored = serde_json::json!({
+            "enabled": true,
+            "bucketName": "feels-backups",
+            "region": "eu-west-1",
+            "accessKeyId": "admin-key",
+            "secretAccessKey": "admin-secret",
+            "schedule": "weekly",
+        });
+        let settings = resolve_settings(&BackupOverrides::default(), Some(&stored))?
+            .expect("enabled in the admin panel");
+        assert_eq!(settings.s3.endpoint, "https://s3.eu-west-1.amazonaws.com");
+        assert_eq!(settings.prefix, "backups/");
+        assert_eq!(settings.keep, DEFAULT_KEEP);
+        assert_eq!(
+            settings.schedule.map(|s| s.describe()).as_deref(),
+            Some("every 168 hour(s)")
+        );
+
+        let overrides = BackupOverrides {
+            endpoint: Some("http://localhost:9000".to_string()),
+            access_key_id: Some("minio".to_string()),
+            prefix: Some("feels".to_string()),
+            keep: Some(3),
+            interval_minutes: Some(0),
+            ..BackupOverrides::default()
+        };
+        let settings = resolve_settings(&overrides, Some(&stored))?.expect("still enabled");
+        assert_eq!(settings.s3.endpoint, "http://localhost:9000");
+        assert_eq!(settings.s3.access_key_id, "minio");
+        assert_eq!(settings.s3.secret_access_key, "admin-secret");
+        assert_eq!(settings.prefix, "feels/");
+        assert_eq!(settings.keep, 3);
+        assert!(settings.schedule.is_none());
+
+        let disabled = serde_json::json!({ "enabled": false, "bucketName": "feels-backups" });
+        assert!(resolve_settings(&BackupOverrides::default(), Some(&disabled))?.is_none());
+        assert!(resolve_settings(&BackupOverrides::default(), None)?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn backups_upload_compressed_snapshots_and_keep_the_newest() -> Result<(), CrawlerError> {
+        let path = std::env::temp_dir().join(format!("crawler-backup-{}.db", std::process::id()));
+        let database = SqliteDatabase::new(&path.to_string_lossy(), Duration::from_secs(1))?;
+        database.init_schema()?;
+        database.execute_sql(
+            "INSERT INTO twitter_users (id, twitter_id, username, display_name, is_active)
+             VALUES (5, 'user_5', 'user5', 'User Five', 1)",
+        )?;
+
+        let (endpoint, objects) = fake::serve().await;
+        let settings = BackupSettings {
+            s3: S3Settings {
+                endpoint,
+                bucket: "feels".to_string(),
+                region: "us-east-1".to_string(),
+                access_key_id: "minio".to_string(),
+                secret_access_key: "minio-secret".to_string(),
+            },
+            prefix: "backups/".to_string(),
+            keep: 2,
+            schedule: None,
+        };
+        let shutdown = Shutdown::new();
+        let timeout = Duration::from_secs(1);
+
+        let first = run_backup(&path, timeout, &settings, "manual", &shutdown).await?;
+        run_backup(&path, timeout, &settings, "schedule", &shutdown).await?;
+        // A request from the admin panel is claimed instead of adding a row.
+        database.execute_sql(
+            "INSERT INTO backups (status, triggered_by) VALUES ('requested', 'admin')",
+        )?;
+        assert!(has_requested_backup(&path, timeout).await?);
+        let latest = run_backup(&path, timeout, &settings, "schedule", &shutdown).await?;
+        assert!(!has_requested_backup(&path, timeout).await?);
+
+        let stored: Vec<String> = objects.lock().unwrap().keys().cloned().collect();
+        assert_eq!(stored.len(), 2);
+        assert!(!stored.contains(&first.object_key));
+        assert!(stored.contains(&latest.object_key));
+
+        let rows = database.query_text(
+            "SELECT group_concat(status || ':' || triggered_by, ',') FROM (SELECT * FROM backups ORDER BY id)",
+        )?;
+        assert_eq!(
+            rows.as_deref(),
+            Some("expired:manual,completed:schedule,completed:admin")
+        );
+
+        // The archive is a self-contained copy of the database.
+        let archive = objects.lock().unwrap()[&latest.object_key].clone();
+        assert_eq!(archive.len() as u64, latest.compressed_bytes);
+        let mut restored = Vec::new();
+        GzDecoder::new(archive.as_slice())
+            .read_to_end(&mut restored)
+            .map_err(|error| CrawlerError::Backup(error.to_string()))?;
+        assert_eq!(restored.len() as u64, latest.database_bytes);
+        let restored_path = path.with_extension("restored.db");
+        std::fs::write(&restored_path, restored)
+            .map_err(|error| CrawlerError::Backup(error.to_string()))?;
+        let copy = Connection::open(&restored_path)?;
+        let username: String = copy.query_row(
+            "SELECT username FROM twitter_users WHERE id = 5",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(username, "user5");
+        let journal_mode: String = copy.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+        assert_eq!(journal_mode, "delete");
+
+        drop(copy);
+        drop(database);
+        for file in [path.clone(), restored_path] {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = std::fs::remove_file(format!("{}{suffix}", file.display()));
+            }
+        }
+        Ok(())
+    }
+}
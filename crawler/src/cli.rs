@@ -0,0 +1,200 @@
+//! Command line interface for the crawler
+//!
+//! Exit codes:
+//! - `0`: the command succeeded
+//! - `1`: the command failed
+//! - `2`: invalid usage (reported by clap) or an invalid configuration
+//! - `3`: the command finished but recorded errors along the way
+//! - `4`: there was nothing to report (e.g. no crawler run recorded yet)
+//! - `5`: the command was cancelled by a shutdown signal
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Debug, Parser)]
+#[command(name = "twitter-feels-crawler", version, about)]
+pub struct Cli {
+    /// Log line format
+    #[arg(long, global = true, env = "LOG_FORMAT", value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// TOML config file with settings and per-user overrides
+    #[arg(long, global = true, env = "CRAWLER_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per line, including the enclosing spans
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run a single crawl cycle and exit
+    Crawl {
+        /// Only crawl this tracked username
+        #[arg(long)]
+        user: Option<String>,
+    },
+
+    /// Process pending analysis jobs and exit
+    Analyze {
+        /// Maximum number of jobs to process
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// Only process jobs for this LLM model id
+        #[arg(long)]
+        model: Option<i64>,
+    },
+
+    /// Recompute user and global aggregations
+    Aggregate,
+
+    /// Apply the retention rules once, then vacuum if configured
+    Prune,
+
+    /// Back up the SQLite database to S3-compatible storage now
+    Backup,
+
+    /// List backups in the bucket, or restore one in place of the database
+    ///
+    /// Every crawler process using the database must be stopped first, and the API too.
+    Restore {
+        /// Object key or file name of the backup to restore; omit to list backups
+        backup: Option<String>,
+
+        /// Restore the newest backup
+        #[arg(long, conflicts_with = "backup")]
+        latest: bool,
+    },
+
+    /// Fetch tweets for a fixed date range, ignoring checkpoints
+    Backfill {
+        /// First day to fetch (YYYY-MM-DD, inclusive)
+        #[arg(long)]
+        from: NaiveDate,
+
+        /// Last day to fetch (YYYY-MM-DD, inclusive)
+        #[arg(long)]
+        to: NaiveDate,
+
+        /// Only backfill this tracked username
+        #[arg(long)]
+        user: Option<String>,
+    },
+
+    /// Print a summary of the latest crawler run
+    Status,
+
+    /// Print a tracked user's recent per-run crawl outcomes
+    History {
+        /// Tracked username
+        #[arg(long)]
+        user: String,
+
+        /// Number of runs to show
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+    },
+
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Run crawl cycles forever on the configured interval (default)
+    Daemon,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print every setting with its source and report all invalid ones
+    Check,
+}
+
+/// Result of a command, mapped onto the process exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Failed,
+    InvalidConfig,
+    CompletedWithErrors,
+    NothingToReport,
+    Cancelled,
+}
+
+impl Outcome {
+    pub fn exit_code(self) -> ExitCode {
+        match self {
+            Outcome::Success => ExitCode::SUCCESS,
+            Outcome::Failed => ExitCode::from(1),
+            Outcome::InvalidConfig => ExitCode::from(2),
+            Outcome::CompletedWithErrors => ExitCode::from(3),
+            Outcome::NothingToReport => ExitCode::from(4),
+            Outcome::Cancelled => ExitCode::from(5),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_backfill_range() {
+        let cli = Cli::try_parse_from([
+            "twitter-feels-crawler",
+            "backfill",
+            "--from",
+            "2024-01-01",
+            "--to",
+            "2024-01-31",
+            "--user",
+            "tester",
+        ])
+        .expect("parse backfill");
+
+        let Some(Command::Backfill { from, to, user }) = cli.command else {
+            panic!("expected backfill command");
+        };
+        assert_eq!(from, NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        assert_eq!(to, NaiveDate::from_ymd_opt(2024, 1, 31).unwrap());
+        assert_eq!(user.as_deref(), Some("tester"));
+    }
+
+    #[test]
+    fn no_subcommand_defaults_to_none() {
+        let cli = Cli::try_parse_from(["twitter-feels-crawler"]).expect("parse empty");
+        assert!(cli.command.is_none());
+    }
+
+    #[test]
+    fn parse_config_check() {
+        let cli = Cli::try_parse_from(["twitter-feels-crawler", "config", "check"])
+            .expect("parse config check");
+        assert!(matches!(
+            cli.command,
+            Some(Command::Config {
+                command: ConfigCommand::Check
+            })
+        ));
+    }
+
+    #[test]
+    fn log_format_is_global() {
+        let cli = Cli::try_parse_from(["twitter-feels-crawler", "status", "--log-format", "json"])
+            .expect("parse log format");
+        assert_eq!(cli.log_format, LogFormat::Json);
+    }
+}
@@ -1,18 +1,593 @@
 //! Configuration module for the crawler
+//!
+//! Every setting is named after its environment variable and resolved from
+//! layers, later ones winning: built-in defaults, the TOML file named by
+//! `--config` or `CRAWLER_CONFIG` (keys in lower case, e.g.
+//! `history_depth_days = 30`), the environment, and the admin panel's
+//! `crawler` settings in the `configurations` table. The database layer is
+//! re-read at the start of every crawl cycle, so admin edits apply without a
+//! restart.
+//!
+//! The file can also override crawling for single users in
+//! `[users.<username>]` tables; see [`UserOverrides`].
 
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::fmt;
+use std::fs::File;
+use std::ops::{Bound, RangeBounds};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
 
-/// Crawler configuration loaded from environment variables
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::backup::BackupOverrides;
+use crate::db_pool::DbPool;
+use crate::error::CrawlerError;
+use crate::models::LlmModel;
+use crate::retention::{RetentionPolicy, VacuumMode};
+use crate::schedule::CrawlSchedule;
+use crate::storage::{self, StoredConfiguration, TweetTypes};
+
+/// `configurations` key the admin panel stores crawler settings under
+pub const CONFIGURATION_KEY: &str = "crawler";
+
+/// Admin panel settings, the variables they set and the value `schema.sql`
+/// seeds them with
+const DATABASE_SETTINGS: [(&str, &str, &str); 3] = [
+    ("intervalHours", "CRAWL_INTERVAL_HOURS", "1"),
+    ("historyDepthDays", "HISTORY_DEPTH_DAYS", "90"),
+    ("rateLimitPer15Min", "RATE_LIMIT_PER_15MIN", "450"),
+];
+
+/// Variables that choose the crawl schedule; they are resolved together
+const SCHEDULE_SETTINGS: [&str; 3] = [
+    "CRAWL_CRON",
+    "CRAWL_INTERVAL_MINUTES",
+    "CRAWL_INTERVAL_HOURS",
+];
+
+/// Where a setting's value came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File,
+    Env,
+    Database,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Source::Default => "default",
+            Source::File => "config file",
+            Source::Env => "environment",
+            Source::Database => "database",
+        })
+    }
+}
+
+/// Raw setting values per source
+#[derive(Debug, Clone, Default)]
+pub struct Layers {
+    file: HashMap<String, String>,
+    env: HashMap<String, String>,
+    database: HashMap<String, String>,
+    /// The file's `[users.<username>]` tables, by lower-case username
+    users: BTreeMap<String, toml::Value>,
+}
+
+impl Layers {
+    /// Config file (if one is given) and environment layers
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let mut layers = Self {
+            env: env::vars().collect(),
+            ..Self::default()
+        };
+        if let Some(path) = path {
+            let text = std::fs::read_to_string(path).map_err(|error| {
+                anyhow::anyhow!("Could not read config file {}: {error}", path.display())
+            })?;
+            (layers.file, layers.users) = file_layer(&text).map_err(|error| {
+                anyhow::anyhow!("Invalid config file {}: {error}", path.display())
+            })?;
+        }
+        Ok(layers)
+    }
+
+    /// Replace the database layer with the admin panel's `crawler` settings
+    ///
+    /// `schema.sql` seeds the row on every install, so until an admin saves it
+    /// (`updated_by` is set) only values that differ from the seed count.
+    pub fn with_database(&self, stored: Option<&StoredConfiguration>) -> Self {
+        let database = DATABASE_SETTINGS
+            .iter()
+            .filter_map(|(key, name, seeded)| {
+                let stored = stored?;
+                let value = scalar(stored.value.get(key)?)?;
+                if stored.updated_by.is_none() && value == *seeded {
+                    return None;
+                }
+                Some((name.to_string(), value))
+            })
+            .collect();
+        Self {
+            database,
+            ..self.clone()
+        }
+    }
+
+    fn layers(&self) -> [(Source, &HashMap<String, String>); 3] {
+        [
+            (Source::Database, &self.database),
+            (Source::Env, &self.env),
+            (Source::File, &self.file),
+        ]
+    }
+
+    fn lookup(&self, name: &str) -> Option<(&str, Source)> {
+        self.layers()
+            .into_iter()
+            .find_map(|(source, layer)| layer.get(name).map(|value| (value.as_str(), source)))
+    }
+
+    /// Which layer supplied `name`
+    pub fn source(&self, name: &str) -> Source {
+        self.lookup(name)
+            .map_or(Source::Default, |(_, source)| source)
+    }
+
+    /// The highest layer that sets any crawl schedule variable decides the
+    /// schedule on its own, so an admin panel interval replaces `CRAWL_CRON`
+    fn schedule_layer(&self) -> Option<(Source, &HashMap<String, String>)> {
+        self.layers().into_iter().find(|(_, layer)| {
+            SCHEDULE_SETTINGS
+                .iter()
+                .any(|name| layer.contains_key(*name))
+        })
+    }
+}
+
+/// A config file's top-level scalars, keyed by variable name, and its
+/// `[users.<username>]` tables
+type FileLayer = (HashMap<String, String>, BTreeMap<String, toml::Value>);
+
+fn file_layer(text: &str) -> anyhow::Result<FileLayer> {
+    let mut table: toml::Table = text.parse()?;
+    let users = match table.remove("users") {
+        None => BTreeMap::new(),
+        Some(toml::Value::Table(users)) => users
+            .into_iter()
+            .map(|(username, overrides)| (normalize_username(&username), overrides))
+            .collect(),
+        Some(_) => {
+            return Err(anyhow::anyhow!(
+                "users must be a table of [users.<username>] tables"
+            ))
+        }
+    };
+    let settings = table
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                toml::Value::String(text) => text,
+                toml::Value::Integer(number) => number.to_string(),
+                toml::Value::Float(number) => number.to_string(),
+                toml::Value::Boolean(flag) => flag.to_string(),
+                _ => return None,
+            };
+            Some((key.to_ascii_uppercase(), value))
+        })
+        .collect();
+    Ok((settings, users))
+}
+
+fn normalize_username(username: &str) -> String {
+    username.trim().trim_start_matches('@').to_ascii_lowercase()
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(flag) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+/// Crawl settings for one tracked user, from a `[users.<username>]` table
+///
+/// ```toml
+/// [users.elonmusk]
+/// history_depth_days = 7
+/// crawl_interval_hours = 6
+/// include_replies = false
+/// include_retweets = false
+/// models = ["meta-llama/Llama-3.2-3B-Instruct"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserOverrides {
+    /// Replaces `HISTORY_DEPTH_DAYS`
+    pub history_depth_days: Option<u32>,
+    /// Crawl the user at most this often (`crawl_interval_hours` or
+    /// `crawl_interval_minutes`); scheduled cycles in between skip it
+    pub crawl_interval_minutes: Option<u32>,
+    /// `false` leaves replies out of the timeline request; `true` fetches
+    /// them even when `EXCLUDE_TWEET_TYPES` lists them
+    pub include_replies: Option<bool>,
+    /// `false` leaves retweets out of the timeline request; `true` fetches
+    /// them even when `EXCLUDE_TWEET_TYPES` lists them
+    pub include_retweets: Option<bool>,
+    /// Models (Hugging Face ids or names) that analyse the user's tweets,
+    /// instead of every enabled one
+    pub models: Option<Vec<String>>,
+}
+
+impl UserOverrides {
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(days) = self.history_depth_days {
+            parts.push(format!("history depth {days} days"));
+        }
+        if let Some(interval) = self.crawl_interval_minutes {
+            parts.push(minutes(interval).describe());
+        }
+        for (include, kind) in [
+            (self.include_replies, "replies"),
+            (self.include_retweets, "retweets"),
+        ] {
+            match include {
+                Some(true) => parts.push(format!("with {kind}")),
+                Some(false) => parts.push(format!("no {kind}")),
+                None => {}
+            }
+        }
+        if let Some(models) = &self.models {
+            parts.push(format!("models {}", models.join(", ")));
+        }
+        if parts.is_empty() {
+            "no overrides".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// A `[users.<username>]` table as written
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UserTable {
+    history_depth_days: Option<u32>,
+    crawl_interval_hours: Option<u32>,
+    crawl_interval_minutes: Option<u32>,
+    include_replies: Option<bool>,
+    include_retweets: Option<bool>,
+    models: Option<Vec<String>>,
+}
+
+/// A setting whose value was rejected; its default is used instead
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidSetting {
+    pub name: String,
+    pub value: String,
+    pub source: Source,
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} = {:?} ({}): {}",
+            self.name, self.value, self.source, self.reason
+        )
+    }
+}
+
+/// Every invalid setting found while resolving the configuration
+#[derive(Debug)]
+pub struct InvalidConfig(pub Vec<InvalidSetting>);
+
+impl fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} invalid setting(s):", self.0.len())?;
+        for setting in &self.0 {
+            write!(f, "\n  {setting}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidConfig {}
+
+/// Reads typed settings from [`Layers`], collecting every rejected value
+struct Resolver<'a> {
+    layers: &'a Layers,
+    names: Vec<&'static str>,
+    invalid: Vec<InvalidSetting>,
+}
+
+impl<'a> Resolver<'a> {
+    fn lookup(&mut self, name: &'static str) -> Option<(&'a str, Source)> {
+        if !self.names.contains(&name) {
+            self.names.push(name);
+        }
+        self.layers.lookup(name)
+    }
+
+    fn reject(&mut self, name: &str, value: &str, source: Source, reason: impl Into<String>) {
+        self.invalid.push(InvalidSetting {
+            name: name.to_string(),
+            value: value.to_string(),
+            source,
+            reason: reason.into(),
+        });
+    }
+
+    /// A non-empty value, trimmed
+    fn text(&mut self, name: &'static str) -> Option<String> {
+        self.lookup(name)
+            .map(|(value, _)| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+
+    /// A number within `range`; blank counts as unset
+    fn number<T>(&mut self, name: &'static str, range: impl RangeBounds<T>) -> Option<T>
+    where
+        T: FromStr + PartialOrd + fmt::Display,
+    {
+        let (raw, source) = self.lookup(name)?;
+        if raw.trim().is_empty() {
+            return None;
+        }
+        match raw.trim().parse::<T>() {
+            Ok(value) if range.contains(&value) => Some(value),
+            _ => {
+                self.reject(
+                    name,
+                    raw,
+                    source,
+                    format!("must be a whole number{}", bounds(&range)),
+                );
+                None
+            }
+        }
+    }
+
+    /// Interval in minutes, or an hourly fallback
+    fn interval(&mut self, name: &'static str, default: u32) -> CrawlSchedule {
+        minutes(self.number(name, 1..).unwrap_or(default))
+    }
+
+    /// The crawl schedule from the highest layer that sets any of its variables
+    fn crawl_schedule(&mut self) -> CrawlSchedule {
+        for name in SCHEDULE_SETTINGS {
+            self.lookup(name);
+        }
+        let Some((source, layer)) = self.layers.schedule_layer() else {
+            return minutes(60);
+        };
+        if let Some(expression) = layer.get("CRAWL_CRON") {
+            return match CrawlSchedule::cron(expression) {
+                Ok(schedule) => schedule,
+                Err(error) => {
+                    self.reject("CRAWL_CRON", expression, source, reason(error));
+                    minutes(60)
+                }
+            };
+        }
+        let mut parse = |name: &str, per_unit: u32| {
+            let raw = layer.get(name)?;
+            match raw.trim().parse::<u32>() {
+                Ok(value) if value > 0 => Some(minutes(value.saturating_mul(per_unit))),
+                _ => {
+                    self.reject(name, raw, source, "must be a whole number of at least 1");
+                    None
+                }
+            }
+        };
+        parse("CRAWL_INTERVAL_MINUTES", 1)
+            .or_else(|| parse("CRAWL_INTERVAL_HOURS", 60))
+            .unwrap_or_else(|| minutes(60))
+    }
+
+    /// Days to keep rows for one retention rule; `0` or `off` keeps them forever
+    fn retention_days(&mut self, name: &'static str, default: Option<u32>) -> Option<u32> {
+        let Some((raw, source)) = self.lookup(name) else {
+            return default;
+        };
+        let value = raw.trim();
+        if value.is_empty() {
+            return default;
+        }
+        if value.eq_ignore_ascii_case("off") {
+            return None;
+        }
+        match value.parse::<u32>() {
+            Ok(0) => None,
+            Ok(days) => Some(days),
+            Err(_) => {
+                self.reject(
+                    name,
+                    raw,
+                    source,
+                    "must be a whole number of days, 0 or off",
+                );
+                default
+            }
+        }
+    }
+
+    fn vacuum(&mut self) -> VacuumMode {
+        let Some((raw, source)) = self.lookup("PRUNE_VACUUM") else {
+            return VacuumMode::Off;
+        };
+        VacuumMode::parse(raw).unwrap_or_else(|| {
+            self.reject(
+                "PRUNE_VACUUM",
+                raw,
+                source,
+                "must be off, incremental or full",
+            );
+            VacuumMode::Off
+        })
+    }
+
+    /// A comma-separated list of tweet types; blank or `none` is the empty set
+    fn tweet_types(&mut self, name: &'static str, default: TweetTypes) -> TweetTypes {
+        let Some((raw, source)) = self.lookup(name) else {
+            return default;
+        };
+        TweetTypes::parse(raw).unwrap_or_else(|| {
+            self.reject(
+                name,
+                raw,
+                source,
+                "must list replies and/or retweets, or be none",
+            );
+            default
+        })
+    }
+
+    /// Overrides from every `[users.<username>]` table; a table with an unknown
+    /// key or a wrongly typed value is ignored as a whole
+    fn users(&mut self) -> BTreeMap<String, UserOverrides> {
+        let layers = self.layers;
+        let mut users = BTreeMap::new();
+        for (username, table) in &layers.users {
+            let name = format!("users.{username}");
+            let parsed = match UserTable::deserialize(table.clone()) {
+                Ok(parsed) => parsed,
+                Err(error) => {
+                    self.reject(&name, &table.to_string(), Source::File, error.message());
+                    continue;
+                }
+            };
+            let mut at_least_one = |field: &str, value: Option<u32>| match value {
+                Some(0) => {
+                    let reason = "must be a whole number of at least 1";
+                    self.reject(&format!("{name}.{field}"), "0", Source::File, reason);
+                    None
+                }
+                value => value,
+            };
+            let history_depth_days = at_least_one("history_depth_days", parsed.history_depth_days);
+            let interval_minutes =
+                at_least_one("crawl_interval_minutes", parsed.crawl_interval_minutes);
+            let interval_hours = at_least_one("crawl_interval_hours", parsed.crawl_interval_hours);
+            users.insert(
+                username.clone(),
+                UserOverrides {
+                    history_depth_days,
+                    crawl_interval_minutes: interval_minutes
+                        .or(interval_hours.map(|hours| hours.saturating_mul(60))),
+                    include_replies: parsed.include_replies,
+                    include_retweets: parsed.include_retweets,
+                    models: parsed.models,
+                },
+            );
+        }
+        users
+    }
+
+    /// A SQLite file has to be readable, or creatable in an existing directory
+    fn check_database(&mut self, database_url: &str) {
+        let Some(path) = storage::sqlite_path(database_url) else {
+            return;
+        };
+        let problem = if path.is_dir() {
+            Some("is a directory".to_string())
+        } else if path.exists() {
+            File::open(&path)
+                .err()
+                .map(|error| format!("cannot be read: {error}"))
+        } else {
+            path.parent()
+                .filter(|parent| !parent.as_os_str().is_empty() && !parent.is_dir())
+                .map(|parent| format!("directory {} does not exist", parent.display()))
+        };
+        if let Some(problem) = problem {
+            let source = self.layers.source("DATABASE_URL");
+            self.reject("DATABASE_URL", database_url, source, problem);
+        }
+    }
+}
+
+fn minutes(minutes: u32) -> CrawlSchedule {
+    CrawlSchedule::Interval(chrono::Duration::minutes(minutes.max(1).into()))
+}
+
+fn bounds<T: fmt::Display>(range: &impl RangeBounds<T>) -> String {
+    match (range.start_bound(), range.end_bound()) {
+        (Bound::Included(min), Bound::Unbounded) => format!(" of at least {min}"),
+        (Bound::Included(min), Bound::Included(max)) => format!(" from {min} to {max}"),
+        _ => String::new(),
+    }
+}
+
+fn reason(error: CrawlerError) -> String {
+    match error {
+        CrawlerError::Config(message) => message,
+        error => error.to_string(),
+    }
+}
+
+/// Crawler configuration resolved from [`Layers`]
 #[allow(dead_code)]
 pub struct Config {
-    /// Path to SQLite database
+    /// Path to the SQLite database, or a `postgres://` URL
     pub database_url: String,
 
+    /// How long a connection waits for another writer's lock before `SQLITE_BUSY` (in milliseconds)
+    pub sqlite_busy_timeout_ms: u64,
+
+    /// Maximum number of pooled database connections
+    pub db_pool_size: u32,
+
     /// Twitter API bearer token
     pub twitter_bearer_token: String,
 
-    /// How often to run the crawler (in hours)
-    pub crawl_interval_hours: u32,
+    /// When to run the crawler (`CRAWL_CRON`, `CRAWL_INTERVAL_MINUTES` or `CRAWL_INTERVAL_HOURS`)
+    pub crawl_schedule: CrawlSchedule,
+
+    /// How often the reanalysis dispatcher polls for requests (in seconds)
+    pub reanalysis_poll_secs: u64,
+
+    /// Number of concurrent analysis workers
+    pub analysis_workers: usize,
+
+    /// How long an idle analysis worker waits before polling again (in seconds)
+    pub analysis_poll_secs: u64,
+
+    /// When the aggregator recomputes aggregations (`AGGREGATION_INTERVAL_MINUTES`)
+    pub aggregation_schedule: CrawlSchedule,
+
+    /// How long tweets, resolved errors, run error details and completed jobs are kept
+    pub retention: RetentionPolicy,
+
+    /// When the pruner applies the retention rules (`PRUNE_INTERVAL_MINUTES`)
+    pub prune_schedule: CrawlSchedule,
+
+    /// `BACKUP_*` overrides for the admin panel's S3 backup settings
+    pub backup: BackupOverrides,
+
+    /// How often the backup stage checks for backups requested from the admin panel (in seconds)
+    pub backup_poll_secs: u64,
+
+    /// Minimum time between live progress writes to `crawler_runs` (in seconds)
+    pub progress_update_secs: u64,
+
+    /// Address for the status/health HTTP server; `None` disables it (`STATUS_ADDR`, `off`)
+    pub status_addr: Option<String>,
+
+    /// A running cycle without progress for this long fails readiness (in minutes)
+    pub stall_timeout_minutes: u64,
+
+    /// How long in-flight work may run after SIGINT/SIGTERM (in seconds)
+    pub shutdown_drain_secs: u64,
 
     /// How far back to fetch tweets (in days)
     pub history_depth_days: u32,
@@ -20,6 +595,12 @@ pub struct Config {
     /// Rate limit for Twitter API (requests per 15 minutes)
     pub rate_limit_per_15min: u32,
 
+    /// Tweet types left out of timeline requests (`EXCLUDE_TWEET_TYPES`)
+    pub exclude_tweet_types: TweetTypes,
+
+    /// Tweet types stored without queueing analysis (`SKIP_ANALYSIS_TWEET_TYPES`)
+    pub skip_analysis: TweetTypes,
+
     /// Default LLM model to use
     #[allow(dead_code)]
     pub default_model: String,
@@ -27,43 +608,508 @@ pub struct Config {
     /// Hugging Face API token (optional)
     #[allow(dead_code)]
     pub huggingface_token: Option<String>,
+
+    /// Per-user overrides from the config file, by lower-case username
+    pub users: BTreeMap<String, UserOverrides>,
+
+    layers: Layers,
+
+    /// Settings read while resolving, in order
+    names: Vec<&'static str>,
 }
 
 impl Config {
-    /// Load configuration from environment variables
-    pub fn from_env() -> anyhow::Result<Self> {
-        let twitter_bearer_token = env::var("TWITTER_BEARER_TOKEN").unwrap_or_default();
-        if twitter_bearer_token.trim().is_empty() {
+    /// Load configuration from the config file and environment variables
+    ///
+    /// The database layer is added later by [`Config::refresh`], once a
+    /// connection pool is open.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        Ok(Self::from_layers(Layers::load(path)?)?)
+    }
+
+    /// Resolve `layers`, failing with every invalid setting at once
+    pub fn from_layers(layers: Layers) -> Result<Self, InvalidConfig> {
+        match Self::resolve(layers) {
+            (config, invalid) if invalid.is_empty() => Ok(config),
+            (_, invalid) => Err(InvalidConfig(invalid)),
+        }
+    }
+
+    /// Resolve `layers`, using the default for every invalid setting
+    pub fn resolve(layers: Layers) -> (Self, Vec<InvalidSetting>) {
+        let mut settings = Resolver {
+            layers: &layers,
+            names: Vec::new(),
+            invalid: Vec::new(),
+        };
+
+        let database_url = settings
+            .text("DATABASE_URL")
+            .unwrap_or_else(|| "../backend/data/twitter_feels.db".to_string());
+        settings.check_database(&database_url);
+
+        let config = Self {
+            sqlite_busy_timeout_ms: settings
+                .number("SQLITE_BUSY_TIMEOUT_MS", 0..)
+                .unwrap_or(5000),
+
+            db_pool_size: settings.number("DB_POOL_SIZE", 1..).unwrap_or(8),
+
+            twitter_bearer_token: settings.text("TWITTER_BEARER_TOKEN").unwrap_or_default(),
+
+            crawl_schedule: settings.crawl_schedule(),
+
+            reanalysis_poll_secs: settings
+                .number("REANALYSIS_POLL_SECONDS", 1..)
+                .unwrap_or(60),
+
+            analysis_workers: settings.number("ANALYSIS_WORKERS", 1..).unwrap_or(1),
+
+            analysis_poll_secs: settings.number("ANALYSIS_POLL_SECONDS", 1..).unwrap_or(30),
+
+            aggregation_schedule: settings.interval("AGGREGATION_INTERVAL_MINUTES", 15),
+
+            retention: RetentionPolicy {
+                tweets_days: settings.retention_days("RETENTION_TWEETS_DAYS", None),
+                api_errors_days: settings.retention_days("RETENTION_API_ERRORS_DAYS", Some(30)),
+                run_errors_days: settings.retention_days("RETENTION_RUN_ERRORS_DAYS", Some(30)),
+                completed_jobs_days: settings
+                    .retention_days("RETENTION_COMPLETED_JOBS_DAYS", Some(7)),
+                batch_size: settings.number("PRUNE_BATCH_SIZE", 1..).unwrap_or(500),
+                vacuum: settings.vacuum(),
+            },
+
+            prune_schedule: settings.interval("PRUNE_INTERVAL_MINUTES", 1440),
+
+            backup: BackupOverrides {
+                endpoint: settings.text("BACKUP_S3_ENDPOINT"),
+                bucket: settings.text("BACKUP_S3_BUCKET"),
+                region: settings.text("BACKUP_S3_REGION"),
+                access_key_id: settings.text("BACKUP_S3_ACCESS_KEY_ID"),
+                secret_access_key: settings.text("BACKUP_S3_SECRET_ACCESS_KEY"),
+                prefix: settings
+                    .lookup("BACKUP_S3_PREFIX")
+                    .map(|(value, _)| value.to_string()),
+                keep: settings.number("BACKUP_KEEP", 0..),
+                interval_minutes: settings.number("BACKUP_INTERVAL_MINUTES", 0..),
+            },
+
+            backup_poll_secs: settings.number("BACKUP_POLL_SECONDS", 1..).unwrap_or(60),
+
+            progress_update_secs: settings.number("PROGRESS_UPDATE_SECONDS", 0..).unwrap_or(5),
+
+            status_addr: match settings.lookup("STATUS_ADDR") {
+                Some((value, _)) if value.is_empty() || value.eq_ignore_ascii_case("off") => None,
+                Some((value, _)) => Some(value.to_string()),
+                None => Some("0.0.0.0:8080".to_string()),
+            },
+
+            stall_timeout_minutes: settings.number("STALL_TIMEOUT_MINUTES", 1..).unwrap_or(30),
+
+            shutdown_drain_secs: settings.number("SHUTDOWN_DRAIN_SECONDS", 0..).unwrap_or(30),
+
+            history_depth_days: settings.number("HISTORY_DEPTH_DAYS", 1..).unwrap_or(90),
+
+            rate_limit_per_15min: settings.number("RATE_LIMIT_PER_15MIN", 1..).unwrap_or(450),
+
+            exclude_tweet_types: settings.tweet_types("EXCLUDE_TWEET_TYPES", TweetTypes::default()),
+
+            skip_analysis: settings.tweet_types(
+                "SKIP_ANALYSIS_TWEET_TYPES",
+                TweetTypes {
+                    retweets: true,
+                    ..TweetTypes::default()
+                },
+            ),
+
+            default_model: settings
+                .text("DEFAULT_MODEL")
+                .unwrap_or_else(|| "meta-llama/Llama-3.2-3B-Instruct".to_string()),
+
+            huggingface_token: settings.text("HUGGINGFACE_TOKEN"),
+
+            users: settings.users(),
+            database_url,
+            names: settings.names,
+            layers: layers.clone(),
+        };
+        (config, settings.invalid)
+    }
+
+    /// Every setting with its effective value (secrets masked) and source
+    pub fn settings(&self) -> Vec<(&'static str, Option<String>, Source)> {
+        self.names
+            .iter()
+            .map(|name| {
+                let value = self.layers.lookup(name).map(|(value, _)| {
+                    if *name == "DATABASE_URL" {
+                        storage::redacted_url(value)
+                    } else if ["TOKEN", "SECRET", "ACCESS_KEY"]
+                        .iter()
+                        .any(|secret| name.contains(secret))
+                    {
+                        "(set)".to_string()
+                    } else {
+                        value.to_string()
+                    }
+                });
+                (*name, value, self.layers.source(name))
+            })
+            .collect()
+    }
+
+    /// Overrides for one tracked user, if the config file has any
+    pub fn user(&self, username: &str) -> Option<&UserOverrides> {
+        self.users.get(&normalize_username(username))
+    }
+
+    /// `DEFAULT_MODEL` (unless it is still the default) and every per-user
+    /// model has to name one of `models`
+    pub fn check_models(&self, models: &[LlmModel]) -> Vec<InvalidSetting> {
+        let known: Vec<_> = models
+            .iter()
+            .map(|model| model.huggingface_model_id.as_deref().unwrap_or(&model.name))
+            .collect();
+        let unknown = |name: String, value: &str, source: Source| InvalidSetting {
+            name,
+            value: value.to_string(),
+            source,
+            reason: if known.is_empty() {
+                "unknown model; llm_models is empty".to_string()
+            } else {
+                format!("unknown model; llm_models has {}", known.join(", "))
+            },
+        };
+        let is_known = |value: &str| models.iter().any(|model| model.matches(value));
+
+        let mut invalid = Vec::new();
+        let source = self.layers.source("DEFAULT_MODEL");
+        if source != Source::Default && !is_known(&self.default_model) {
+            invalid.push(unknown(
+                "DEFAULT_MODEL".to_string(),
+                &self.default_model,
+                source,
+            ));
+        }
+        for (username, overrides) in &self.users {
+            for model in overrides.models.iter().flatten() {
+                if !is_known(model) {
+                    invalid.push(unknown(
+                        format!("users.{username}.models"),
+                        model,
+                        Source::File,
+                    ));
+                }
+            }
+        }
+        invalid
+    }
+
+    /// `SQLITE_BUSY_TIMEOUT_MS` as a `Duration`, for [`crate::db_pool::DbPool::open`]
+    pub fn sqlite_busy_timeout(&self) -> Duration {
+        Duration::from_millis(self.sqlite_busy_timeout_ms)
+    }
+
+    /// Re-read the admin panel's `crawler` settings
+    ///
+    /// Returns the new configuration and logs what changed, or `None` when
+    /// nothing did. Settings that cannot be read or are invalid are reported and
+    /// the current configuration stays in effect.
+    pub async fn refresh(&self, pool: &DbPool) -> Option<Config> {
+        let stored = pool
+            .run(|database| database.load_stored_configuration(CONFIGURATION_KEY))
+            .await;
+        let refreshed = stored.map_err(anyhow::Error::from).and_then(|stored| {
+            Ok(Self::from_layers(
+                self.layers.with_database(stored.as_ref()),
+            )?)
+        });
+        let refreshed = match refreshed {
+            Ok(refreshed) => refreshed,
+            Err(error) => {
+                warn!("Ignoring the admin panel's crawler settings: {:#}", error);
+                return None;
+            }
+        };
+
+        let changes = self.changes(&refreshed);
+        if changes.is_empty() {
+            return None;
+        }
+        for change in changes {
+            info!("Configuration changed: {}", change);
+        }
+        Some(refreshed)
+    }
+
+    /// Settings the database layer can change, as `name: old (source) -> new (source)`
+    pub fn changes(&self, newer: &Config) -> Vec<String> {
+        let schedule_source = |config: &Config| {
+            config
+                .layers
+                .schedule_layer()
+                .map_or(Source::Default, |(source, _)| source)
+        };
+        let settings = |config: &Config| {
+            [
+                (
+                    "crawl schedule",
+                    config.crawl_schedule.describe(),
+                    schedule_source(config),
+                ),
+                (
+                    "history depth",
+                    format!("{} days", config.history_depth_days),
+                    config.layers.source("HISTORY_DEPTH_DAYS"),
+                ),
+                (
+                    "rate limit",
+                    format!("{} per 15 minutes", config.rate_limit_per_15min),
+                    config.layers.source("RATE_LIMIT_PER_15MIN"),
+                ),
+            ]
+        };
+        settings(self)
+            .into_iter()
+            .zip(settings(newer))
+            .filter(|(old, new)| old.1 != new.1)
+            .map(|((name, old, old_source), (_, new, new_source))| {
+                format!("{name}: {old} ({old_source}) -> {new} ({new_source})")
+            })
+            .collect()
+    }
+
+    /// Fail unless a Twitter bearer token is configured (only needed for fetching)
+    pub fn require_twitter_token(&self) -> anyhow::Result<()> {
+        if self.twitter_bearer_token.trim().is_empty() {
             return Err(anyhow::anyhow!(
                 "TWITTER_BEARER_TOKEN is required for the crawler"
             ));
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn later_layers_win_and_the_database_replaces_the_schedule() -> anyhow::Result<()> {
+        let (file, _) = file_layer(
+            "history_depth_days = 30\nrate_limit_per_15min = 100\n\n[ignored]\nkey = 1\n",
+        )?;
+        let layers = Layers {
+            file,
+            env: layer(&[
+                ("DATABASE_URL", ":memory:"),
+                ("HISTORY_DEPTH_DAYS", "60"),
+                ("CRAWL_CRON", "5 8-22 * * *"),
+            ]),
+            ..Layers::default()
+        };
+        let config = Config::from_layers(layers)?;
+        assert_eq!(config.history_depth_days, 60);
+        assert_eq!(config.rate_limit_per_15min, 100);
+        assert_eq!(config.layers.source("RATE_LIMIT_PER_15MIN"), Source::File);
+        assert_eq!(config.crawl_schedule.describe(), "cron '0 5 8-22 * * *'");
+
+        let stored = StoredConfiguration {
+            value: serde_json::json!({"intervalHours": 2, "historyDepthDays": 7}),
+            updated_by: Some("admin".to_string()),
+        };
+        let refreshed = Config::from_layers(config.layers.with_database(Some(&stored)))?;
+        assert_eq!(refreshed.history_depth_days, 7);
+        assert_eq!(refreshed.rate_limit_per_15min, 100);
+        assert_eq!(refreshed.crawl_schedule.describe(), "every 2 hour(s)");
+        assert_eq!(
+            config.changes(&refreshed),
+            [
+                "crawl schedule: cron '0 5 8-22 * * *' (environment) -> every 2 hour(s) (database)",
+                "history depth: 60 days (environment) -> 7 days (database)",
+            ]
+        );
 
-        Ok(Self {
-            database_url: env::var("DATABASE_URL")
-                .unwrap_or_else(|_| "../backend/data/twitter_feels.db".to_string()),
+        // Clearing the admin settings falls back to the lower layers.
+        let cleared = Config::from_layers(refreshed.layers.with_database(None))?;
+        assert_eq!(refreshed.changes(&cleared).len(), 2);
+        assert!(config.changes(&cleared).is_empty());
+        Ok(())
+    }
 
-            twitter_bearer_token,
+    #[test]
+    fn seeded_admin_settings_leave_the_lower_layers_alone() -> anyhow::Result<()> {
+        let layers = Layers {
+            env: layer(&[
+                ("DATABASE_URL", ":memory:"),
+                ("CRAWL_CRON", "5 8-22 * * *"),
+                ("HISTORY_DEPTH_DAYS", "30"),
+            ]),
+            ..Layers::default()
+        };
+        let seeded = |updated_by: Option<&str>| StoredConfiguration {
+            value: serde_json::json!({
+                "intervalHours": 1,
+                "historyDepthDays": 90,
+                "rateLimitPer15Min": 450,
+            }),
+            updated_by: updated_by.map(str::to_string),
+        };
 
-            crawl_interval_hours: env::var("CRAWL_INTERVAL_HOURS")
-                .ok()
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(1),
+        let config = Config::from_layers(layers.with_database(Some(&seeded(None))))?;
+        assert_eq!(config.crawl_schedule.describe(), "cron '0 5 8-22 * * *'");
+        assert_eq!(config.history_depth_days, 30);
+        assert_eq!(
+            config.layers.source("RATE_LIMIT_PER_15MIN"),
+            Source::Default
+        );
 
-            history_depth_days: env::var("HISTORY_DEPTH_DAYS")
-                .ok()
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(90),
+        // Once an admin saves the settings, even unchanged values apply.
+        let saved = Config::from_layers(layers.with_database(Some(&seeded(Some("admin")))))?;
+        assert_eq!(saved.crawl_schedule.describe(), "every 1 hour(s)");
+        assert_eq!(saved.history_depth_days, 90);
+        Ok(())
+    }
 
-            rate_limit_per_15min: env::var("RATE_LIMIT_PER_15MIN")
-                .ok()
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(450),
+    #[test]
+    fn every_invalid_setting_is_reported_with_its_source() {
+        let layers = Layers {
+            file: layer(&[("PRUNE_VACUUM", "sometimes"), ("HISTORY_DEPTH_DAYS", "30")]),
+            env: layer(&[
+                ("DATABASE_URL", "/nonexistent/crawler/twitter_feels.db"),
+                ("CRAWL_INTERVAL_HOURS", "0"),
+                ("HISTORY_DEPTH_DAYS", "-5"),
+                ("RETENTION_TWEETS_DAYS", "forever"),
+                ("DB_POOL_SIZE", ""),
+                ("SKIP_ANALYSIS_TWEET_TYPES", "quotes"),
+            ]),
+            database: layer(&[("RATE_LIMIT_PER_15MIN", "lots")]),
+            ..Layers::default()
+        };
+        let Err(InvalidConfig(invalid)) = Config::from_layers(layers.clone()) else {
+            panic!("expected the configuration to be rejected");
+        };
+        let reported: Vec<_> = invalid
+            .iter()
+            .map(|setting| {
+                (
+                    setting.name.as_str(),
+                    setting.value.as_str(),
+                    setting.source,
+                )
+            })
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                (
+                    "DATABASE_URL",
+                    "/nonexistent/crawler/twitter_feels.db",
+                    Source::Env
+                ),
+                ("CRAWL_INTERVAL_HOURS", "0", Source::Env),
+                ("RETENTION_TWEETS_DAYS", "forever", Source::Env),
+                ("PRUNE_VACUUM", "sometimes", Source::File),
+                ("HISTORY_DEPTH_DAYS", "-5", Source::Env),
+                ("RATE_LIMIT_PER_15MIN", "lots", Source::Database),
+                ("SKIP_ANALYSIS_TWEET_TYPES", "quotes", Source::Env),
+            ]
+        );
 
-            default_model: env::var("DEFAULT_MODEL")
-                .unwrap_or_else(|_| "meta-llama/Llama-3.2-3B-Instruct".to_string()),
+        // Invalid values fall back to their defaults; blank ones count as unset.
+        let (config, _) = Config::resolve(layers);
+        assert_eq!(config.history_depth_days, 90);
+        assert_eq!(config.db_pool_size, 8);
+        assert_eq!(config.crawl_schedule.describe(), "every 1 hour(s)");
+        assert_eq!(config.skip_analysis.names(), ["retweets"]);
 
-            huggingface_token: env::var("HUGGINGFACE_TOKEN").ok(),
-        })
+        let models = [LlmModel {
+            id: 1,
+            name: "Llama".to_string(),
+            huggingface_model_id: Some("meta-llama/Llama-3.2-3B-Instruct".to_string()),
+            is_enabled: true,
+        }];
+        assert_eq!(config.check_models(&models), []);
+        let (config, _) = Config::resolve(Layers {
+            env: layer(&[("DEFAULT_MODEL", "gpt-5")]),
+            ..Layers::default()
+        });
+        let unknown = config.check_models(&models);
+        assert_eq!(
+            unknown[0].to_string(),
+            "DEFAULT_MODEL = \"gpt-5\" (environment): unknown model; llm_models has meta-llama/Llama-3.2-3B-Instruct"
+        );
+    }
+
+    #[test]
+    fn user_tables_override_single_users() -> anyhow::Result<()> {
+        let (file, users) = file_layer(
+            r#"
+history_depth_days = 30
+
+[users.ElonMusk]
+history_depth_days = 7
+crawl_interval_hours = 6
+include_retweets = false
+models = ["org/model"]
+
+[users.typo]
+history_depth = 3
+
+[users.zero]
+crawl_interval_minutes = 0
+include_replies = false
+"#,
+        )?;
+        let layers = Layers {
+            file,
+            users,
+            env: layer(&[("DATABASE_URL", ":memory:")]),
+            ..Layers::default()
+        };
+        let (config, invalid) = Config::resolve(layers);
+        assert_eq!(config.history_depth_days, 30);
+        assert_eq!(
+            config.user("@elonmusk"),
+            Some(&UserOverrides {
+                history_depth_days: Some(7),
+                crawl_interval_minutes: Some(360),
+                include_replies: None,
+                include_retweets: Some(false),
+                models: Some(vec!["org/model".to_string()]),
+            })
+        );
+        assert_eq!(
+            config.user("zero"),
+            Some(&UserOverrides {
+                include_replies: Some(false),
+                ..UserOverrides::default()
+            })
+        );
+        assert_eq!(config.user("typo"), None);
+        let names: Vec<_> = invalid
+            .iter()
+            .map(|setting| setting.name.as_str())
+            .collect();
+        assert_eq!(names, ["users.typo", "users.zero.crawl_interval_minutes"]);
+
+        let models = [LlmModel {
+            id: 1,
+            name: "Other".to_string(),
+            huggingface_model_id: Some("org/other".to_string()),
+            is_enabled: true,
+        }];
+        let unknown = config.check_models(&models);
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].name, "users.elonmusk.models");
+        assert_eq!(unknown[0].value, "org/model");
+        Ok(())
     }
 }
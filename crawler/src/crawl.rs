@@ -0,0 +1,1090 @@
+//! Crawl cycle: fetch tweets for tracked users and enqueue analysis jobs
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::{DateTime, Duration, Utc};
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::config::{Config, UserOverrides};
+use crate::db_pool::{DbPool, DbTask};
+use crate::error::CrawlerError;
+use crate::models::{
+    FetchProgress, LlmModel, ReanalysisRequest, RunProgress, TwitterApiError, TwitterApiTweet,
+    UserCrawlOutcome,
+};
+use crate::rate_limit::{build_rate_limiter, RATE_LIMIT_WINDOW};
+use crate::shutdown::Shutdown;
+use crate::status::StatusBoard;
+use crate::storage::{ApiErrorDetail, Storage, TweetTypes};
+use crate::telemetry;
+use crate::twitter_api::TwitterApiClient;
+
+/// Tweets enqueued per transaction by `user` and `all` reanalysis requests
+const REANALYSIS_CHUNK_SIZE: i64 = 500;
+
+/// Knobs for a single crawl cycle
+#[derive(Debug, Default, Clone)]
+pub struct CrawlOptions {
+    /// Only crawl this tracked username
+    pub username: Option<String>,
+
+    /// Explicit fetch window; checkpoints are ignored when set
+    pub window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+
+    /// Skip pending reanalysis requests (backfills, or when the pipeline dispatcher owns them)
+    pub skip_reanalysis: bool,
+}
+
+/// Totals collected while a crawl cycle runs
+#[derive(Debug)]
+pub struct CrawlReport {
+    pub run_id: i64,
+    pub status: &'static str,
+    pub tweets_fetched: u64,
+    pub tweets_queued: u64,
+    pub error_details: Vec<ApiErrorDetail>,
+    pub users_total: usize,
+    pub users_completed: usize,
+    pub current_user: Option<String>,
+    started: Instant,
+    progress_interval: std::time::Duration,
+    last_progress_write: Option<Instant>,
+    board: Arc<StatusBoard>,
+}
+
+impl CrawlReport {
+    fn new(run_id: i64, progress_interval: std::time::Duration, board: Arc<StatusBoard>) -> Self {
+        Self {
+            run_id,
+            status: "completed",
+            tweets_fetched: 0,
+            tweets_queued: 0,
+            error_details: Vec::new(),
+            users_total: 0,
+            users_completed: 0,
+            current_user: None,
+            started: Instant::now(),
+            progress_interval,
+            last_progress_write: None,
+            board,
+        }
+    }
+
+    /// Publish live progress to the status board, and to `crawler_runs` at most
+    /// once per progress interval unless `force` is set, to keep SQLite write
+    /// contention down
+    async fn write_progress(&mut self, pool: &DbPool, force: bool) {
+        let progress = RunProgress {
+            users_total: self.users_total as i64,
+            users_completed: self.users_completed as i64,
+            current_user: self.current_user.clone(),
+            tweets_fetched: self.tweets_fetched as i64,
+            jobs_queued: self.tweets_queued as i64,
+            errors_count: self.error_details.len() as i64,
+            eta: self.estimate_completion(),
+        };
+        self.board.run_progress(self.run_id, &progress);
+
+        let due = self
+            .last_progress_write
+            .is_none_or(|last| last.elapsed() >= self.progress_interval);
+        if !force && !due {
+            return;
+        }
+        self.last_progress_write = Some(Instant::now());
+
+        let run_id = self.run_id;
+        let written = pool
+            .run(move |database| database.update_crawler_run_progress(run_id, &progress))
+            .await;
+        if let Err(error) = written {
+            warn!("Failed to write crawl progress: {}", error);
+        }
+    }
+
+    /// Linear ETA from the average time spent per finished user
+    fn estimate_completion(&self) -> Option<DateTime<Utc>> {
+        if self.users_completed == 0 || self.users_total <= self.users_completed {
+            return None;
+        }
+        let per_user = self.started.elapsed() / self.users_completed as u32;
+        let remaining = per_user * (self.users_total - self.users_completed) as u32;
+        Duration::from_std(remaining).ok().map(|left| Utc::now() + left)
+    }
+}
+
+pub async fn run_crawl_cycle(
+    pool: &DbPool,
+    config: &Config,
+    options: &CrawlOptions,
+    shutdown: &Arc<Shutdown>,
+    board: &Arc<StatusBoard>,
+) -> anyhow::Result<CrawlReport> {
+    info!("Starting crawl cycle...");
+    let cycle_started = Instant::now();
+
+    let run_id = pool.run(|database| database.create_crawler_run()).await?;
+    let mut report = CrawlReport::new(
+        run_id,
+        std::time::Duration::from_secs(config.progress_update_secs),
+        board.clone(),
+    );
+    board.run_started(run_id);
+    board.configure_rate_limit("twitter", config.rate_limit_per_15min, RATE_LIMIT_WINDOW);
+
+    let rate_limiter = build_rate_limiter(config.rate_limit_per_15min)?;
+    let twitter_client = TwitterApiClient::new(config.twitter_bearer_token.clone(), rate_limiter)?;
+
+    // Every log line of the cycle carries run_id so it can be joined to crawler_runs.
+    let run_span = info_span!("crawl_run", run_id);
+    let cycle_result = perform_crawl(
+        pool,
+        &twitter_client,
+        config,
+        options,
+        shutdown,
+        &mut report,
+    )
+    .instrument(run_span.clone())
+    .await;
+
+    if let Err(error) = &cycle_result {
+        report.status = "failed";
+        record_error(
+            pool,
+            &mut report.error_details,
+            error_kind(error),
+            format!("Crawler cycle error: {error}"),
+            None,
+            None,
+        )
+        .instrument(run_span.clone())
+        .await;
+    }
+    let _run_span = run_span.enter();
+
+    let (status, tweets_fetched, tweets_queued) =
+        (report.status, report.tweets_fetched, report.tweets_queued);
+    let error_details = report.error_details.clone();
+    pool.run(move |database| {
+        database.complete_crawler_run(run_id, status, tweets_fetched, tweets_queued, &error_details)
+    })
+    .await?;
+    board.run_finished(run_id, report.status);
+    telemetry::record_cycle(report.status, cycle_started.elapsed());
+
+    if let Err(error) = cycle_result {
+        return Err(error.into());
+    }
+
+    Ok(report)
+}
+
+pub async fn perform_crawl(
+    pool: &DbPool,
+    twitter_client: &TwitterApiClient,
+    config: &Config,
+    options: &CrawlOptions,
+    shutdown: &Arc<Shutdown>,
+    report: &mut CrawlReport,
+) -> Result<(), CrawlerError> {
+    let models = pool.run(|database| database.load_models()).await?;
+    let enabled_models: Vec<i64> = models
+        .iter()
+        .filter(|model| model.is_enabled)
+        .map(|model| model.id)
+        .collect();
+    if !options.skip_reanalysis {
+        let models = enabled_models.clone();
+        let skip_analysis = config.skip_analysis;
+        let shutdown = shutdown.clone();
+        let dispatched = pool
+            .run(move |database| {
+                let mut error_details = Vec::new();
+                let result = process_reanalysis_requests(
+                    database,
+                    &models,
+                    skip_analysis,
+                    &shutdown,
+                    &mut error_details,
+                );
+                Ok((result, error_details))
+            })
+            .await;
+        let result = dispatched.and_then(|(result, error_details)| {
+            report.error_details.extend(error_details);
+            result
+        });
+        // Reanalysis problems are reported but never stop the crawl.
+        if let Err(error) = result {
+            record_error(
+                pool,
+                &mut report.error_details,
+                error_kind(&error),
+                format!("Reanalysis requests not processed: {error}"),
+                None,
+                None,
+            )
+            .await;
+        }
+    }
+
+    let mut active_users = pool.run(|database| database.load_active_users()).await?;
+    if let Some(username) = &options.username {
+        active_users.retain(|user| user.username.eq_ignore_ascii_case(username));
+        if active_users.is_empty() {
+            return Err(CrawlerError::Config(format!(
+                "@{username} is not an active tracked user"
+            )));
+        }
+    }
+
+    if active_users.is_empty() {
+        info!("No active users to crawl.");
+        return Ok(());
+    }
+
+    let usernames: Vec<String> = active_users
+        .iter()
+        .map(|user| user.username.clone())
+        .collect();
+
+    let users_response = twitter_client.fetch_users_by_usernames(&usernames).await?;
+    if let Some(errors) = users_response.errors {
+        for api_error in errors {
+            record_twitter_api_error(
+                pool,
+                &mut report.error_details,
+                "api_change",
+                &api_error,
+                Some("/2/users/by"),
+            )
+            .await;
+        }
+    }
+
+    let mut api_users = HashMap::new();
+    if let Some(users) = users_response.data {
+        for user in users {
+            api_users.insert(user.username.clone(), user);
+        }
+    }
+
+    let cycle_started = Utc::now();
+
+    report.users_total = active_users.len();
+    report.write_progress(pool, true).await;
+
+    for (index, tracked_user) in active_users.into_iter().enumerate() {
+        report.users_completed = index;
+        report.current_user = Some(tracked_user.username.clone());
+        report.write_progress(pool, false).await;
+
+        if shutdown.is_requested() {
+            info!("Shutdown requested, cancelling crawl before @{}", tracked_user.username);
+            report.status = "cancelled";
+            break;
+        }
+
+        let overrides = config.user(&tracked_user.username);
+        if let Some(due) = next_crawl_due(pool, options, tracked_user.id, overrides).await? {
+            info!("Skipping @{} until {}", tracked_user.username, due.to_rfc3339());
+            continue;
+        }
+
+        let user_started = Instant::now();
+        let mut outcome = UserCrawlOutcome {
+            status: "completed",
+            tweets_fetched: 0,
+            pages_fetched: 0,
+            api_calls: 0,
+            duration_ms: 0,
+            api_error_id: None,
+            error_message: None,
+        };
+
+        let user_models = match user_models(&models, &tracked_user.username, overrides) {
+            Ok(user_models) => user_models,
+            Err(error) => {
+                outcome.api_error_id = record_error(
+                    pool,
+                    &mut report.error_details,
+                    error_kind(&error),
+                    error.to_string(),
+                    None,
+                    None,
+                )
+                .await;
+                outcome.status = "failed";
+                outcome.error_message = Some(error.to_string());
+                finish_user(pool, report, tracked_user.id, user_started, outcome).await;
+                continue;
+            }
+        };
+
+        let Some(api_user) = api_users.get(&tracked_user.username) else {
+            let message = format!("Twitter user not found: @{}", tracked_user.username);
+            outcome.api_error_id = record_error(
+                pool,
+                &mut report.error_details,
+                "api_change",
+                message.clone(),
+                None,
+                Some("/2/users/by"),
+            )
+            .await;
+            outcome.status = "not_found";
+            outcome.error_message = Some(message);
+            finish_user(pool, report, tracked_user.id, user_started, outcome).await;
+            continue;
+        };
+
+        let username = tracked_user.username.clone();
+        let api_user_update = api_user.clone();
+        let twitter_user_id = tracked_user.id;
+        let (checkpoint, saved_progress) = pool
+            .run(move |database| {
+                let api_user = api_user_update;
+                let metrics = api_user.public_metrics.as_ref();
+                database.update_user_from_api(
+                    &username,
+                    &api_user.id,
+                    Some(&api_user.name),
+                    api_user.profile_image_url.as_deref(),
+                    metrics.and_then(|metrics| metrics.followers_count),
+                    metrics.and_then(|metrics| metrics.following_count),
+                )?;
+                Ok((
+                    database.get_checkpoint(twitter_user_id)?,
+                    database.get_fetch_progress(twitter_user_id)?,
+                ))
+            })
+            .await?;
+        let history_depth_days = overrides
+            .and_then(|overrides| overrides.history_depth_days)
+            .unwrap_or(config.history_depth_days);
+        let base_start_time = cycle_started - Duration::days(history_depth_days as i64);
+        let (start_time, end_time) = match options.window {
+            Some((start, end)) => (start, Some(end)),
+            None => match checkpoint {
+                Some(checkpoint) if checkpoint > base_start_time => {
+                    (checkpoint + Duration::seconds(1), None)
+                }
+                _ => (base_start_time, None),
+            },
+        };
+
+        // Resume an interrupted fetch of the same kind (scheduled crawl vs. backfill window).
+        let resume = saved_progress.filter(|progress| match options.window {
+                Some((start, end)) => progress.start_time == start && progress.end_time == Some(end),
+                None => progress.end_time.is_none(),
+            });
+        let mut progress = resume.unwrap_or(FetchProgress {
+            start_time,
+            end_time,
+            pagination_token: String::new(),
+            newest_timestamp: None,
+        });
+        if !progress.pagination_token.is_empty() {
+            info!("Resuming interrupted fetch for @{}", tracked_user.username);
+        }
+
+        let fetch = UserFetch {
+            twitter_user_id: tracked_user.id,
+            username: &tracked_user.username,
+            api_user_id: &api_user.id,
+            exclude: excluded_tweet_types(config.exclude_tweet_types, overrides).names(),
+            skip_analysis: config.skip_analysis,
+            enabled_models: user_models.as_deref().unwrap_or(&enabled_models),
+        };
+        let fetched = fetch_user_pages(
+            pool,
+            twitter_client,
+            &fetch,
+            &mut progress,
+            shutdown,
+            report,
+            &mut outcome,
+        )
+        .instrument(info_span!(
+            "crawl_user",
+            username = %tracked_user.username,
+            twitter_user_id = tracked_user.id
+        ))
+        .await;
+        let finished = match fetched {
+            Ok(finished) => finished,
+            Err(error) => {
+                outcome.api_error_id = record_error(
+                    pool,
+                    &mut report.error_details,
+                    error_kind(&error),
+                    format!("Failed fetching tweets for @{}", tracked_user.username),
+                    None,
+                    Some("/2/users/:id/tweets"),
+                )
+                .await;
+                outcome.status = "failed";
+                outcome.error_message = Some(error.to_string());
+                finish_user(pool, report, tracked_user.id, user_started, outcome).await;
+                if should_abort_on_error(&error) {
+                    report.status = "failed";
+                    return Err(error);
+                }
+                continue;
+            }
+        };
+
+        if !finished {
+            info!(
+                "Drain timeout reached, fetch for @{} checkpointed mid-timeline",
+                tracked_user.username
+            );
+            outcome.status = "cancelled";
+            finish_user(pool, report, tracked_user.id, user_started, outcome).await;
+            report.status = "cancelled";
+            break;
+        }
+
+        // Backfills can fetch tweets older than the checkpoint; never move it backwards.
+        if let Some(latest_timestamp) = progress.newest_timestamp {
+            if checkpoint.is_none_or(|current| latest_timestamp > current) {
+                pool.run(move |database| database.set_checkpoint(twitter_user_id, latest_timestamp))
+                    .await?;
+            }
+        }
+        finish_user(pool, report, tracked_user.id, user_started, outcome).await;
+    }
+
+    if report.status != "cancelled" {
+        report.users_completed = report.users_total;
+    }
+    report.current_user = None;
+
+    info!(
+        "Crawl cycle {}: {} tweets fetched, {} analysis jobs queued",
+        report.status, report.tweets_fetched, report.tweets_queued
+    );
+
+    Ok(())
+}
+
+struct UserFetch<'a> {
+    twitter_user_id: i64,
+    username: &'a str,
+    api_user_id: &'a str,
+    /// Tweet types left out of the timeline request
+    exclude: Vec<&'static str>,
+    /// Tweet types stored without analysis jobs
+    skip_analysis: TweetTypes,
+    enabled_models: &'a [i64],
+}
+
+/// When a user with its own crawl interval is next due, if that is still in
+/// the future
+///
+/// Intervals are measured between the starts of completed crawls, with a
+/// minute of slack so a user reached slightly earlier in the next cycle is not
+/// pushed back a whole cycle. Backfills and single-user crawls never skip.
+async fn next_crawl_due(
+    pool: &DbPool,
+    options: &CrawlOptions,
+    twitter_user_id: i64,
+    overrides: Option<&UserOverrides>,
+) -> Result<Option<DateTime<Utc>>, CrawlerError> {
+    let Some(interval) = overrides.and_then(|overrides| overrides.crawl_interval_minutes) else {
+        return Ok(None);
+    };
+    if options.window.is_some() || options.username.is_some() {
+        return Ok(None);
+    }
+    let last_started = pool
+        .run(move |database| database.last_crawl_started(twitter_user_id))
+        .await?;
+    Ok(last_started
+        .map(|started| started + Duration::minutes(i64::from(interval)))
+        .filter(|due| *due - Duration::minutes(1) > Utc::now()))
+}
+
+/// Enabled models among a user's configured ones; `None` without an override
+///
+/// An override that leaves no enabled model is an error, since jobs without a
+/// model are never claimed.
+fn user_models(
+    models: &[LlmModel],
+    username: &str,
+    overrides: Option<&UserOverrides>,
+) -> Result<Option<Vec<i64>>, CrawlerError> {
+    let Some(wanted) = overrides.and_then(|overrides| overrides.models.as_ref()) else {
+        return Ok(None);
+    };
+    for name in wanted {
+        if !models.iter().any(|model| model.is_enabled && model.matches(name)) {
+            warn!("Model {} configured for @{} is not an enabled model", name, username);
+        }
+    }
+    let ids: Vec<i64> = models
+        .iter()
+        .filter(|model| model.is_enabled && wanted.iter().any(|name| model.matches(name)))
+        .map(|model| model.id)
+        .collect();
+    if ids.is_empty() {
+        return Err(CrawlerError::Config(format!(
+            "None of the models configured for @{username} is enabled: {}",
+            wanted.join(", ")
+        )));
+    }
+    Ok(Some(ids))
+}
+
+/// `EXCLUDE_TWEET_TYPES`, with a user's `include_*` overrides applied
+fn excluded_tweet_types(excluded: TweetTypes, overrides: Option<&UserOverrides>) -> TweetTypes {
+    let Some(overrides) = overrides else {
+        return excluded;
+    };
+    TweetTypes {
+        replies: overrides.include_replies.map_or(excluded.replies, |include| !include),
+        retweets: overrides.include_retweets.map_or(excluded.retweets, |include| !include),
+    }
+}
+
+/// Page through a user's timeline, storing each page and persisting the next
+/// pagination token so an interrupted fetch can resume.
+///
+/// A page is stored while the next one is being fetched; its pagination token
+/// is only saved once the page itself has landed.
+///
+/// Returns `false` when the drain timeout cut the fetch short.
+async fn fetch_user_pages(
+    pool: &DbPool,
+    twitter_client: &TwitterApiClient,
+    fetch: &UserFetch<'_>,
+    progress: &mut FetchProgress,
+    shutdown: &Shutdown,
+    report: &mut CrawlReport,
+    outcome: &mut UserCrawlOutcome,
+) -> Result<bool, CrawlerError> {
+    let mut storing = None;
+    loop {
+        let token = (!progress.pagination_token.is_empty()).then_some(progress.pagination_token.as_str());
+        outcome.api_calls += 1;
+        let next_page = async {
+            tokio::select! {
+                biased;
+                _ = shutdown.drain_expired() => None,
+                page = twitter_client.fetch_user_tweets_page(
+                    fetch.api_user_id,
+                    progress.start_time,
+                    progress.end_time,
+                    &fetch.exclude,
+                    token,
+                ) => Some(page),
+            }
+        };
+        let (page, stored) = tokio::join!(next_page, wait_stored(storing.take()));
+        if let Some(stored) = stored? {
+            page_stored(pool, report, outcome, progress, stored).await;
+        }
+        let Some(page) = page else {
+            return Ok(false);
+        };
+        let page = page?;
+
+        outcome.pages_fetched += 1;
+        if let Some(remaining) = twitter_client.remaining_requests() {
+            report.board.set_rate_remaining("twitter", remaining);
+        }
+
+        for api_error in &page.errors {
+            let error_id = record_twitter_api_error(
+                pool,
+                &mut report.error_details,
+                "api_change",
+                api_error,
+                Some("/2/users/:id/tweets"),
+            )
+            .await;
+            outcome.api_error_id = error_id.or(outcome.api_error_id);
+        }
+
+        let finished = page.next_token.is_none();
+        if let Some(next_token) = page.next_token {
+            progress.pagination_token = next_token;
+        }
+        let task = store_page(pool, fetch, progress.clone(), page.tweets, finished);
+
+        if finished || shutdown.is_drain_expired() {
+            page_stored(pool, report, outcome, progress, task.wait().await?).await;
+            return Ok(finished);
+        }
+        storing = Some(task);
+
+        if shutdown.is_requested() {
+            info!("Shutdown requested, finishing timeline for @{}", fetch.username);
+        }
+    }
+}
+
+/// What storing one page of tweets wrote
+struct StoredPage {
+    inserted: u64,
+    enqueued: u64,
+    latest: Option<DateTime<Utc>>,
+}
+
+/// Insert a page and its analysis jobs, then save the token for the next page
+/// (or clear it once the timeline is done)
+fn store_page(
+    pool: &DbPool,
+    fetch: &UserFetch<'_>,
+    mut progress: FetchProgress,
+    tweets: Vec<TwitterApiTweet>,
+    finished: bool,
+) -> DbTask<StoredPage> {
+    let twitter_user_id = fetch.twitter_user_id;
+    let enabled_models = fetch.enabled_models.to_vec();
+    let skip_analysis = fetch.skip_analysis;
+    pool.spawn(move |database| {
+        let (inserted, enqueued, latest) = database.insert_tweets_and_enqueue(
+            twitter_user_id,
+            &tweets,
+            &enabled_models,
+            skip_analysis,
+        )?;
+        if finished {
+            database.clear_fetch_progress(twitter_user_id)?;
+        } else {
+            if latest > progress.newest_timestamp {
+                progress.newest_timestamp = latest;
+            }
+            database.save_fetch_progress(twitter_user_id, &progress)?;
+        }
+        Ok(StoredPage {
+            inserted,
+            enqueued,
+            latest,
+        })
+    })
+}
+
+async fn wait_stored(
+    storing: Option<DbTask<StoredPage>>,
+) -> Result<Option<StoredPage>, CrawlerError> {
+    match storing {
+        Some(task) => task.wait().await.map(Some),
+        None => Ok(None),
+    }
+}
+
+async fn page_stored(
+    pool: &DbPool,
+    report: &mut CrawlReport,
+    outcome: &mut UserCrawlOutcome,
+    progress: &mut FetchProgress,
+    stored: StoredPage,
+) {
+    report.tweets_fetched += stored.inserted;
+    report.tweets_queued += stored.enqueued;
+    outcome.tweets_fetched += stored.inserted;
+    if stored.latest > progress.newest_timestamp {
+        progress.newest_timestamp = stored.latest;
+    }
+    report.write_progress(pool, false).await;
+}
+
+/// Turn pending reanalysis requests into analysis jobs (the pipeline's dispatcher stage)
+pub fn dispatch_reanalysis_requests(
+    database: &dyn Storage,
+    skip_analysis: TweetTypes,
+    shutdown: &Shutdown,
+) -> Result<usize, CrawlerError> {
+    let enabled_models = database.get_enabled_model_ids()?;
+    let mut error_details = Vec::new();
+    process_reanalysis_requests(
+        database,
+        &enabled_models,
+        skip_analysis,
+        shutdown,
+        &mut error_details,
+    )
+}
+
+/// `skip_analysis` applies to reanalysis as it does to new tweets, so a `user`
+/// or `all` request does not score retweets the crawl stored without analysis
+fn process_reanalysis_requests(
+    database: &dyn Storage,
+    enabled_models: &[i64],
+    skip_analysis: TweetTypes,
+    shutdown: &Shutdown,
+    error_details: &mut Vec<ApiErrorDetail>,
+) -> Result<usize, CrawlerError> {
+    // Requests are claimed one at a time, so a lease only runs while its
+    // request is being walked.
+    let mut processed = 0;
+    while processed < 25 && !shutdown.is_requested() {
+        let Some(request) = database.claim_reanalysis_requests(1)?.pop() else {
+            break;
+        };
+
+        // A request that cannot be processed is closed as failed rather than
+        // retried forever, and never stops the rest of the batch.
+        match process_reanalysis_request(
+            database,
+            &request,
+            enabled_models,
+            skip_analysis,
+            shutdown,
+        ) {
+            // Interrupted by shutdown; the stored cursor resumes it next time.
+            Ok(false) => {
+                if let Err(error) = database.release_reanalysis_request(request.id) {
+                    warn!("Failed to release reanalysis request {}: {}", request.id, error);
+                }
+                break;
+            }
+            Ok(true) => {}
+            Err(error) => {
+                let detail = error_detail(
+                    error_kind(&error),
+                    format!("Failed reanalysis request {}: {error}", request.id),
+                    None,
+                    None,
+                );
+                store_error(database, &detail);
+                error_details.push(detail);
+                if let Err(error) = database.mark_reanalysis_failed(request.id, &error.to_string()) {
+                    warn!("Failed to mark reanalysis request {} failed: {}", request.id, error);
+                }
+            }
+        }
+        processed += 1;
+    }
+
+    Ok(processed)
+}
+
+/// Enqueue one request, or only estimate it when it is a dry run
+///
+/// Returns `false` when shutdown interrupted it.
+fn process_reanalysis_request(
+    database: &dyn Storage,
+    request: &ReanalysisRequest,
+    enabled_models: &[i64],
+    skip_analysis: TweetTypes,
+    shutdown: &Shutdown,
+) -> Result<bool, CrawlerError> {
+    match request.request_type.as_str() {
+        "tweet" if request.tweet_id.is_none() => {
+            return Err(CrawlerError::Config(
+                "Missing tweet_id for reanalysis request".to_string(),
+            ))
+        }
+        "user" if request.twitter_user_id.is_none() => {
+            return Err(CrawlerError::Config(
+                "Missing twitter_user_id for reanalysis request".to_string(),
+            ))
+        }
+        "tweet" | "user" | "all" => {}
+        other => {
+            return Err(CrawlerError::Config(format!(
+                "Unknown reanalysis request type: {other}"
+            )))
+        }
+    }
+
+    let models = match request.llm_model_id {
+        Some(model_id) if enabled_models.contains(&model_id) => vec![model_id],
+        Some(model_id) => {
+            return Err(CrawlerError::Config(format!(
+                "Reanalysis model {model_id} is not enabled"
+            )))
+        }
+        // Jobs without a model would never be claimed.
+        None if enabled_models.is_empty() => {
+            return Err(CrawlerError::Config(
+                "No enabled models for reanalysis".to_string(),
+            ))
+        }
+        None => enabled_models.to_vec(),
+    };
+
+    if request.dry_run {
+        let jobs = database.estimate_reanalysis_jobs(request, &models, skip_analysis)?;
+        database.record_reanalysis_estimate(request.id, jobs)?;
+        info!("Reanalysis request {} is a dry run and would enqueue {} jobs", request.id, jobs);
+        return Ok(true);
+    }
+
+    if !enqueue_in_chunks(database, request, &models, skip_analysis, shutdown)? {
+        return Ok(false);
+    }
+    database.mark_reanalysis_completed(request.id)?;
+    Ok(true)
+}
+
+/// Walk the request's tweets in id order, one transaction per chunk, so the
+/// writer lock is released between chunks and a crash loses at most one chunk.
+///
+/// Returns `false` when shutdown interrupted the walk.
+fn enqueue_in_chunks(
+    database: &dyn Storage,
+    request: &ReanalysisRequest,
+    models: &[i64],
+    skip_analysis: TweetTypes,
+    shutdown: &Shutdown,
+) -> Result<bool, CrawlerError> {
+    let tweets_total = match request.tweets_total {
+        Some(_) => None,
+        None => Some(database.count_reanalysis_tweets(request, skip_analysis)?),
+    };
+    database.mark_reanalysis_processing(request.id, tweets_total)?;
+
+    let mut cursor = request.cursor_tweet_id.unwrap_or(0);
+    if cursor > 0 {
+        info!("Resuming reanalysis request {} after tweet {}", request.id, cursor);
+    }
+    let mut jobs_enqueued = 0;
+    loop {
+        if shutdown.is_requested() {
+            info!("Shutdown requested, reanalysis request {} paused after tweet {}", request.id, cursor);
+            return Ok(false);
+        }
+
+        let chunk = database.enqueue_reanalysis_chunk(
+            request,
+            cursor,
+            REANALYSIS_CHUNK_SIZE,
+            models,
+            skip_analysis,
+        )?;
+        jobs_enqueued += chunk.jobs_enqueued;
+        match chunk.last_tweet_id {
+            Some(last) if chunk.tweets as i64 == REANALYSIS_CHUNK_SIZE => cursor = last,
+            _ => break,
+        }
+    }
+
+    info!("Reanalysis request {} enqueued {} jobs", request.id, jobs_enqueued);
+    Ok(true)
+}
+
+async fn record_twitter_api_error(
+    pool: &DbPool,
+    error_details: &mut Vec<ApiErrorDetail>,
+    error_type: &str,
+    api_error: &TwitterApiError,
+    endpoint: Option<&str>,
+) -> Option<i64> {
+    let title = api_error.title.as_deref().unwrap_or("Twitter API error");
+    let detail = api_error.detail.as_deref().unwrap_or("Unknown error");
+    let message = format!("{title}: {detail}");
+    record_error(
+        pool,
+        error_details,
+        error_type,
+        message,
+        api_error.type_.as_deref(),
+        endpoint,
+    )
+    .await
+}
+
+async fn record_error(
+    pool: &DbPool,
+    error_details: &mut Vec<ApiErrorDetail>,
+    error_type: &str,
+    message: String,
+    code: Option<&str>,
+    endpoint: Option<&str>,
+) -> Option<i64> {
+    let detail = error_detail(error_type, message, code, endpoint);
+    error_details.push(detail.clone());
+
+    pool.run(move |database| Ok(store_error(database, &detail)))
+        .await
+        .unwrap_or_else(|error| {
+            warn!("Failed to record api error: {}", error);
+            None
+        })
+}
+
+fn error_detail(
+    error_type: &str,
+    message: String,
+    code: Option<&str>,
+    endpoint: Option<&str>,
+) -> ApiErrorDetail {
+    ApiErrorDetail {
+        error_type: error_type.to_string(),
+        message,
+        code: code.map(|value| value.to_string()),
+        endpoint: endpoint.map(|value| value.to_string()),
+        timestamp: Utc::now().to_rfc3339(),
+    }
+}
+
+/// Insert an `api_errors` row; bookkeeping failures never fail the crawl
+fn store_error(database: &dyn Storage, detail: &ApiErrorDetail) -> Option<i64> {
+    match database.insert_api_error(
+        &detail.error_type,
+        &detail.message,
+        detail.code.as_deref(),
+        detail.endpoint.as_deref(),
+    ) {
+        Ok(id) => Some(id),
+        Err(error) => {
+            warn!("Failed to record api error: {}", error);
+            None
+        }
+    }
+}
+
+/// Store a user's outcome for this run; bookkeeping failures never fail the crawl
+async fn finish_user(
+    pool: &DbPool,
+    report: &CrawlReport,
+    twitter_user_id: i64,
+    started: Instant,
+    mut outcome: UserCrawlOutcome,
+) {
+    outcome.duration_ms = started.elapsed().as_millis() as i64;
+    let run_id = report.run_id;
+    let recorded = pool
+        .run(move |database| database.record_run_user(run_id, twitter_user_id, &outcome))
+        .await;
+    if let Err(error) = recorded {
+        warn!("Failed to record crawl outcome: {}", error);
+    }
+}
+
+fn error_kind(error: &CrawlerError) -> &'static str {
+    match error {
+        CrawlerError::RateLimitExceeded => "rate_limit",
+        CrawlerError::Authentication(_) => "auth",
+        CrawlerError::Network(_) => "network",
+        CrawlerError::TwitterApi(_) => "api_change",
+        _ => "other",
+    }
+}
+
+fn should_abort_on_error(error: &CrawlerError) -> bool {
+    matches!(
+        error,
+        CrawlerError::RateLimitExceeded | CrawlerError::Authentication(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SqliteDatabase;
+
+    #[test]
+    fn bad_reanalysis_requests_fail_without_stopping_the_batch() -> Result<(), CrawlerError> {
+        let database = SqliteDatabase::new(":memory:", std::time::Duration::from_secs(1))?;
+        database.init_schema()?;
+        database.execute_sql(
+            "INSERT INTO twitter_users (id, twitter_id, username, display_name, is_active)
+             VALUES (5, 'user_5', 'user5', 'User Five', 1);
+             INSERT INTO tweets (id, twitter_user_id, tweet_id, content, tweet_timestamp)
+             VALUES (1, 5, 'tweet_1', 'hello', '2024-01-10T12:00:00+00:00');
+             INSERT INTO llm_models (id, name, huggingface_model_id, is_enabled)
+             VALUES (1, 'Model', 'org/model', 1);
+             INSERT INTO reanalysis_requests (id, request_type) VALUES (1, 'tweet');
+             INSERT INTO reanalysis_requests (id, request_type, tweets_from) VALUES (2, 'all', 'soon');
+             INSERT INTO reanalysis_requests (id, request_type, llm_model_id) VALUES (3, 'all', 9);
+             INSERT INTO reanalysis_requests (id, request_type) VALUES (4, 'everything');
+             INSERT INTO reanalysis_requests (id, request_type) VALUES (5, 'all')",
+        )?;
+
+        let mut error_details = Vec::new();
+        let processed = process_reanalysis_requests(
+            &database,
+            &[1],
+            TweetTypes::default(),
+            &Shutdown::new(),
+            &mut error_details,
+        )?;
+        assert_eq!(processed, 4);
+        assert_eq!(error_details.len(), 3);
+
+        let status = |id: i64| {
+            database.query_text(&format!(
+                "SELECT status || ':' || COALESCE(error_message, CAST(jobs_enqueued AS TEXT))
+                 FROM reanalysis_requests WHERE id = {id}"
+            ))
+        };
+        assert_eq!(
+            status(1)?.as_deref(),
+            Some("failed:Configuration error: Missing tweet_id for reanalysis request")
+        );
+        assert!(status(2)?.unwrap_or_default().starts_with("failed:Malformed request"));
+        assert_eq!(
+            status(3)?.as_deref(),
+            Some("failed:Configuration error: Reanalysis model 9 is not enabled")
+        );
+        assert!(status(4)?.unwrap_or_default().contains("Unknown reanalysis request type"));
+        assert_eq!(status(5)?.as_deref(), Some("completed:1"));
+
+        // Without an enabled model a request fails instead of queueing jobs no
+        // worker would claim.
+        database
+            .execute_sql("INSERT INTO reanalysis_requests (id, request_type) VALUES (6, 'all')")?;
+        process_reanalysis_requests(
+            &database,
+            &[],
+            TweetTypes::default(),
+            &Shutdown::new(),
+            &mut error_details,
+        )?;
+        assert_eq!(
+            status(6)?.as_deref(),
+            Some("failed:Configuration error: No enabled models for reanalysis")
+        );
+        let modelless_jobs = database.query_text(
+            "SELECT CAST(COUNT(*) AS TEXT) FROM analysis_queue WHERE llm_model_id IS NULL",
+        )?;
+        assert_eq!(modelless_jobs.as_deref(), Some("0"));
+        Ok(())
+    }
+
+    #[test]
+    fn user_overrides_pick_models_and_excluded_tweet_types() {
+        let model = |id, name: &str, is_enabled| LlmModel {
+            id,
+            name: name.to_string(),
+            huggingface_model_id: Some(format!("org/{}", name.to_lowercase())),
+            is_enabled,
+        };
+        let models = [model(1, "Llama", true), model(2, "Qwen", true), model(3, "Phi", false)];
+        let overrides = UserOverrides {
+            include_retweets: Some(false),
+            include_replies: Some(true),
+            models: Some(vec!["org/qwen".to_string(), "Phi".to_string()]),
+            ..UserOverrides::default()
+        };
+
+        assert_eq!(user_models(&models, "user5", None).ok(), Some(None));
+        assert_eq!(user_models(&models, "user5", Some(&overrides)).ok(), Some(Some(vec![2])));
+        let disabled = UserOverrides {
+            models: Some(vec!["Phi".to_string(), "Mistral".to_string()]),
+            ..UserOverrides::default()
+        };
+        let Err(CrawlerError::Config(message)) = user_models(&models, "user5", Some(&disabled))
+        else {
+            panic!("expected an override without enabled models to be refused");
+        };
+        assert!(message.contains("@user5"), "{message}");
+        let replies = TweetTypes {
+            replies: true,
+            ..TweetTypes::default()
+        };
+        assert_eq!(excluded_tweet_types(replies, None), replies);
+        assert_eq!(
+            excluded_tweet_types(replies, Some(&overrides)).names(),
+            ["retweets"]
+        );
+    }
+}
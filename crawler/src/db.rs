@@ -1,76 +1,161 @@
-//! SQLite access for the crawler
+//! SQLite storage backend, sharing its database file with the Node backend
+
+use std::time::Duration;
 
 use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection, OptionalExtension};
-use serde::Serialize;
-use serde_json::json;
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use tracing::{info, warn};
 
 use crate::error::CrawlerError;
-use crate::models::{ReanalysisRequest, TrackedUser, TwitterApiTweet};
-
-#[derive(Debug, Serialize)]
-pub struct ApiErrorDetail {
-    pub error_type: String,
-    pub message: String,
-    pub code: Option<String>,
-    pub endpoint: Option<String>,
-    pub timestamp: String,
-}
+use crate::migrations;
+use crate::retention::{PruneTarget, VacuumMode};
+use crate::schema_compat;
+use crate::storage::{
+    self, ApiErrorDetail, Storage, StoredConfiguration, TweetTypes, REANALYSIS_LEASE_MINUTES,
+};
+use crate::telemetry;
+use crate::models::{
+    AnalysisJob, AnalysisScoreRow, CrawlRunUser, CrawlerRunSummary, FetchProgress,
+    GlobalAggregation, LlmModel, ReanalysisChunk, ReanalysisRequest, RunProgress, TrackedUser,
+    TwitterApiTweet, UserAggregation, UserCrawlOutcome,
+};
 
-pub struct Database {
+pub struct SqliteDatabase {
     conn: Connection,
 }
 
-impl Database {
-    pub fn new(database_url: &str) -> Result<Self, CrawlerError> {
+impl SqliteDatabase {
+    /// Open the shared SQLite file
+    ///
+    /// The Express API reads the same file, so the connection uses WAL (readers
+    /// never block the crawler's writes and vice versa) and waits up to
+    /// `busy_timeout` for the write lock instead of failing with `SQLITE_BUSY`.
+    pub fn new(database_url: &str, busy_timeout: Duration) -> Result<Self, CrawlerError> {
         let conn = Connection::open(database_url)?;
+        conn.busy_timeout(busy_timeout)?;
         conn.pragma_update(None, "foreign_keys", "ON")?;
+        let journal_mode: String =
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+        if !journal_mode.eq_ignore_ascii_case("wal") && database_url != ":memory:" {
+            warn!("SQLite journal mode is {} instead of WAL", journal_mode);
+        }
+        // NORMAL is durable across application crashes in WAL mode and avoids an fsync per commit.
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
         Ok(Self { conn })
     }
 
-    pub fn init_schema(&self) -> Result<(), CrawlerError> {
-        let sql = r#"
-            CREATE TABLE IF NOT EXISTS analysis_queue (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                tweet_id INTEGER NOT NULL,
-                llm_model_id INTEGER,
-                status TEXT NOT NULL DEFAULT 'pending',
-                attempt_count INTEGER DEFAULT 0,
-                last_error TEXT,
-                enqueued_at TEXT DEFAULT (datetime('now')),
-                updated_at TEXT DEFAULT (datetime('now')),
-                UNIQUE(tweet_id, llm_model_id),
-                FOREIGN KEY (tweet_id) REFERENCES tweets(id) ON DELETE CASCADE,
-                FOREIGN KEY (llm_model_id) REFERENCES llm_models(id)
-            );
+    fn query_run_users(
+        &self,
+        filter: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<CrawlRunUser>, CrawlerError> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT cru.run_id, cru.twitter_user_id, tu.username, cru.status, cru.tweets_fetched,
+                    cru.pages_fetched, cru.api_calls, cru.duration_ms, cru.api_error_id,
+                    cru.error_message, cru.completed_at
+             FROM crawler_run_users cru
+             JOIN twitter_users tu ON tu.id = cru.twitter_user_id
+             {filter}"
+        ))?;
+        let rows = stmt.query_map(params, |row| {
+            Ok(CrawlRunUser {
+                run_id: row.get(0)?,
+                twitter_user_id: row.get(1)?,
+                username: row.get(2)?,
+                status: row.get(3)?,
+                tweets_fetched: row.get::<_, Option<i64>>(4)?.unwrap_or(0),
+                pages_fetched: row.get::<_, Option<i64>>(5)?.unwrap_or(0),
+                api_calls: row.get::<_, Option<i64>>(6)?.unwrap_or(0),
+                duration_ms: row.get::<_, Option<i64>>(7)?.unwrap_or(0),
+                api_error_id: row.get(8)?,
+                error_message: row.get(9)?,
+                completed_at: row.get(10)?,
+            })
+        })?;
 
-            CREATE TABLE IF NOT EXISTS crawler_checkpoints (
-                twitter_user_id INTEGER PRIMARY KEY,
-                last_tweet_timestamp TEXT NOT NULL,
-                updated_at TEXT DEFAULT (datetime('now')),
-                FOREIGN KEY (twitter_user_id) REFERENCES twitter_users(id) ON DELETE CASCADE
-            );
+        let mut history = Vec::new();
+        for row in rows {
+            history.push(row?);
+        }
+        Ok(history)
+    }
 
-            CREATE TABLE IF NOT EXISTS reanalysis_requests (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                request_type TEXT NOT NULL,
-                tweet_id INTEGER,
-                twitter_user_id INTEGER,
-                status TEXT NOT NULL DEFAULT 'pending',
-                requested_at TEXT DEFAULT (datetime('now')),
-                processed_at TEXT
-            );
+    fn enqueue_jobs(
+        &self,
+        tweet_id: i64,
+        enabled_model_ids: &[i64],
+    ) -> Result<u64, CrawlerError> {
+        let mut jobs_enqueued = 0_u64;
 
-            CREATE INDEX IF NOT EXISTS idx_analysis_queue_status ON analysis_queue(status);
-            CREATE INDEX IF NOT EXISTS idx_analysis_queue_tweet ON analysis_queue(tweet_id);
-            CREATE INDEX IF NOT EXISTS idx_reanalysis_status ON reanalysis_requests(status);
-        "#;
+        if enabled_model_ids.is_empty() {
+            jobs_enqueued += self.enqueue_job(tweet_id, None, false)?;
+        } else {
+            for model_id in enabled_model_ids {
+                jobs_enqueued += self.enqueue_job(tweet_id, Some(*model_id), false)?;
+            }
+        }
 
-        self.conn.execute_batch(sql)?;
+        Ok(jobs_enqueued)
+    }
+
+    /// With `reanalyze`, a completed or failed job for the same tweet and model
+    /// goes back to `pending` instead of being skipped
+    fn enqueue_job(
+        &self,
+        tweet_id: i64,
+        llm_model_id: Option<i64>,
+        reanalyze: bool,
+    ) -> Result<u64, CrawlerError> {
+        let sql = if reanalyze {
+            "INSERT INTO analysis_queue (tweet_id, llm_model_id)
+             VALUES (?, ?)
+             ON CONFLICT (tweet_id, llm_model_id) DO UPDATE
+             SET status = 'pending',
+                 attempt_count = 0,
+                 last_error = NULL,
+                 updated_at = datetime('now')
+             WHERE status IN ('completed', 'failed')"
+        } else {
+            "INSERT OR IGNORE INTO analysis_queue (tweet_id, llm_model_id)
+             VALUES (?, ?)"
+        };
+        let changes = self.conn.execute(sql, params![tweet_id, llm_model_id])?;
+        if changes > 0 {
+            telemetry::record_job_enqueued(llm_model_id);
+        }
+        Ok(changes as u64)
+    }
+}
+
+impl Storage for SqliteDatabase {
+    fn init_schema(&self) -> Result<(), CrawlerError> {
+        migrations::migrate(&self.conn)?;
+        schema_compat::check(&self.conn)
+    }
+
+    fn check_schema(&self) -> Result<(), CrawlerError> {
+        schema_compat::check(&self.conn)
+    }
+
+    fn schema_version(&self) -> Result<Option<i64>, CrawlerError> {
+        let version = self
+            .conn
+            .query_row("SELECT MAX(version) FROM schema_migrations", [], |row| {
+                row.get(0)
+            })?;
+        Ok(version)
+    }
+
+    fn latest_schema_version(&self) -> i64 {
+        migrations::latest_version()
+    }
+
+    fn ping(&self) -> Result<(), CrawlerError> {
+        self.conn.query_row("SELECT 1", [], |_| Ok(()))?;
         Ok(())
     }
 
-    pub fn create_crawler_run(&self) -> Result<i64, CrawlerError> {
+    fn create_crawler_run(&self) -> Result<i64, CrawlerError> {
         self.conn.execute(
             "INSERT INTO crawler_runs (status, tweets_fetched, tweets_analyzed, errors_count)
              VALUES ('running', 0, 0, 0)",
@@ -79,7 +164,7 @@ impl Database {
         Ok(self.conn.last_insert_rowid())
     }
 
-    pub fn complete_crawler_run(
+    fn complete_crawler_run(
         &self,
         run_id: i64,
         status: &str,
@@ -88,13 +173,7 @@ impl Database {
         error_details: &[ApiErrorDetail],
     ) -> Result<(), CrawlerError> {
         let error_count = error_details.len() as u64;
-        let error_json = if error_details.is_empty() {
-            None
-        } else {
-            Some(serde_json::to_string(error_details).map_err(|err| {
-                CrawlerError::Database(rusqlite::Error::ToSqlConversionFailure(Box::new(err)))
-            })?)
-        };
+        let error_json = storage::error_details_json(error_details)?;
 
         self.conn.execute(
             "UPDATE crawler_runs
@@ -102,13 +181,18 @@ impl Database {
                  completed_at = datetime('now'),
                  tweets_fetched = ?,
                  tweets_analyzed = ?,
+                 jobs_queued = ?,
                  errors_count = ?,
-                 error_details = ?
+                 error_details = ?,
+                 current_user = NULL,
+                 eta_at = NULL,
+                 progress_updated_at = datetime('now')
              WHERE id = ?",
             params![
                 status,
                 tweets_fetched as i64,
                 tweets_analyzed as i64,
+                tweets_analyzed as i64,
                 error_count as i64,
                 error_json,
                 run_id
@@ -118,22 +202,155 @@ impl Database {
         Ok(())
     }
 
-    pub fn insert_api_error(
+    fn update_crawler_run_progress(
+        &self,
+        run_id: i64,
+        progress: &RunProgress,
+    ) -> Result<(), CrawlerError> {
+        self.conn.execute(
+            "UPDATE crawler_runs
+             SET users_total = ?,
+                 users_completed = ?,
+                 current_user = ?,
+                 tweets_fetched = ?,
+                 tweets_analyzed = ?,
+                 jobs_queued = ?,
+                 errors_count = ?,
+                 eta_at = ?,
+                 progress_updated_at = datetime('now')
+             WHERE id = ?",
+            params![
+                progress.users_total,
+                progress.users_completed,
+                progress.current_user,
+                progress.tweets_fetched,
+                progress.jobs_queued,
+                progress.jobs_queued,
+                progress.errors_count,
+                progress.eta.map(|value| value.to_rfc3339()),
+                run_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn fail_abandoned_runs(&self, stale_minutes: u64) -> Result<u64, CrawlerError> {
+        let changes = self.conn.execute(
+            "UPDATE crawler_runs
+             SET status = 'failed',
+                 completed_at = datetime('now'),
+                 current_user = NULL,
+                 eta_at = NULL
+             WHERE status = 'running'
+               AND COALESCE(progress_updated_at, started_at) < datetime('now', ?)",
+            params![format!("-{stale_minutes} minutes")],
+        )?;
+        Ok(changes as u64)
+    }
+
+    fn set_next_scheduled_run(
+        &self,
+        schedule: &str,
+        next_run: Option<DateTime<Utc>>,
+    ) -> Result<(), CrawlerError> {
+        self.conn.execute(
+            "INSERT INTO crawler_schedule (id, schedule, next_scheduled_run, updated_at)
+             VALUES (1, ?, ?, datetime('now'))
+             ON CONFLICT(id) DO UPDATE SET
+                 schedule = excluded.schedule,
+                 next_scheduled_run = excluded.next_scheduled_run,
+                 updated_at = datetime('now')",
+            params![schedule, next_run.map(|value| value.to_rfc3339())],
+        )?;
+        Ok(())
+    }
+
+    fn insert_api_error(
         &self,
         error_type: &str,
         error_message: &str,
         error_code: Option<&str>,
         endpoint: Option<&str>,
-    ) -> Result<(), CrawlerError> {
+    ) -> Result<i64, CrawlerError> {
         self.conn.execute(
             "INSERT INTO api_errors (error_type, error_message, error_code, endpoint, resolved)
              VALUES (?, ?, ?, ?, 0)",
             params![error_type, error_message, error_code, endpoint],
         )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    fn record_run_user(
+        &self,
+        run_id: i64,
+        twitter_user_id: i64,
+        outcome: &UserCrawlOutcome,
+    ) -> Result<(), CrawlerError> {
+        self.conn.execute(
+            "INSERT INTO crawler_run_users
+             (run_id, twitter_user_id, status, tweets_fetched, pages_fetched, api_calls,
+              duration_ms, api_error_id, error_message)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(run_id, twitter_user_id) DO UPDATE SET
+               status = excluded.status,
+               tweets_fetched = excluded.tweets_fetched,
+               pages_fetched = excluded.pages_fetched,
+               api_calls = excluded.api_calls,
+               duration_ms = excluded.duration_ms,
+               api_error_id = excluded.api_error_id,
+               error_message = excluded.error_message,
+               completed_at = datetime('now')",
+            params![
+                run_id,
+                twitter_user_id,
+                outcome.status,
+                outcome.tweets_fetched as i64,
+                outcome.pages_fetched as i64,
+                outcome.api_calls as i64,
+                outcome.duration_ms,
+                outcome.api_error_id,
+                outcome.error_message
+            ],
+        )?;
         Ok(())
     }
 
-    pub fn load_active_users(&self) -> Result<Vec<TrackedUser>, CrawlerError> {
+    fn user_crawl_history(
+        &self,
+        twitter_user_id: i64,
+        limit: i64,
+    ) -> Result<Vec<CrawlRunUser>, CrawlerError> {
+        self.query_run_users(
+            "WHERE cru.twitter_user_id = ? ORDER BY cru.run_id DESC LIMIT ?",
+            params![twitter_user_id, limit],
+        )
+    }
+
+    fn run_user_outcomes(&self, run_id: i64) -> Result<Vec<CrawlRunUser>, CrawlerError> {
+        self.query_run_users("WHERE cru.run_id = ? ORDER BY cru.id", params![run_id])
+    }
+
+    fn last_crawl_started(
+        &self,
+        twitter_user_id: i64,
+    ) -> Result<Option<DateTime<Utc>>, CrawlerError> {
+        let started: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT strftime('%Y-%m-%dT%H:%M:%SZ',
+                                 julianday(completed_at) - COALESCE(duration_ms, 0) / 86400000.0)
+                 FROM crawler_run_users
+                 WHERE twitter_user_id = ? AND status = 'completed'
+                 ORDER BY id DESC LIMIT 1",
+                params![twitter_user_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        Ok(started.as_deref().and_then(parse_timestamp))
+    }
+
+    fn load_active_users(&self) -> Result<Vec<TrackedUser>, CrawlerError> {
         let mut stmt = self.conn.prepare(
             "SELECT id, twitter_id, username, display_name
              FROM twitter_users
@@ -157,7 +374,19 @@ impl Database {
         Ok(users)
     }
 
-    pub fn update_user_from_api(
+    fn find_user_id(&self, username: &str) -> Result<Option<i64>, CrawlerError> {
+        let id = self
+            .conn
+            .query_row(
+                "SELECT id FROM twitter_users WHERE username = ? COLLATE NOCASE",
+                params![username],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(id)
+    }
+
+    fn update_user_from_api(
         &self,
         username: &str,
         twitter_id: &str,
@@ -187,7 +416,7 @@ impl Database {
         Ok(())
     }
 
-    pub fn get_enabled_model_ids(&self) -> Result<Vec<i64>, CrawlerError> {
+    fn get_enabled_model_ids(&self) -> Result<Vec<i64>, CrawlerError> {
         let mut stmt = self
             .conn
             .prepare("SELECT id FROM llm_models WHERE is_enabled = 1")?;
@@ -200,7 +429,27 @@ impl Database {
         Ok(ids)
     }
 
-    pub fn get_checkpoint(&self, twitter_user_id: i64) -> Result<Option<DateTime<Utc>>, CrawlerError> {
+    fn load_models(&self) -> Result<Vec<LlmModel>, CrawlerError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, huggingface_model_id, is_enabled FROM llm_models ORDER BY id",
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(LlmModel {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                huggingface_model_id: row.get(2)?,
+                is_enabled: row.get::<_, Option<bool>>(3)?.unwrap_or(false),
+            })
+        })?;
+        let mut models = Vec::new();
+        for row in rows {
+            models.push(row?);
+        }
+        Ok(models)
+    }
+
+    fn get_checkpoint(&self, twitter_user_id: i64) -> Result<Option<DateTime<Utc>>, CrawlerError> {
         let timestamp: Option<String> = self
             .conn
             .query_row(
@@ -214,14 +463,10 @@ impl Database {
             return Ok(None);
         };
 
-        let parsed = DateTime::parse_from_rfc3339(&timestamp)
-            .map(|dt| dt.with_timezone(&Utc))
-            .ok();
-
-        Ok(parsed)
+        Ok(parse_timestamp(&timestamp))
     }
 
-    pub fn set_checkpoint(
+    fn set_checkpoint(
         &self,
         twitter_user_id: i64,
         timestamp: DateTime<Utc>,
@@ -237,56 +482,111 @@ impl Database {
         Ok(())
     }
 
-    pub fn insert_tweets_and_enqueue(
+    fn get_fetch_progress(
+        &self,
+        twitter_user_id: i64,
+    ) -> Result<Option<FetchProgress>, CrawlerError> {
+        let row: Option<(String, Option<String>, String, Option<String>)> = self
+            .conn
+            .query_row(
+                "SELECT start_time, end_time, pagination_token, newest_timestamp
+                 FROM crawler_fetch_progress
+                 WHERE twitter_user_id = ?",
+                params![twitter_user_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .optional()?;
+
+        let Some((start_time, end_time, pagination_token, newest_timestamp)) = row else {
+            return Ok(None);
+        };
+        let Some(start_time) = parse_timestamp(&start_time) else {
+            return Ok(None);
+        };
+
+        Ok(Some(FetchProgress {
+            start_time,
+            end_time: end_time.as_deref().and_then(parse_timestamp),
+            pagination_token,
+            newest_timestamp: newest_timestamp.as_deref().and_then(parse_timestamp),
+        }))
+    }
+
+    fn save_fetch_progress(
+        &self,
+        twitter_user_id: i64,
+        progress: &FetchProgress,
+    ) -> Result<(), CrawlerError> {
+        self.conn.execute(
+            "INSERT INTO crawler_fetch_progress
+             (twitter_user_id, start_time, end_time, pagination_token, newest_timestamp, updated_at)
+             VALUES (?, ?, ?, ?, ?, datetime('now'))
+             ON CONFLICT(twitter_user_id) DO UPDATE SET
+                 start_time = excluded.start_time,
+                 end_time = excluded.end_time,
+                 pagination_token = excluded.pagination_token,
+                 newest_timestamp = excluded.newest_timestamp,
+                 updated_at = datetime('now')",
+            params![
+                twitter_user_id,
+                progress.start_time.to_rfc3339(),
+                progress.end_time.map(|value| value.to_rfc3339()),
+                progress.pagination_token,
+                progress.newest_timestamp.map(|value| value.to_rfc3339())
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn clear_fetch_progress(&self, twitter_user_id: i64) -> Result<(), CrawlerError> {
+        self.conn.execute(
+            "DELETE FROM crawler_fetch_progress WHERE twitter_user_id = ?",
+            params![twitter_user_id],
+        )?;
+        Ok(())
+    }
+
+    fn insert_tweets_and_enqueue(
         &self,
         twitter_user_id: i64,
         tweets: &[TwitterApiTweet],
         enabled_model_ids: &[i64],
+        skip_analysis: TweetTypes,
     ) -> Result<(u64, u64, Option<DateTime<Utc>>), CrawlerError> {
         let mut tweets_inserted = 0_u64;
         let mut jobs_enqueued = 0_u64;
         let mut latest_timestamp: Option<DateTime<Utc>> = None;
 
-        let mut stmt = self.conn.prepare(
+        // One write transaction per page instead of an autocommit per tweet and job.
+        // IMMEDIATE takes the write lock up front, so contention waits on busy_timeout
+        // rather than failing when a deferred read transaction tries to upgrade.
+        let tx = Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate)?;
+        let mut stmt = tx.prepare(
             "INSERT OR IGNORE INTO tweets
              (twitter_user_id, tweet_id, content, tweet_timestamp, engagement_metrics, is_retweet, is_reply)
              VALUES (?, ?, ?, ?, ?, ?, ?)",
         )?;
 
         for tweet in tweets {
-            let metrics = tweet.public_metrics.as_ref();
-            let engagement = json!({
-                "likes": metrics.and_then(|m| m.like_count).unwrap_or(0),
-                "retweets": metrics.and_then(|m| m.retweet_count).unwrap_or(0),
-                "replies": metrics.and_then(|m| m.reply_count).unwrap_or(0),
-                "quotes": metrics.and_then(|m| m.quote_count).unwrap_or(0),
-            });
-
-            let is_retweet = tweet
-                .referenced_tweets
-                .as_ref()
-                .map(|refs| refs.iter().any(|r| r.reference_type == "retweeted"))
-                .unwrap_or(false);
-            let is_reply = tweet
-                .referenced_tweets
-                .as_ref()
-                .map(|refs| refs.iter().any(|r| r.reference_type == "replied_to"))
-                .unwrap_or(false);
+            let is_retweet = storage::references(tweet, "retweeted");
+            let is_reply = storage::references(tweet, "replied_to");
 
             let changes = stmt.execute(params![
                 twitter_user_id,
                 tweet.id,
                 tweet.text,
                 tweet.created_at.to_rfc3339(),
-                engagement.to_string(),
+                storage::engagement_json(tweet),
                 if is_retweet { 1 } else { 0 },
                 if is_reply { 1 } else { 0 }
             ])?;
 
             if changes > 0 {
                 tweets_inserted += 1;
-                let tweet_db_id = self.conn.last_insert_rowid();
-                jobs_enqueued += self.enqueue_jobs(tweet_db_id, enabled_model_ids)?;
+                if !skip_analysis.contains(tweet) {
+                    let tweet_db_id = tx.last_insert_rowid();
+                    jobs_enqueued += self.enqueue_jobs(tweet_db_id, enabled_model_ids)?;
+                }
             }
 
             latest_timestamp = match latest_timestamp {
@@ -294,138 +594,765 @@ impl Database {
                 _ => Some(tweet.created_at),
             };
         }
+        drop(stmt);
+        tx.commit()?;
 
+        telemetry::record_tweets_inserted(tweets_inserted);
         Ok((tweets_inserted, jobs_enqueued, latest_timestamp))
     }
 
-    pub fn enqueue_reanalysis_for_tweet(
+    fn count_reanalysis_tweets(
         &self,
-        tweet_id: i64,
-        enabled_model_ids: &[i64],
-    ) -> Result<u64, CrawlerError> {
-        self.enqueue_jobs(tweet_id, enabled_model_ids)
+        request: &ReanalysisRequest,
+        skip_analysis: TweetTypes,
+    ) -> Result<i64, CrawlerError> {
+        let (tweet_id, user_id, tweets_from, tweets_to) = reanalysis_scope(request);
+        let skipped = storage::skipped_tweet_types(request, skip_analysis);
+        let count = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM tweets t WHERE {REANALYSIS_SCOPE}{skipped}"),
+            params![tweet_id, user_id, tweets_from, tweets_to],
+            |row| row.get(0),
+        )?;
+        Ok(count)
     }
 
-    pub fn enqueue_reanalysis_for_user(
+    fn estimate_reanalysis_jobs(
         &self,
-        twitter_user_id: i64,
-        enabled_model_ids: &[i64],
-    ) -> Result<u64, CrawlerError> {
-        let mut stmt = self
-            .conn
-            .prepare("SELECT id FROM tweets WHERE twitter_user_id = ?")?;
-        let rows = stmt.query_map(params![twitter_user_id], |row| row.get(0))?;
-        let mut jobs = 0_u64;
-        for row in rows {
-            let tweet_id: i64 = row?;
-            jobs += self.enqueue_jobs(tweet_id, enabled_model_ids)?;
-        }
-        Ok(jobs)
+        request: &ReanalysisRequest,
+        model_ids: &[i64],
+        skip_analysis: TweetTypes,
+    ) -> Result<i64, CrawlerError> {
+        let (tweet_id, user_id, tweets_from, tweets_to) = reanalysis_scope(request);
+        let skipped = storage::skipped_tweet_types(request, skip_analysis);
+        // Jobs already waiting are left as they are, so they are not counted.
+        let count = self.conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM tweets t, json_each(?5) m
+                 WHERE {REANALYSIS_SCOPE}{skipped} AND {REANALYSIS_PAIR_FILTER}
+                   AND NOT EXISTS (
+                       SELECT 1 FROM analysis_queue q
+                       WHERE q.tweet_id = t.id AND q.llm_model_id IS m.value
+                         AND q.status IN ('pending', 'processing')
+                   )"
+            ),
+            params![
+                tweet_id,
+                user_id,
+                tweets_from,
+                tweets_to,
+                model_list(model_ids),
+                request.only_failed,
+                request.missing_emotion,
+            ],
+            |row| row.get(0),
+        )?;
+        Ok(count)
     }
 
-    pub fn enqueue_reanalysis_for_all(
+    fn enqueue_reanalysis_chunk(
         &self,
-        enabled_model_ids: &[i64],
-    ) -> Result<u64, CrawlerError> {
-        let mut stmt = self.conn.prepare("SELECT id FROM tweets")?;
-        let rows = stmt.query_map([], |row| row.get(0))?;
-        let mut jobs = 0_u64;
-        for row in rows {
-            let tweet_id: i64 = row?;
-            jobs += self.enqueue_jobs(tweet_id, enabled_model_ids)?;
+        request: &ReanalysisRequest,
+        after_tweet_id: i64,
+        chunk_size: i64,
+        model_ids: &[i64],
+        skip_analysis: TweetTypes,
+    ) -> Result<ReanalysisChunk, CrawlerError> {
+        let (tweet_id, user_id, tweets_from, tweets_to) = reanalysis_scope(request);
+        let skipped = storage::skipped_tweet_types(request, skip_analysis);
+        let tx = Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate)?;
+        let tweet_ids = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT t.id FROM tweets t
+                 WHERE {REANALYSIS_SCOPE}{skipped} AND t.id > ?5
+                 ORDER BY t.id
+                 LIMIT ?6"
+            ))?;
+            let rows = stmt.query_map(
+                params![tweet_id, user_id, tweets_from, tweets_to, after_tweet_id, chunk_size],
+                |row| row.get::<_, i64>(0),
+            )?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+
+        let last_tweet_id = tweet_ids.last().copied();
+        let mut jobs_enqueued = 0_u64;
+        if let Some(last) = last_tweet_id {
+            let pairs = {
+                let mut stmt = tx.prepare(&format!(
+                    "SELECT t.id, m.value FROM tweets t, json_each(?5) m
+                     WHERE {REANALYSIS_SCOPE}{skipped} AND {REANALYSIS_PAIR_FILTER}
+                       AND t.id > ?8 AND t.id <= ?9
+                     ORDER BY t.id"
+                ))?;
+                let rows = stmt.query_map(
+                    params![
+                        tweet_id,
+                        user_id,
+                        tweets_from,
+                        tweets_to,
+                        model_list(model_ids),
+                        request.only_failed,
+                        request.missing_emotion,
+                        after_tweet_id,
+                        last,
+                    ],
+                    |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Option<i64>>(1)?)),
+                )?;
+                rows.collect::<Result<Vec<_>, _>>()?
+            };
+            for (pair_tweet_id, llm_model_id) in pairs {
+                jobs_enqueued += self.enqueue_job(pair_tweet_id, llm_model_id, true)?;
+            }
         }
-        Ok(jobs)
+
+        tx.execute(
+            "UPDATE reanalysis_requests
+             SET cursor_tweet_id = COALESCE(?1, cursor_tweet_id),
+                 tweets_processed = COALESCE(tweets_processed, 0) + ?2,
+                 jobs_enqueued = COALESCE(jobs_enqueued, 0) + ?4,
+                 progress_percent = MIN(
+                     100.0,
+                     (COALESCE(tweets_processed, 0) + ?2) * 100.0 / MAX(COALESCE(tweets_total, 0), 1)
+                 ),
+                 lease_expires_at = datetime('now', ?5)
+             WHERE id = ?3",
+            params![
+                last_tweet_id,
+                tweet_ids.len() as i64,
+                request.id,
+                jobs_enqueued as i64,
+                lease_modifier()
+            ],
+        )?;
+        tx.commit()?;
+
+        Ok(ReanalysisChunk {
+            tweets: tweet_ids.len() as u64,
+            jobs_enqueued,
+            last_tweet_id,
+        })
     }
 
-    pub fn load_pending_reanalysis_requests(
+    fn claim_reanalysis_requests(
         &self,
         limit: i64,
     ) -> Result<Vec<ReanalysisRequest>, CrawlerError> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, request_type, tweet_id, twitter_user_id
-             FROM reanalysis_requests
-             WHERE status = 'pending'
-             ORDER BY requested_at ASC
-             LIMIT ?",
-        )?;
+        // A batch of nothing but malformed rows is skipped, not mistaken for
+        // an empty queue.
+        loop {
+            // A single `UPDATE ... RETURNING`, so two processes never claim the same request.
+            let mut stmt = self.conn.prepare(
+                "UPDATE reanalysis_requests
+                 SET lease_expires_at = datetime('now', ?2)
+                 WHERE id IN (
+                     SELECT id
+                     FROM reanalysis_requests
+                     WHERE status IN ('pending', 'processing')
+                       AND (lease_expires_at IS NULL OR lease_expires_at < datetime('now'))
+                     ORDER BY requested_at ASC, id ASC
+                     LIMIT ?1
+                 )
+                 RETURNING id, request_type, tweet_id, twitter_user_id, cursor_tweet_id,
+                           tweets_total, llm_model_id, tweets_from, tweets_to, only_failed,
+                           missing_emotion, dry_run, requested_at",
+            )?;
 
-        let rows = stmt.query_map(params![limit], |row| {
-            Ok(ReanalysisRequest {
-                id: row.get(0)?,
-                request_type: row.get(1)?,
-                tweet_id: row.get(2)?,
-                twitter_user_id: row.get(3)?,
-            })
-        })?;
+            let rows = stmt.query_map(params![limit, lease_modifier()], |row| {
+                Ok((
+                    row.get::<_, Option<String>>(12)?,
+                    row.get::<_, i64>(0)?,
+                    reanalysis_request(row),
+                ))
+            })?;
+            let mut claimed = rows.collect::<Result<Vec<_>, _>>()?;
+            let claimed_any = !claimed.is_empty();
+            drop(stmt);
+            claimed.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
 
-        let mut requests = Vec::new();
-        for row in rows {
-            requests.push(row?);
+            let mut requests = Vec::new();
+            for (_, request_id, request) in claimed {
+                match request {
+                    Ok(request) => requests.push(request),
+                    Err(error) => {
+                        warn!("Reanalysis request {} is malformed: {}", request_id, error);
+                        let message = format!("Malformed request: {error}");
+                        self.mark_reanalysis_failed(request_id, &message)?;
+                    }
+                }
+            }
+
+            if !requests.is_empty() || !claimed_any {
+                return Ok(requests);
+            }
         }
+    }
+
+    fn release_reanalysis_request(&self, request_id: i64) -> Result<(), CrawlerError> {
+        self.conn.execute(
+            "UPDATE reanalysis_requests SET lease_expires_at = NULL WHERE id = ?",
+            params![request_id],
+        )?;
+        Ok(())
+    }
 
-        Ok(requests)
+    fn mark_reanalysis_processing(
+        &self,
+        request_id: i64,
+        tweets_total: Option<i64>,
+    ) -> Result<(), CrawlerError> {
+        self.conn.execute(
+            "UPDATE reanalysis_requests
+             SET status = 'processing',
+                 tweets_total = COALESCE(tweets_total, ?2)
+             WHERE id = ?1",
+            params![request_id, tweets_total],
+        )?;
+        Ok(())
     }
 
-    pub fn mark_reanalysis_processing(&self, request_id: i64) -> Result<(), CrawlerError> {
+    fn mark_reanalysis_completed(&self, request_id: i64) -> Result<(), CrawlerError> {
         self.conn.execute(
             "UPDATE reanalysis_requests
-             SET status = 'processing'
+             SET status = 'completed',
+                 progress_percent = 100,
+                 processed_at = datetime('now'),
+                 lease_expires_at = NULL
              WHERE id = ?",
             params![request_id],
         )?;
         Ok(())
     }
 
-    pub fn mark_reanalysis_completed(&self, request_id: i64) -> Result<(), CrawlerError> {
+    fn mark_reanalysis_failed(
+        &self,
+        request_id: i64,
+        error_message: &str,
+    ) -> Result<(), CrawlerError> {
         self.conn.execute(
             "UPDATE reanalysis_requests
+             SET status = 'failed',
+                 error_message = ?2,
+                 processed_at = datetime('now'),
+                 lease_expires_at = NULL
+             WHERE id = ?1",
+            params![request_id, error_message],
+        )?;
+        Ok(())
+    }
+
+    fn record_reanalysis_estimate(
+        &self,
+        request_id: i64,
+        jobs_estimate: i64,
+    ) -> Result<(), CrawlerError> {
+        self.conn.execute(
+            "UPDATE reanalysis_requests
+             SET status = 'estimated',
+                 jobs_estimate = ?2,
+                 processed_at = datetime('now'),
+                 lease_expires_at = NULL
+             WHERE id = ?1",
+            params![request_id, jobs_estimate],
+        )?;
+        Ok(())
+    }
+
+    fn latest_crawler_run(&self) -> Result<Option<CrawlerRunSummary>, CrawlerError> {
+        let summary = self
+            .conn
+            .query_row(
+                "SELECT id, status, started_at, completed_at, tweets_fetched, tweets_analyzed, errors_count
+                 FROM crawler_runs
+                 ORDER BY id DESC
+                 LIMIT 1",
+                [],
+                |row| {
+                    Ok(CrawlerRunSummary {
+                        id: row.get(0)?,
+                        status: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                        started_at: row.get(2)?,
+                        completed_at: row.get(3)?,
+                        tweets_fetched: row.get::<_, Option<i64>>(4)?.unwrap_or(0),
+                        tweets_analyzed: row.get::<_, Option<i64>>(5)?.unwrap_or(0),
+                        errors_count: row.get::<_, Option<i64>>(6)?.unwrap_or(0),
+                    })
+                },
+            )
+            .optional()?;
+        Ok(summary)
+    }
+
+    fn load_stored_configuration(
+        &self,
+        key: &str,
+    ) -> Result<Option<StoredConfiguration>, CrawlerError> {
+        let row: Option<(String, Option<String>)> = self
+            .conn
+            .query_row(
+                "SELECT value, updated_by FROM configurations WHERE key = ?",
+                params![key],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        let Some((raw, updated_by)) = row else {
+            return Ok(None);
+        };
+
+        let value = serde_json::from_str(&raw)
+            .map_err(|err| CrawlerError::Config(format!("Invalid {key} configuration: {err}")))?;
+        Ok(Some(StoredConfiguration { value, updated_by }))
+    }
+
+    fn claim_analysis_jobs(
+        &self,
+        limit: i64,
+        llm_model_id: Option<i64>,
+    ) -> Result<Vec<AnalysisJob>, CrawlerError> {
+        // A single `UPDATE ... RETURNING`, so concurrent workers never claim the same job.
+        let mut claim = self.conn.prepare(
+            "UPDATE analysis_queue
+             SET status = 'processing',
+                 attempt_count = COALESCE(attempt_count, 0) + 1,
+                 updated_at = datetime('now')
+             WHERE id IN (
+                 SELECT q.id
+                 FROM analysis_queue q
+                 JOIN llm_models m ON m.id = q.llm_model_id
+                 WHERE q.status = 'pending'
+                   AND m.is_enabled = 1
+                   AND (?1 IS NULL OR q.llm_model_id = ?1)
+                 ORDER BY q.id ASC
+                 LIMIT ?2
+             )
+             RETURNING id",
+        )?;
+        let claimed = claim.query_map(params![llm_model_id, limit], |row| row.get::<_, i64>(0))?;
+        let mut ids = Vec::new();
+        for row in claimed {
+            ids.push(row?);
+        }
+        ids.sort_unstable();
+
+        let mut stmt = self.conn.prepare(
+            "SELECT q.id, q.tweet_id, q.llm_model_id,
+                    COALESCE(m.huggingface_model_id, m.name), t.content, q.attempt_count
+             FROM analysis_queue q
+             JOIN tweets t ON t.id = q.tweet_id
+             JOIN llm_models m ON m.id = q.llm_model_id
+             WHERE q.id = ?",
+        )?;
+
+        let mut jobs = Vec::new();
+        for id in ids {
+            let job = stmt.query_row(params![id], |row| {
+                Ok(AnalysisJob {
+                    id: row.get(0)?,
+                    tweet_id: row.get(1)?,
+                    llm_model_id: row.get(2)?,
+                    model_name: row.get(3)?,
+                    content: row.get(4)?,
+                    attempt_count: row.get::<_, Option<i64>>(5)?.unwrap_or(0),
+                })
+            })?;
+            jobs.push(job);
+        }
+
+        Ok(jobs)
+    }
+
+    fn complete_analysis_job(
+        &self,
+        job: &AnalysisJob,
+        emotion_scores: &str,
+        raw_llm_response: &str,
+        analysis_duration_ms: i64,
+    ) -> Result<(), CrawlerError> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "UPDATE sentiment_analyses
+             SET is_current = 0
+             WHERE tweet_id = ? AND llm_model_id = ? AND is_current = 1",
+            params![job.tweet_id, job.llm_model_id],
+        )?;
+        tx.execute(
+            "INSERT INTO sentiment_analyses
+             (tweet_id, llm_model_id, emotion_scores, raw_llm_response, analysis_duration_ms,
+              model_version, analysis_version, is_current)
+             VALUES (?1, ?2, ?3, ?4, ?5,
+                     (SELECT version FROM llm_models WHERE id = ?2),
+                     COALESCE(
+                         (SELECT MAX(analysis_version) FROM sentiment_analyses
+                          WHERE tweet_id = ?1 AND llm_model_id = ?2),
+                         0
+                     ) + 1,
+                     1)",
+            params![
+                job.tweet_id,
+                job.llm_model_id,
+                emotion_scores,
+                raw_llm_response,
+                analysis_duration_ms
+            ],
+        )?;
+        tx.execute(
+            "UPDATE analysis_queue
              SET status = 'completed',
-                 processed_at = datetime('now')
+                 last_error = NULL,
+                 updated_at = datetime('now')
              WHERE id = ?",
-            params![request_id],
+            params![job.id],
         )?;
+        tx.commit()?;
         Ok(())
     }
 
-    fn enqueue_jobs(
+    fn fail_analysis_job(
         &self,
-        tweet_id: i64,
-        enabled_model_ids: &[i64],
-    ) -> Result<u64, CrawlerError> {
-        let mut jobs_enqueued = 0_u64;
+        job_id: i64,
+        error_message: &str,
+        retry: bool,
+    ) -> Result<(), CrawlerError> {
+        self.conn.execute(
+            "UPDATE analysis_queue
+             SET status = ?,
+                 last_error = ?,
+                 updated_at = datetime('now')
+             WHERE id = ?",
+            params![if retry { "pending" } else { "failed" }, error_message, job_id],
+        )?;
+        Ok(())
+    }
 
-        if enabled_model_ids.is_empty() {
-            jobs_enqueued += self.enqueue_job(tweet_id, None)?;
-        } else {
-            for model_id in enabled_model_ids {
-                jobs_enqueued += self.enqueue_job(tweet_id, Some(*model_id))?;
+    fn analysis_queue_depth(&self) -> Result<Vec<(String, i64)>, CrawlerError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT status, COUNT(*) FROM analysis_queue GROUP BY status")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        let mut depth = Vec::new();
+        for row in rows {
+            depth.push(row?);
+        }
+        Ok(depth)
+    }
+
+    fn latest_analysis_id(&self) -> Result<Option<i64>, CrawlerError> {
+        let id = self
+            .conn
+            .query_row("SELECT MAX(id) FROM sentiment_analyses", [], |row| row.get(0))?;
+        Ok(id)
+    }
+
+    fn release_analysis_job(&self, job_id: i64) -> Result<(), CrawlerError> {
+        self.conn.execute(
+            "UPDATE analysis_queue
+             SET status = 'pending',
+                 attempt_count = MAX(COALESCE(attempt_count, 1) - 1, 0),
+                 updated_at = datetime('now')
+             WHERE id = ?",
+            params![job_id],
+        )?;
+        Ok(())
+    }
+
+    fn load_analysis_scores(&self) -> Result<Vec<AnalysisScoreRow>, CrawlerError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT t.twitter_user_id, sa.tweet_id, sa.llm_model_id, t.tweet_timestamp, sa.emotion_scores
+             FROM sentiment_analyses sa
+             JOIN tweets t ON t.id = sa.tweet_id
+             WHERE sa.is_current = 1",
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(AnalysisScoreRow {
+                twitter_user_id: row.get(0)?,
+                tweet_id: row.get(1)?,
+                llm_model_id: row.get(2)?,
+                tweet_timestamp: row.get(3)?,
+                emotion_scores: row.get(4)?,
+            })
+        })?;
+
+        let mut scores = Vec::new();
+        for row in rows {
+            scores.push(row?);
+        }
+        Ok(scores)
+    }
+
+    fn replace_aggregations(
+        &self,
+        user_rows: &[UserAggregation],
+        global_rows: &[GlobalAggregation],
+    ) -> Result<(), CrawlerError> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM user_aggregations", [])?;
+        tx.execute("DELETE FROM global_aggregations", [])?;
+
+        {
+            let mut user_stmt = tx.prepare(
+                "INSERT INTO user_aggregations
+                 (twitter_user_id, llm_model_id, time_bucket, bucket_start_date,
+                  emotion_averages, emotion_medians, emotion_modes, tweet_count)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            )?;
+            for row in user_rows {
+                user_stmt.execute(params![
+                    row.twitter_user_id,
+                    row.llm_model_id,
+                    row.time_bucket,
+                    row.bucket_start_date,
+                    row.emotion_averages,
+                    row.emotion_medians,
+                    row.emotion_modes,
+                    row.tweet_count
+                ])?;
+            }
+
+            let mut global_stmt = tx.prepare(
+                "INSERT INTO global_aggregations
+                 (llm_model_id, time_bucket, bucket_start_date, emotion_averages, gauge_values)
+                 VALUES (?, ?, ?, ?, ?)",
+            )?;
+            for row in global_rows {
+                global_stmt.execute(params![
+                    row.llm_model_id,
+                    row.time_bucket,
+                    row.bucket_start_date,
+                    row.emotion_averages,
+                    row.gauge_values
+                ])?;
             }
         }
 
-        Ok(jobs_enqueued)
+        tx.commit()?;
+        Ok(())
     }
 
-    fn enqueue_job(
+    fn prune_batch(
         &self,
-        tweet_id: i64,
-        llm_model_id: Option<i64>,
+        target: PruneTarget,
+        cutoff: DateTime<Utc>,
+        batch_size: i64,
     ) -> Result<u64, CrawlerError> {
-        let changes = self.conn.execute(
-            "INSERT OR IGNORE INTO analysis_queue (tweet_id, llm_model_id)
-             VALUES (?, ?)",
-            params![tweet_id, llm_model_id],
-        )?;
+        // Each statement is its own transaction; deleted tweets take their
+        // analyses and jobs with them through `ON DELETE CASCADE`.
+        let sql = match target {
+            PruneTarget::Tweets => {
+                "DELETE FROM tweets WHERE id IN (
+                     SELECT id FROM tweets
+                     WHERE julianday(tweet_timestamp) < julianday(?1)
+                     ORDER BY id LIMIT ?2
+                 )"
+            }
+            PruneTarget::ResolvedApiErrors => {
+                "DELETE FROM api_errors WHERE id IN (
+                     SELECT id FROM api_errors
+                     WHERE resolved = 1 AND julianday(occurred_at) < julianday(?1)
+                     ORDER BY id LIMIT ?2
+                 )"
+            }
+            PruneTarget::RunErrorDetails => {
+                "UPDATE crawler_runs SET error_details = NULL WHERE id IN (
+                     SELECT id FROM crawler_runs
+                     WHERE error_details IS NOT NULL
+                       AND julianday(completed_at) < julianday(?1)
+                     ORDER BY id LIMIT ?2
+                 )"
+            }
+            PruneTarget::CompletedJobs => {
+                "DELETE FROM analysis_queue WHERE id IN (
+                     SELECT id FROM analysis_queue
+                     WHERE status = 'completed' AND julianday(updated_at) < julianday(?1)
+                     ORDER BY id LIMIT ?2
+                 )"
+            }
+        };
+        let changes = self
+            .conn
+            .execute(sql, params![cutoff.to_rfc3339(), batch_size])?;
         Ok(changes as u64)
     }
+
+    fn database_size_bytes(&self) -> Result<i64, CrawlerError> {
+        let size = self.conn.query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(size)
+    }
+
+    fn vacuum(&self, mode: VacuumMode) -> Result<(), CrawlerError> {
+        match mode {
+            VacuumMode::Off => {}
+            VacuumMode::Full => self.conn.execute_batch("VACUUM")?,
+            VacuumMode::Incremental => {
+                // Switching to incremental auto-vacuum only takes effect after
+                // one full VACUUM; later runs just release the free pages.
+                let auto_vacuum: i64 =
+                    self.conn
+                        .query_row("PRAGMA auto_vacuum", [], |row| row.get(0))?;
+                if auto_vacuum != 2 {
+                    info!("Enabling incremental auto-vacuum, running a one-off full VACUUM");
+                    self.conn.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
+                    self.conn.execute_batch("VACUUM")?;
+                } else {
+                    self.conn.execute_batch("PRAGMA incremental_vacuum")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn execute_sql(&self, sql: &str) -> Result<(), CrawlerError> {
+        self.conn.execute_batch(sql)?;
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn query_text(&self, sql: &str) -> Result<Option<String>, CrawlerError> {
+        let value = self
+            .conn
+            .query_row(sql, [], |row| row.get(0))
+            .optional()?;
+        Ok(value.flatten())
+    }
+}
+
+/// Tweets a reanalysis request covers, bound as `?1`..`?4`
+const REANALYSIS_SCOPE: &str = "(?1 IS NULL OR t.id = ?1)
+     AND (?2 IS NULL OR t.twitter_user_id = ?2)
+     AND (?3 IS NULL OR julianday(t.tweet_timestamp) >= julianday(?3))
+     AND (?4 IS NULL OR julianday(t.tweet_timestamp) < julianday(?4))";
+
+/// Narrows tweet `t` and model `m.value` pairs to `?6` (only failed jobs) and
+/// `?7` (current analysis has no score for this emotion)
+const REANALYSIS_PAIR_FILTER: &str = "(?6 = 0 OR EXISTS (
+         SELECT 1 FROM analysis_queue q
+         WHERE q.tweet_id = t.id AND q.llm_model_id IS m.value AND q.status = 'failed'
+     ))
+     AND (?7 IS NULL OR NOT EXISTS (
+         SELECT 1 FROM sentiment_analyses s
+         WHERE s.tweet_id = t.id AND s.llm_model_id IS m.value AND s.is_current = 1
+           AND json_extract(s.emotion_scores, '$.' || json_quote(?7)) IS NOT NULL
+     ))";
+
+/// Tweet id, user id and time range a reanalysis request is scoped to
+fn reanalysis_scope(
+    request: &ReanalysisRequest,
+) -> (Option<i64>, Option<i64>, Option<String>, Option<String>) {
+    (
+        storage::reanalysis_tweet(request),
+        storage::reanalysis_user(request),
+        request.tweets_from.map(|value| value.to_rfc3339()),
+        request.tweets_to.map(|value| value.to_rfc3339()),
+    )
+}
+
+/// Model ids as a JSON array, for `json_each`
+fn model_list(model_ids: &[i64]) -> String {
+    serde_json::json!(model_ids).to_string()
+}
+
+/// The Node backend writes these rows without type checks, so a bad value
+/// fails only its own request
+/// `datetime('now', ...)` modifier for the end of a reanalysis lease
+fn lease_modifier() -> String {
+    format!("+{REANALYSIS_LEASE_MINUTES} minutes")
+}
+
+fn reanalysis_request(row: &rusqlite::Row) -> rusqlite::Result<ReanalysisRequest> {
+    Ok(ReanalysisRequest {
+        id: row.get(0)?,
+        request_type: row.get(1)?,
+        tweet_id: row.get(2)?,
+        twitter_user_id: row.get(3)?,
+        cursor_tweet_id: row.get(4)?,
+        tweets_total: row.get(5)?,
+        llm_model_id: row.get(6)?,
+        tweets_from: scope_time(row, 7)?,
+        tweets_to: scope_time(row, 8)?,
+        only_failed: row.get::<_, Option<bool>>(9)?.unwrap_or(false),
+        missing_emotion: row.get(10)?,
+        dry_run: row.get::<_, Option<bool>>(11)?.unwrap_or(false),
+    })
+}
+
+/// Reanalysis time bounds may be RFC 3339, SQLite's `YYYY-MM-DD HH:MM:SS` or
+/// a bare date; anything else fails the request rather than silently widening it
+fn scope_time(row: &rusqlite::Row, index: usize) -> rusqlite::Result<Option<DateTime<Utc>>> {
+    let Some(value) = row.get::<_, Option<String>>(index)? else {
+        return Ok(None);
+    };
+    parse_timestamp(&value)
+        .or_else(|| {
+            chrono::NaiveDateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(|value| value.and_utc())
+        })
+        .or_else(|| {
+            chrono::NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                .ok()
+                .map(|value| value.and_time(chrono::NaiveTime::MIN).and_utc())
+        })
+        .map(Some)
+        .ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                index,
+                rusqlite::types::Type::Text,
+                format!("invalid reanalysis time bound {value:?}").into(),
+            )
+        })
+}
+
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::TwitterApiTweet;
-    use chrono::{Duration, TimeZone};
 
-    fn setup_db() -> Result<Database, CrawlerError> {
+    #[test]
+    fn file_database_uses_wal() -> Result<(), CrawlerError> {
+        let path = std::env::temp_dir().join(format!("crawler-wal-{}.db", std::process::id()));
+        let url = path.to_string_lossy().into_owned();
+        let writer = SqliteDatabase::new(&url, std::time::Duration::from_millis(100))?;
+        let journal_mode: String = writer
+            .conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+        assert_eq!(journal_mode, "wal");
+
+        // A reader is not blocked by an open write transaction.
+        writer
+            .conn
+            .execute_batch("CREATE TABLE t (id INTEGER); BEGIN IMMEDIATE; INSERT INTO t VALUES (1);")?;
+        let reader = SqliteDatabase::new(&url, std::time::Duration::from_millis(100))?;
+        let count: i64 = reader
+            .conn
+            .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))?;
+        assert_eq!(count, 0);
+        writer.conn.execute_batch("COMMIT")?;
+
+        drop((writer, reader));
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{url}{suffix}"));
+        }
+        Ok(())
+    }
+
+    fn setup_db() -> Result<SqliteDatabase, CrawlerError> {
+        let db = node_tables()?;
+        db.init_schema()?;
+        Ok(db)
+    }
+
+    /// Tables as an older Node `schema.sql` created them, before the crawler ran
+    fn node_tables() -> Result<SqliteDatabase, CrawlerError> {
         let conn = Connection::open_in_memory()?;
         conn.execute_batch(
             "CREATE TABLE tweets (
@@ -440,6 +1367,9 @@ mod tests {
             );
             CREATE TABLE llm_models (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT,
+                version TEXT,
+                huggingface_model_id TEXT,
                 is_enabled INTEGER DEFAULT 0
             );
             CREATE TABLE twitter_users (
@@ -447,11 +1377,16 @@ mod tests {
                 twitter_id TEXT,
                 username TEXT,
                 display_name TEXT,
-                is_active INTEGER DEFAULT 1
+                avatar_url TEXT,
+                follower_count INTEGER DEFAULT 0,
+                following_count INTEGER DEFAULT 0,
+                is_active INTEGER DEFAULT 1,
+                updated_at TEXT
             );
             CREATE TABLE crawler_runs (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 status TEXT,
+                completed_at TEXT,
                 tweets_fetched INTEGER DEFAULT 0,
                 tweets_analyzed INTEGER DEFAULT 0,
                 errors_count INTEGER DEFAULT 0,
@@ -469,53 +1404,46 @@ mod tests {
             );",
         )?;
 
-        let db = Database { conn };
-        db.init_schema()?;
-        Ok(db)
+        Ok(SqliteDatabase { conn })
     }
 
     #[test]
-    fn insert_tweets_ignores_duplicates() -> Result<(), CrawlerError> {
-        let db = setup_db()?;
-        let tweet_time = Utc.with_ymd_and_hms(2024, 1, 10, 12, 0, 0).unwrap();
-        let tweet = TwitterApiTweet {
-            id: "tweet_1".to_string(),
-            text: "hello".to_string(),
-            created_at: tweet_time,
-            public_metrics: None,
-            referenced_tweets: None,
+    fn check_schema_reports_an_old_schema_without_migrating_it() -> Result<(), CrawlerError> {
+        let db = node_tables()?;
+        let Err(CrawlerError::Config(message)) = db.check_schema() else {
+            panic!("expected the old schema to be reported");
         };
+        assert!(message.contains("missing table crawler_schedule"), "{message}");
+        let migrated: i64 = db.conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name = 'schema_migrations'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(migrated, 0);
 
-        let (first_inserted, first_jobs, _) =
-            db.insert_tweets_and_enqueue(1, &[tweet.clone()], &[])?;
-        let (second_inserted, second_jobs, _) =
-            db.insert_tweets_and_enqueue(1, &[tweet], &[])?;
-
-        assert_eq!(first_inserted, 1);
-        assert_eq!(first_jobs, 1);
-        assert_eq!(second_inserted, 0);
-        assert_eq!(second_jobs, 0);
-
-        Ok(())
+        db.init_schema()?;
+        db.check_schema()
     }
 
     #[test]
-    fn checkpoint_updates_with_latest_timestamp() -> Result<(), CrawlerError> {
+    fn init_schema_adopts_tables_created_by_node() -> Result<(), CrawlerError> {
         let db = setup_db()?;
-        db.conn.execute(
-            "INSERT INTO twitter_users (id, twitter_id, username, display_name, is_active)
-             VALUES (?, ?, ?, ?, 1)",
-            params![5, "user_5", "user5", "User Five"],
-        )?;
-        let older = Utc::now() - Duration::days(2);
-        let newer = Utc::now() - Duration::days(1);
-
-        db.set_checkpoint(5, older)?;
-        db.set_checkpoint(5, newer)?;
-
-        let stored = db.get_checkpoint(5)?.expect("checkpoint missing");
-        assert_eq!(stored.timestamp(), newer.timestamp());
+        // Re-running init_schema must not try to add the progress columns twice.
+        db.init_schema()?;
 
+        let run_id = db.create_crawler_run()?;
+        db.update_crawler_run_progress(
+            run_id,
+            &RunProgress {
+                users_total: 4,
+                users_completed: 1,
+                current_user: Some("tester".to_string()),
+                tweets_fetched: 12,
+                jobs_queued: 24,
+                errors_count: 0,
+                eta: None,
+            },
+        )?;
         Ok(())
     }
 }
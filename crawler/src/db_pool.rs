@@ -0,0 +1,172 @@
+//! Connection pool that keeps blocking database calls off the runtime threads
+//!
+//! Both storage backends are synchronous. [`DbPool::run`] checks a connection
+//! out of an r2d2 pool on tokio's blocking thread pool, so a large insert or a
+//! reanalysis enqueue never stalls HTTP fetching or the other pipeline stages.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use crate::error::CrawlerError;
+use crate::lock::CrawlerLock;
+use crate::storage::{self, Storage};
+
+struct StorageManager {
+    database_url: String,
+    busy_timeout: Duration,
+}
+
+impl r2d2::ManageConnection for StorageManager {
+    type Connection = Box<dyn Storage>;
+    type Error = CrawlerError;
+
+    fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        storage::open(&self.database_url, self.busy_timeout)
+    }
+
+    fn is_valid(&self, connection: &mut Self::Connection) -> Result<(), Self::Error> {
+        connection.ping()
+    }
+
+    fn has_broken(&self, _connection: &mut Self::Connection) -> bool {
+        false
+    }
+}
+
+/// Shared handle to the database; clones share one pool
+#[derive(Clone)]
+pub struct DbPool {
+    pool: r2d2::Pool<StorageManager>,
+    /// Held while any clone lives, so a restore cannot swap the file underneath
+    _lock: Option<Arc<CrawlerLock>>,
+}
+
+impl DbPool {
+    /// Build a pool of up to `max_size` connections
+    ///
+    /// One connection is opened up front so a bad `DATABASE_URL` fails here
+    /// instead of after the pool's checkout timeout. An in-memory SQLite
+    /// database only exists within its connection, so it gets exactly one that
+    /// is never recycled. A SQLite file also takes the shared
+    /// [`CrawlerLock`].
+    pub async fn open(
+        database_url: &str,
+        busy_timeout: Duration,
+        max_size: u32,
+    ) -> Result<Self, CrawlerError> {
+        let manager = StorageManager {
+            database_url: database_url.to_string(),
+            busy_timeout,
+        };
+        let in_memory = database_url.trim_start_matches("sqlite://") == ":memory:";
+        let sqlite_path = storage::sqlite_path(database_url);
+
+        join(
+            tokio::task::spawn_blocking(move || {
+                r2d2::ManageConnection::connect(&manager)?;
+                let lock = sqlite_path
+                    .map(|path| CrawlerLock::shared(&path).map(Arc::new))
+                    .transpose()?;
+
+                let builder = r2d2::Pool::builder().min_idle(Some(0));
+                let builder = if in_memory {
+                    builder.max_size(1).idle_timeout(None).max_lifetime(None)
+                } else {
+                    builder.max_size(max_size.max(1))
+                };
+                let pool = builder
+                    .build(manager)
+                    .map_err(|error| CrawlerError::Pool(error.to_string()))?;
+                Ok(Self { pool, _lock: lock })
+            })
+            .await,
+        )?
+    }
+
+    /// Run `f` on a pooled connection and wait for the result
+    pub async fn run<T, F>(&self, f: F) -> Result<T, CrawlerError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn Storage) -> Result<T, CrawlerError> + Send + 'static,
+    {
+        self.spawn(f).wait().await
+    }
+
+    /// Start `f` on a pooled connection without waiting, so the caller can
+    /// keep doing network I/O while it runs
+    pub fn spawn<T, F>(&self, f: F) -> DbTask<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn Storage) -> Result<T, CrawlerError> + Send + 'static,
+    {
+        let pool = self.pool.clone();
+        DbTask(tokio::task::spawn_blocking(move || {
+            let connection = pool
+                .get()
+                .map_err(|error| CrawlerError::Pool(error.to_string()))?;
+            f(&**connection)
+        }))
+    }
+}
+
+/// A database call running on the blocking thread pool
+///
+/// Dropping it does not cancel the call.
+pub struct DbTask<T>(JoinHandle<Result<T, CrawlerError>>);
+
+impl<T> DbTask<T> {
+    pub async fn wait(self) -> Result<T, CrawlerError> {
+        join(self.0.await)?
+    }
+}
+
+/// Re-raise a panic from the blocking closure on the awaiting task
+fn join<T>(result: Result<T, tokio::task::JoinError>) -> Result<T, CrawlerError> {
+    match result {
+        Ok(value) => Ok(value),
+        Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+        Err(error) => Err(CrawlerError::Pool(error.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn pooled_connections_share_a_file_database() -> Result<(), CrawlerError> {
+        let path = std::env::temp_dir().join(format!("crawler-pool-{}.db", std::process::id()));
+        let pool = DbPool::open(&path.to_string_lossy(), Duration::from_secs(1), 4).await?;
+        pool.run(|database| database.init_schema()).await?;
+
+        // Several calls in flight at once, each on its own connection.
+        let tasks: Vec<_> = (0..4)
+            .map(|n| {
+                pool.spawn(move |database| {
+                    database.insert_api_error("other", &format!("error {n}"), None, None)
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.wait().await?;
+        }
+        let errors = pool
+            .run(|database| database.query_text("SELECT CAST(COUNT(*) AS TEXT) FROM api_errors"))
+            .await?;
+        assert_eq!(errors.as_deref(), Some("4"));
+
+        drop(pool);
+        for suffix in ["", "-wal", "-shm", ".lock"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_fails_fast_on_an_unreachable_database() {
+        let result = DbPool::open("/nonexistent/crawler.db", Duration::from_secs(1), 4).await;
+        assert!(matches!(result, Err(CrawlerError::Database(_))));
+    }
+}
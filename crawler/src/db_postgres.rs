@@ -0,0 +1,1329 @@
+//! PostgreSQL storage backend
+//!
+//! Selected when `DATABASE_URL` is a `postgres://` URL. The Node backend only
+//! speaks SQLite, so here the crawler owns the whole schema (see
+//! `migrations/postgres`). Timestamps are stored as `TIMESTAMPTZ` and returned
+//! in the same text formats the SQLite backend produces.
+
+use std::cell::{RefCell, RefMut};
+
+use chrono::{DateTime, Utc};
+use postgres::types::ToSql;
+use postgres::{Client, GenericClient, NoTls};
+
+use crate::error::CrawlerError;
+use crate::migrations;
+use crate::models::{
+    AnalysisJob, AnalysisScoreRow, CrawlRunUser, CrawlerRunSummary, FetchProgress,
+    GlobalAggregation, LlmModel, ReanalysisChunk, ReanalysisRequest, RunProgress, TrackedUser,
+    TwitterApiTweet, UserAggregation, UserCrawlOutcome,
+};
+use crate::retention::{PruneTarget, VacuumMode};
+use crate::schema_compat;
+use crate::storage::{
+    self, ApiErrorDetail, Storage, StoredConfiguration, TweetTypes, REANALYSIS_LEASE_MINUTES,
+};
+use crate::telemetry;
+
+pub struct PostgresDatabase {
+    /// Only `None` while dropping
+    client: RefCell<Option<Client>>,
+    /// Schema created for one test and dropped with it
+    #[cfg(test)]
+    test_schema: Option<String>,
+}
+
+impl PostgresDatabase {
+    pub fn connect(database_url: &str) -> Result<Self, CrawlerError> {
+        let client = Client::connect(database_url, NoTls)?;
+        Ok(Self {
+            client: RefCell::new(Some(client)),
+            #[cfg(test)]
+            test_schema: None,
+        })
+    }
+
+    /// Connect with a fresh, empty schema on the search path, dropped on drop
+    #[cfg(test)]
+    pub fn connect_isolated(database_url: &str) -> Result<Self, CrawlerError> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static NEXT_SCHEMA: AtomicUsize = AtomicUsize::new(0);
+        let schema = format!(
+            "crawler_test_{}_{}",
+            std::process::id(),
+            NEXT_SCHEMA.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut database = Self::connect(database_url)?;
+        database.with_client(|client| {
+            client.batch_execute(&format!(
+                "DROP SCHEMA IF EXISTS {schema} CASCADE;
+                 CREATE SCHEMA {schema};
+                 SET search_path TO {schema}"
+            ))
+        })?;
+        database.test_schema = Some(schema);
+        Ok(database)
+    }
+
+    fn client(&self) -> RefMut<'_, Client> {
+        RefMut::map(self.client.borrow_mut(), |client| {
+            client.as_mut().expect("PostgreSQL client used after drop")
+        })
+    }
+
+    fn with_client<T>(
+        &self,
+        f: impl FnOnce(&mut Client) -> Result<T, postgres::Error>,
+    ) -> Result<T, CrawlerError> {
+        Ok(f(&mut self.client())?)
+    }
+
+    fn query_run_users(
+        &self,
+        filter: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<CrawlRunUser>, CrawlerError> {
+        let rows = self.with_client(|client| {
+            client.query(
+                &format!(
+                    "SELECT cru.run_id, cru.twitter_user_id, tu.username, cru.status,
+                            cru.tweets_fetched, cru.pages_fetched, cru.api_calls, cru.duration_ms,
+                            cru.api_error_id, cru.error_message, cru.completed_at
+                     FROM crawler_run_users cru
+                     JOIN twitter_users tu ON tu.id = cru.twitter_user_id
+                     {filter}"
+                ),
+                params,
+            )
+        })?;
+
+        Ok(rows
+            .iter()
+            .map(|row| CrawlRunUser {
+                run_id: row.get(0),
+                twitter_user_id: row.get(1),
+                username: row.get(2),
+                status: row.get(3),
+                tweets_fetched: row.get::<_, Option<i64>>(4).unwrap_or(0),
+                pages_fetched: row.get::<_, Option<i64>>(5).unwrap_or(0),
+                api_calls: row.get::<_, Option<i64>>(6).unwrap_or(0),
+                duration_ms: row.get::<_, Option<i64>>(7).unwrap_or(0),
+                api_error_id: row.get(8),
+                error_message: row.get(9),
+                completed_at: row.get::<_, Option<DateTime<Utc>>>(10).map(sql_time),
+            })
+            .collect())
+    }
+}
+
+impl Drop for PostgresDatabase {
+    /// Closing the connection blocks on the client's runtime, and the last
+    /// pool handle may be dropped on a runtime thread
+    fn drop(&mut self) {
+        let Some(client) = self.client.get_mut().take() else {
+            return;
+        };
+        #[cfg(test)]
+        let test_schema = self.test_schema.take();
+        off_runtime(move || {
+            #[cfg(test)]
+            let client = {
+                let mut client = client;
+                if let Some(schema) = test_schema {
+                    let _ =
+                        client.batch_execute(&format!("DROP SCHEMA IF EXISTS {schema} CASCADE"));
+                }
+                client
+            };
+            let _ = client.close();
+        });
+    }
+}
+
+impl Storage for PostgresDatabase {
+    fn init_schema(&self) -> Result<(), CrawlerError> {
+        let mut client = self.client();
+        migrations::migrate_postgres(&mut client)?;
+        schema_compat::check_postgres(&mut client)
+    }
+
+    fn check_schema(&self) -> Result<(), CrawlerError> {
+        schema_compat::check_postgres(&mut self.client())
+    }
+
+    fn schema_version(&self) -> Result<Option<i64>, CrawlerError> {
+        self.with_client(|client| {
+            Ok(client
+                .query_one("SELECT MAX(version) FROM schema_migrations", &[])?
+                .get(0))
+        })
+    }
+
+    fn latest_schema_version(&self) -> i64 {
+        migrations::latest_postgres_version()
+    }
+
+    fn ping(&self) -> Result<(), CrawlerError> {
+        self.with_client(|client| client.query_one("SELECT 1", &[]).map(|_| ()))
+    }
+
+    fn create_crawler_run(&self) -> Result<i64, CrawlerError> {
+        self.with_client(|client| {
+            Ok(client
+                .query_one(
+                    "INSERT INTO crawler_runs (status, tweets_fetched, tweets_analyzed, errors_count)
+                     VALUES ('running', 0, 0, 0)
+                     RETURNING id",
+                    &[],
+                )?
+                .get(0))
+        })
+    }
+
+    fn complete_crawler_run(
+        &self,
+        run_id: i64,
+        status: &str,
+        tweets_fetched: u64,
+        tweets_analyzed: u64,
+        error_details: &[ApiErrorDetail],
+    ) -> Result<(), CrawlerError> {
+        let error_count = error_details.len() as i64;
+        let error_json = storage::error_details_json(error_details)?;
+        let tweets_fetched = tweets_fetched as i64;
+        let tweets_analyzed = tweets_analyzed as i64;
+
+        self.with_client(|client| {
+            client.execute(
+                "UPDATE crawler_runs
+                 SET status = $1,
+                     completed_at = now(),
+                     tweets_fetched = $2,
+                     tweets_analyzed = $3,
+                     jobs_queued = $3,
+                     errors_count = $4,
+                     error_details = $5,
+                     \"current_user\" = NULL,
+                     eta_at = NULL,
+                     progress_updated_at = now()
+                 WHERE id = $6",
+                &[
+                    &status,
+                    &tweets_fetched,
+                    &tweets_analyzed,
+                    &error_count,
+                    &error_json,
+                    &run_id,
+                ],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn update_crawler_run_progress(
+        &self,
+        run_id: i64,
+        progress: &RunProgress,
+    ) -> Result<(), CrawlerError> {
+        self.with_client(|client| {
+            client.execute(
+                "UPDATE crawler_runs
+                 SET users_total = $1,
+                     users_completed = $2,
+                     \"current_user\" = $3,
+                     tweets_fetched = $4,
+                     tweets_analyzed = $5,
+                     jobs_queued = $5,
+                     errors_count = $6,
+                     eta_at = $7,
+                     progress_updated_at = now()
+                 WHERE id = $8",
+                &[
+                    &progress.users_total,
+                    &progress.users_completed,
+                    &progress.current_user,
+                    &progress.tweets_fetched,
+                    &progress.jobs_queued,
+                    &progress.errors_count,
+                    &progress.eta,
+                    &run_id,
+                ],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn fail_abandoned_runs(&self, stale_minutes: u64) -> Result<u64, CrawlerError> {
+        let stale_minutes = i32::try_from(stale_minutes).unwrap_or(i32::MAX);
+        self.with_client(|client| {
+            client.execute(
+                "UPDATE crawler_runs
+                 SET status = 'failed',
+                     completed_at = now(),
+                     \"current_user\" = NULL,
+                     eta_at = NULL
+                 WHERE status = 'running'
+                   AND COALESCE(progress_updated_at, started_at)
+                       < now() - make_interval(mins => $1)",
+                &[&stale_minutes],
+            )
+        })
+    }
+
+    fn set_next_scheduled_run(
+        &self,
+        schedule: &str,
+        next_run: Option<DateTime<Utc>>,
+    ) -> Result<(), CrawlerError> {
+        self.with_client(|client| {
+            client.execute(
+                "INSERT INTO crawler_schedule (id, schedule, next_scheduled_run, updated_at)
+                 VALUES (1, $1, $2, now())
+                 ON CONFLICT (id) DO UPDATE SET
+                     schedule = excluded.schedule,
+                     next_scheduled_run = excluded.next_scheduled_run,
+                     updated_at = now()",
+                &[&schedule, &next_run],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn insert_api_error(
+        &self,
+        error_type: &str,
+        error_message: &str,
+        error_code: Option<&str>,
+        endpoint: Option<&str>,
+    ) -> Result<i64, CrawlerError> {
+        self.with_client(|client| {
+            Ok(client
+                .query_one(
+                    "INSERT INTO api_errors (error_type, error_message, error_code, endpoint, resolved)
+                     VALUES ($1, $2, $3, $4, FALSE)
+                     RETURNING id",
+                    &[&error_type, &error_message, &error_code, &endpoint],
+                )?
+                .get(0))
+        })
+    }
+
+    fn record_run_user(
+        &self,
+        run_id: i64,
+        twitter_user_id: i64,
+        outcome: &UserCrawlOutcome,
+    ) -> Result<(), CrawlerError> {
+        self.with_client(|client| {
+            client.execute(
+                "INSERT INTO crawler_run_users
+                 (run_id, twitter_user_id, status, tweets_fetched, pages_fetched, api_calls,
+                  duration_ms, api_error_id, error_message)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                 ON CONFLICT (run_id, twitter_user_id) DO UPDATE SET
+                   status = excluded.status,
+                   tweets_fetched = excluded.tweets_fetched,
+                   pages_fetched = excluded.pages_fetched,
+                   api_calls = excluded.api_calls,
+                   duration_ms = excluded.duration_ms,
+                   api_error_id = excluded.api_error_id,
+                   error_message = excluded.error_message,
+                   completed_at = now()",
+                &[
+                    &run_id,
+                    &twitter_user_id,
+                    &outcome.status,
+                    &(outcome.tweets_fetched as i64),
+                    &(outcome.pages_fetched as i64),
+                    &(outcome.api_calls as i64),
+                    &outcome.duration_ms,
+                    &outcome.api_error_id,
+                    &outcome.error_message,
+                ],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn user_crawl_history(
+        &self,
+        twitter_user_id: i64,
+        limit: i64,
+    ) -> Result<Vec<CrawlRunUser>, CrawlerError> {
+        self.query_run_users(
+            "WHERE cru.twitter_user_id = $1 ORDER BY cru.run_id DESC LIMIT $2",
+            &[&twitter_user_id, &limit],
+        )
+    }
+
+    fn run_user_outcomes(&self, run_id: i64) -> Result<Vec<CrawlRunUser>, CrawlerError> {
+        self.query_run_users("WHERE cru.run_id = $1 ORDER BY cru.id", &[&run_id])
+    }
+
+    fn last_crawl_started(
+        &self,
+        twitter_user_id: i64,
+    ) -> Result<Option<DateTime<Utc>>, CrawlerError> {
+        self.with_client(|client| {
+            Ok(client
+                .query_opt(
+                    "SELECT completed_at - COALESCE(duration_ms, 0) * interval '1 millisecond'
+                     FROM crawler_run_users
+                     WHERE twitter_user_id = $1 AND status = 'completed'
+                     ORDER BY id DESC LIMIT 1",
+                    &[&twitter_user_id],
+                )?
+                .and_then(|row| row.get(0)))
+        })
+    }
+
+    fn load_active_users(&self) -> Result<Vec<TrackedUser>, CrawlerError> {
+        let rows = self.with_client(|client| {
+            client.query(
+                "SELECT id, twitter_id, username, display_name
+                 FROM twitter_users
+                 WHERE is_active",
+                &[],
+            )
+        })?;
+
+        Ok(rows
+            .iter()
+            .map(|row| TrackedUser {
+                id: row.get(0),
+                twitter_id: row.get(1),
+                username: row.get(2),
+                display_name: row.get(3),
+            })
+            .collect())
+    }
+
+    fn find_user_id(&self, username: &str) -> Result<Option<i64>, CrawlerError> {
+        self.with_client(|client| {
+            Ok(client
+                .query_opt(
+                    "SELECT id FROM twitter_users WHERE lower(username) = lower($1) LIMIT 1",
+                    &[&username],
+                )?
+                .map(|row| row.get(0)))
+        })
+    }
+
+    fn update_user_from_api(
+        &self,
+        username: &str,
+        twitter_id: &str,
+        display_name: Option<&str>,
+        avatar_url: Option<&str>,
+        follower_count: Option<u64>,
+        following_count: Option<u64>,
+    ) -> Result<(), CrawlerError> {
+        let follower_count = follower_count.map(|value| value as i64);
+        let following_count = following_count.map(|value| value as i64);
+        self.with_client(|client| {
+            client.execute(
+                "UPDATE twitter_users
+                 SET twitter_id = COALESCE($1, twitter_id),
+                     display_name = COALESCE($2, display_name),
+                     avatar_url = COALESCE($3, avatar_url),
+                     follower_count = COALESCE($4, follower_count),
+                     following_count = COALESCE($5, following_count),
+                     updated_at = now()
+                 WHERE username = $6",
+                &[
+                    &twitter_id,
+                    &display_name,
+                    &avatar_url,
+                    &follower_count,
+                    &following_count,
+                    &username,
+                ],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn get_enabled_model_ids(&self) -> Result<Vec<i64>, CrawlerError> {
+        let rows = self.with_client(|client| {
+            client.query("SELECT id FROM llm_models WHERE is_enabled", &[])
+        })?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    fn load_models(&self) -> Result<Vec<LlmModel>, CrawlerError> {
+        let rows = self.with_client(|client| {
+            client.query(
+                "SELECT id, name, huggingface_model_id, is_enabled FROM llm_models ORDER BY id",
+                &[],
+            )
+        })?;
+        Ok(rows
+            .iter()
+            .map(|row| LlmModel {
+                id: row.get(0),
+                name: row.get(1),
+                huggingface_model_id: row.get(2),
+                is_enabled: row.get::<_, Option<bool>>(3).unwrap_or(false),
+            })
+            .collect())
+    }
+
+    fn get_checkpoint(&self, twitter_user_id: i64) -> Result<Option<DateTime<Utc>>, CrawlerError> {
+        self.with_client(|client| {
+            Ok(client
+                .query_opt(
+                    "SELECT last_tweet_timestamp FROM crawler_checkpoints WHERE twitter_user_id = $1",
+                    &[&twitter_user_id],
+                )?
+                .map(|row| row.get(0)))
+        })
+    }
+
+    fn set_checkpoint(
+        &self,
+        twitter_user_id: i64,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), CrawlerError> {
+        self.with_client(|client| {
+            client.execute(
+                "INSERT INTO crawler_checkpoints (twitter_user_id, last_tweet_timestamp, updated_at)
+                 VALUES ($1, $2, now())
+                 ON CONFLICT (twitter_user_id) DO UPDATE SET
+                     last_tweet_timestamp = excluded.last_tweet_timestamp,
+                     updated_at = now()",
+                &[&twitter_user_id, &timestamp],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn get_fetch_progress(
+        &self,
+        twitter_user_id: i64,
+    ) -> Result<Option<FetchProgress>, CrawlerError> {
+        self.with_client(|client| {
+            Ok(client
+                .query_opt(
+                    "SELECT start_time, end_time, pagination_token, newest_timestamp
+                     FROM crawler_fetch_progress
+                     WHERE twitter_user_id = $1",
+                    &[&twitter_user_id],
+                )?
+                .map(|row| FetchProgress {
+                    start_time: row.get(0),
+                    end_time: row.get(1),
+                    pagination_token: row.get(2),
+                    newest_timestamp: row.get(3),
+                }))
+        })
+    }
+
+    fn save_fetch_progress(
+        &self,
+        twitter_user_id: i64,
+        progress: &FetchProgress,
+    ) -> Result<(), CrawlerError> {
+        self.with_client(|client| {
+            client.execute(
+                "INSERT INTO crawler_fetch_progress
+                 (twitter_user_id, start_time, end_time, pagination_token, newest_timestamp, updated_at)
+                 VALUES ($1, $2, $3, $4, $5, now())
+                 ON CONFLICT (twitter_user_id) DO UPDATE SET
+                     start_time = excluded.start_time,
+                     end_time = excluded.end_time,
+                     pagination_token = excluded.pagination_token,
+                     newest_timestamp = excluded.newest_timestamp,
+                     updated_at = now()",
+                &[
+                    &twitter_user_id,
+                    &progress.start_time,
+                    &progress.end_time,
+                    &progress.pagination_token,
+                    &progress.newest_timestamp,
+                ],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn clear_fetch_progress(&self, twitter_user_id: i64) -> Result<(), CrawlerError> {
+        self.with_client(|client| {
+            client.execute(
+                "DELETE FROM crawler_fetch_progress WHERE twitter_user_id = $1",
+                &[&twitter_user_id],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn insert_tweets_and_enqueue(
+        &self,
+        twitter_user_id: i64,
+        tweets: &[TwitterApiTweet],
+        enabled_model_ids: &[i64],
+        skip_analysis: TweetTypes,
+    ) -> Result<(u64, u64, Option<DateTime<Utc>>), CrawlerError> {
+        let (tweets_inserted, jobs_enqueued) = self.with_client(|client| {
+            let mut tx = client.transaction()?;
+            let insert = tx.prepare(
+                "INSERT INTO tweets
+                 (twitter_user_id, tweet_id, content, tweet_timestamp, engagement_metrics, is_retweet, is_reply)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7)
+                 ON CONFLICT (tweet_id) DO NOTHING
+                 RETURNING id",
+            )?;
+
+            let mut tweets_inserted = 0_u64;
+            let mut jobs_enqueued = 0_u64;
+            for tweet in tweets {
+                let inserted = tx.query_opt(
+                    &insert,
+                    &[
+                        &twitter_user_id,
+                        &tweet.id,
+                        &tweet.text,
+                        &tweet.created_at,
+                        &storage::engagement_json(tweet),
+                        &storage::references(tweet, "retweeted"),
+                        &storage::references(tweet, "replied_to"),
+                    ],
+                )?;
+                if let Some(row) = inserted {
+                    tweets_inserted += 1;
+                    if !skip_analysis.contains(tweet) {
+                        jobs_enqueued += enqueue_jobs(&mut tx, row.get(0), enabled_model_ids)?;
+                    }
+                }
+            }
+            tx.commit()?;
+            Ok((tweets_inserted, jobs_enqueued))
+        })?;
+
+        let latest_timestamp = tweets.iter().map(|tweet| tweet.created_at).max();
+        telemetry::record_tweets_inserted(tweets_inserted);
+        Ok((tweets_inserted, jobs_enqueued, latest_timestamp))
+    }
+
+    fn count_reanalysis_tweets(
+        &self,
+        request: &ReanalysisRequest,
+        skip_analysis: TweetTypes,
+    ) -> Result<i64, CrawlerError> {
+        let (tweet_id, user_id) = scope_ids(request);
+        let skipped = storage::skipped_tweet_types(request, skip_analysis);
+        self.with_client(|client| {
+            let row = client.query_one(
+                &format!("SELECT COUNT(*) FROM tweets t WHERE {REANALYSIS_SCOPE}{skipped}"),
+                &[
+                    &tweet_id,
+                    &user_id,
+                    &request.tweets_from,
+                    &request.tweets_to,
+                ],
+            )?;
+            Ok(row.get(0))
+        })
+    }
+
+    fn estimate_reanalysis_jobs(
+        &self,
+        request: &ReanalysisRequest,
+        model_ids: &[i64],
+        skip_analysis: TweetTypes,
+    ) -> Result<i64, CrawlerError> {
+        let (tweet_id, user_id) = scope_ids(request);
+        let skipped = storage::skipped_tweet_types(request, skip_analysis);
+        let models = model_ids.to_vec();
+        self.with_client(|client| {
+            // Jobs already waiting are left as they are, so they are not counted.
+            let row = client.query_one(
+                &format!(
+                    "SELECT COUNT(*) FROM tweets t, unnest($5::BIGINT[]) AS m(id)
+                     WHERE {REANALYSIS_SCOPE}{skipped} AND {REANALYSIS_PAIR_FILTER}
+                       AND NOT EXISTS (
+                           SELECT 1 FROM analysis_queue q
+                           WHERE q.tweet_id = t.id AND q.llm_model_id IS NOT DISTINCT FROM m.id
+                             AND q.status IN ('pending', 'processing')
+                       )"
+                ),
+                &[
+                    &tweet_id,
+                    &user_id,
+                    &request.tweets_from,
+                    &request.tweets_to,
+                    &models,
+                    &request.only_failed,
+                    &request.missing_emotion,
+                ],
+            )?;
+            Ok(row.get(0))
+        })
+    }
+
+    fn enqueue_reanalysis_chunk(
+        &self,
+        request: &ReanalysisRequest,
+        after_tweet_id: i64,
+        chunk_size: i64,
+        model_ids: &[i64],
+        skip_analysis: TweetTypes,
+    ) -> Result<ReanalysisChunk, CrawlerError> {
+        let (tweet_id, user_id) = scope_ids(request);
+        let skipped = storage::skipped_tweet_types(request, skip_analysis);
+        let models = model_ids.to_vec();
+        self.with_client(|client| {
+            let mut tx = client.transaction()?;
+            let tweet_ids: Vec<i64> = tx
+                .query(
+                    &format!(
+                        "SELECT t.id FROM tweets t
+                         WHERE {REANALYSIS_SCOPE}{skipped} AND t.id > $5
+                         ORDER BY t.id
+                         LIMIT $6"
+                    ),
+                    &[
+                        &tweet_id,
+                        &user_id,
+                        &request.tweets_from,
+                        &request.tweets_to,
+                        &after_tweet_id,
+                        &chunk_size,
+                    ],
+                )?
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+
+            let last_tweet_id = tweet_ids.last().copied();
+            let mut jobs_enqueued = 0_u64;
+            if let Some(last) = last_tweet_id {
+                let pairs: Vec<(i64, Option<i64>)> = tx
+                    .query(
+                        &format!(
+                            "SELECT t.id, m.id FROM tweets t, unnest($5::BIGINT[]) AS m(id)
+                             WHERE {REANALYSIS_SCOPE}{skipped} AND {REANALYSIS_PAIR_FILTER}
+                               AND t.id > $8 AND t.id <= $9
+                             ORDER BY t.id"
+                        ),
+                        &[
+                            &tweet_id,
+                            &user_id,
+                            &request.tweets_from,
+                            &request.tweets_to,
+                            &models,
+                            &request.only_failed,
+                            &request.missing_emotion,
+                            &after_tweet_id,
+                            &last,
+                        ],
+                    )?
+                    .iter()
+                    .map(|row| (row.get(0), row.get(1)))
+                    .collect();
+                for (pair_tweet_id, llm_model_id) in pairs {
+                    jobs_enqueued += enqueue_job(&mut tx, pair_tweet_id, llm_model_id, true)?;
+                }
+            }
+
+            tx.execute(
+                "UPDATE reanalysis_requests
+                 SET cursor_tweet_id = COALESCE($1, cursor_tweet_id),
+                     tweets_processed = COALESCE(tweets_processed, 0) + $2,
+                     jobs_enqueued = COALESCE(jobs_enqueued, 0) + $4,
+                     progress_percent = LEAST(
+                         100.0,
+                         (COALESCE(tweets_processed, 0) + $2) * 100.0 / GREATEST(COALESCE(tweets_total, 0), 1)
+                     ),
+                     lease_expires_at = now() + make_interval(mins => $5)
+                 WHERE id = $3",
+                &[
+                    &last_tweet_id,
+                    &(tweet_ids.len() as i64),
+                    &request.id,
+                    &(jobs_enqueued as i64),
+                    &REANALYSIS_LEASE_MINUTES,
+                ],
+            )?;
+            tx.commit()?;
+
+            Ok(ReanalysisChunk {
+                tweets: tweet_ids.len() as u64,
+                jobs_enqueued,
+                last_tweet_id,
+            })
+        })
+    }
+
+    fn claim_reanalysis_requests(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<ReanalysisRequest>, CrawlerError> {
+        // `SKIP LOCKED` leaves requests another claim is taking right now to it.
+        let mut rows = self.with_client(|client| {
+            client.query(
+                "UPDATE reanalysis_requests
+                 SET lease_expires_at = now() + make_interval(mins => $2)
+                 WHERE id IN (
+                     SELECT id
+                     FROM reanalysis_requests
+                     WHERE status IN ('pending', 'processing')
+                       AND (lease_expires_at IS NULL OR lease_expires_at < now())
+                     ORDER BY requested_at ASC, id ASC
+                     LIMIT $1
+                     FOR UPDATE SKIP LOCKED
+                 )
+                 RETURNING id, request_type, tweet_id, twitter_user_id, cursor_tweet_id,
+                           tweets_total, llm_model_id, tweets_from, tweets_to, only_failed,
+                           missing_emotion, dry_run, requested_at",
+                &[&limit, &REANALYSIS_LEASE_MINUTES],
+            )
+        })?;
+        rows.sort_by_key(|row| {
+            (
+                row.get::<_, Option<DateTime<Utc>>>(12),
+                row.get::<_, i64>(0),
+            )
+        });
+
+        Ok(rows
+            .iter()
+            .map(|row| ReanalysisRequest {
+                id: row.get(0),
+                request_type: row.get(1),
+                tweet_id: row.get(2),
+                twitter_user_id: row.get(3),
+                cursor_tweet_id: row.get(4),
+                tweets_total: row.get(5),
+                llm_model_id: row.get(6),
+                tweets_from: row.get(7),
+                tweets_to: row.get(8),
+                only_failed: row.get::<_, Option<bool>>(9).unwrap_or(false),
+                missing_emotion: row.get(10),
+                dry_run: row.get::<_, Option<bool>>(11).unwrap_or(false),
+            })
+            .collect())
+    }
+
+    fn release_reanalysis_request(&self, request_id: i64) -> Result<(), CrawlerError> {
+        self.with_client(|client| {
+            client.execute(
+                "UPDATE reanalysis_requests SET lease_expires_at = NULL WHERE id = $1",
+                &[&request_id],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn mark_reanalysis_processing(
+        &self,
+        request_id: i64,
+        tweets_total: Option<i64>,
+    ) -> Result<(), CrawlerError> {
+        self.with_client(|client| {
+            client.execute(
+                "UPDATE reanalysis_requests
+                 SET status = 'processing',
+                     tweets_total = COALESCE(tweets_total, $2)
+                 WHERE id = $1",
+                &[&request_id, &tweets_total],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn mark_reanalysis_completed(&self, request_id: i64) -> Result<(), CrawlerError> {
+        self.with_client(|client| {
+            client.execute(
+                "UPDATE reanalysis_requests
+                 SET status = 'completed',
+                     progress_percent = 100,
+                     processed_at = now(),
+                     lease_expires_at = NULL
+                 WHERE id = $1",
+                &[&request_id],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn mark_reanalysis_failed(
+        &self,
+        request_id: i64,
+        error_message: &str,
+    ) -> Result<(), CrawlerError> {
+        self.with_client(|client| {
+            client.execute(
+                "UPDATE reanalysis_requests
+                 SET status = 'failed',
+                     error_message = $2,
+                     processed_at = now(),
+                     lease_expires_at = NULL
+                 WHERE id = $1",
+                &[&request_id, &error_message],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn record_reanalysis_estimate(
+        &self,
+        request_id: i64,
+        jobs_estimate: i64,
+    ) -> Result<(), CrawlerError> {
+        self.with_client(|client| {
+            client.execute(
+                "UPDATE reanalysis_requests
+                 SET status = 'estimated',
+                     jobs_estimate = $2,
+                     processed_at = now(),
+                     lease_expires_at = NULL
+                 WHERE id = $1",
+                &[&request_id, &jobs_estimate],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn latest_crawler_run(&self) -> Result<Option<CrawlerRunSummary>, CrawlerError> {
+        let row = self.with_client(|client| {
+            client.query_opt(
+                "SELECT id, status, started_at, completed_at, tweets_fetched, tweets_analyzed, errors_count
+                 FROM crawler_runs
+                 ORDER BY id DESC
+                 LIMIT 1",
+                &[],
+            )
+        })?;
+
+        Ok(row.map(|row| CrawlerRunSummary {
+            id: row.get(0),
+            status: row.get::<_, Option<String>>(1).unwrap_or_default(),
+            started_at: row.get::<_, Option<DateTime<Utc>>>(2).map(sql_time),
+            completed_at: row.get::<_, Option<DateTime<Utc>>>(3).map(sql_time),
+            tweets_fetched: row.get::<_, Option<i64>>(4).unwrap_or(0),
+            tweets_analyzed: row.get::<_, Option<i64>>(5).unwrap_or(0),
+            errors_count: row.get::<_, Option<i64>>(6).unwrap_or(0),
+        }))
+    }
+
+    fn load_stored_configuration(
+        &self,
+        key: &str,
+    ) -> Result<Option<StoredConfiguration>, CrawlerError> {
+        let row: Option<(String, Option<String>)> = self.with_client(|client| {
+            Ok(client
+                .query_opt(
+                    "SELECT value, updated_by FROM configurations WHERE key = $1",
+                    &[&key],
+                )?
+                .map(|row| (row.get(0), row.get(1))))
+        })?;
+
+        let Some((raw, updated_by)) = row else {
+            return Ok(None);
+        };
+
+        let value = serde_json::from_str(&raw)
+            .map_err(|err| CrawlerError::Config(format!("Invalid {key} configuration: {err}")))?;
+        Ok(Some(StoredConfiguration { value, updated_by }))
+    }
+
+    fn claim_analysis_jobs(
+        &self,
+        limit: i64,
+        llm_model_id: Option<i64>,
+    ) -> Result<Vec<AnalysisJob>, CrawlerError> {
+        // SKIP LOCKED lets concurrent workers claim disjoint batches without waiting.
+        let rows = self.with_client(|client| {
+            client.query(
+                "WITH claimed AS (
+                     UPDATE analysis_queue
+                     SET status = 'processing',
+                         attempt_count = COALESCE(attempt_count, 0) + 1,
+                         updated_at = now()
+                     WHERE id IN (
+                         SELECT q.id
+                         FROM analysis_queue q
+                         JOIN llm_models m ON m.id = q.llm_model_id
+                         WHERE q.status = 'pending'
+                           AND m.is_enabled
+                           AND ($1::BIGINT IS NULL OR q.llm_model_id = $1)
+                         ORDER BY q.id ASC
+                         LIMIT $2
+                         FOR UPDATE OF q SKIP LOCKED
+                     )
+                     RETURNING id, tweet_id, llm_model_id, attempt_count
+                 )
+                 SELECT c.id, c.tweet_id, c.llm_model_id,
+                        COALESCE(m.huggingface_model_id, m.name), t.content, c.attempt_count
+                 FROM claimed c
+                 JOIN tweets t ON t.id = c.tweet_id
+                 JOIN llm_models m ON m.id = c.llm_model_id
+                 ORDER BY c.id",
+                &[&llm_model_id, &limit],
+            )
+        })?;
+
+        Ok(rows
+            .iter()
+            .map(|row| AnalysisJob {
+                id: row.get(0),
+                tweet_id: row.get(1),
+                llm_model_id: row.get(2),
+                model_name: row.get(3),
+                content: row.get(4),
+                attempt_count: row.get::<_, Option<i64>>(5).unwrap_or(0),
+            })
+            .collect())
+    }
+
+    fn complete_analysis_job(
+        &self,
+        job: &AnalysisJob,
+        emotion_scores: &str,
+        raw_llm_response: &str,
+        analysis_duration_ms: i64,
+    ) -> Result<(), CrawlerError> {
+        self.with_client(|client| {
+            let mut tx = client.transaction()?;
+            tx.execute(
+                "UPDATE sentiment_analyses
+                 SET is_current = FALSE
+                 WHERE tweet_id = $1 AND llm_model_id = $2 AND is_current",
+                &[&job.tweet_id, &job.llm_model_id],
+            )?;
+            tx.execute(
+                "INSERT INTO sentiment_analyses
+                 (tweet_id, llm_model_id, emotion_scores, raw_llm_response, analysis_duration_ms,
+                  model_version, analysis_version, is_current)
+                 VALUES ($1, $2, $3, $4, $5,
+                         (SELECT version FROM llm_models WHERE id = $2),
+                         COALESCE(
+                             (SELECT MAX(analysis_version) FROM sentiment_analyses
+                              WHERE tweet_id = $1 AND llm_model_id = $2),
+                             0
+                         ) + 1,
+                         TRUE)",
+                &[
+                    &job.tweet_id,
+                    &job.llm_model_id,
+                    &emotion_scores,
+                    &raw_llm_response,
+                    &analysis_duration_ms,
+                ],
+            )?;
+            tx.execute(
+                "UPDATE analysis_queue
+                 SET status = 'completed',
+                     last_error = NULL,
+                     updated_at = now()
+                 WHERE id = $1",
+                &[&job.id],
+            )?;
+            tx.commit()
+        })
+    }
+
+    fn fail_analysis_job(
+        &self,
+        job_id: i64,
+        error_message: &str,
+        retry: bool,
+    ) -> Result<(), CrawlerError> {
+        let status = if retry { "pending" } else { "failed" };
+        self.with_client(|client| {
+            client.execute(
+                "UPDATE analysis_queue
+                 SET status = $1,
+                     last_error = $2,
+                     updated_at = now()
+                 WHERE id = $3",
+                &[&status, &error_message, &job_id],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn analysis_queue_depth(&self) -> Result<Vec<(String, i64)>, CrawlerError> {
+        let rows = self.with_client(|client| {
+            client.query(
+                "SELECT status, COUNT(*) FROM analysis_queue GROUP BY status",
+                &[],
+            )
+        })?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    fn latest_analysis_id(&self) -> Result<Option<i64>, CrawlerError> {
+        self.with_client(|client| {
+            Ok(client
+                .query_one("SELECT MAX(id) FROM sentiment_analyses", &[])?
+                .get(0))
+        })
+    }
+
+    fn release_analysis_job(&self, job_id: i64) -> Result<(), CrawlerError> {
+        self.with_client(|client| {
+            client.execute(
+                "UPDATE analysis_queue
+                 SET status = 'pending',
+                     attempt_count = GREATEST(COALESCE(attempt_count, 1) - 1, 0),
+                     updated_at = now()
+                 WHERE id = $1",
+                &[&job_id],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn load_analysis_scores(&self) -> Result<Vec<AnalysisScoreRow>, CrawlerError> {
+        let rows = self.with_client(|client| {
+            client.query(
+                "SELECT t.twitter_user_id, sa.tweet_id, sa.llm_model_id, t.tweet_timestamp, sa.emotion_scores
+                 FROM sentiment_analyses sa
+                 JOIN tweets t ON t.id = sa.tweet_id
+                 WHERE sa.is_current",
+                &[],
+            )
+        })?;
+
+        Ok(rows
+            .iter()
+            .map(|row| AnalysisScoreRow {
+                twitter_user_id: row.get(0),
+                tweet_id: row.get(1),
+                llm_model_id: row.get(2),
+                tweet_timestamp: row.get::<_, DateTime<Utc>>(3).to_rfc3339(),
+                emotion_scores: row.get(4),
+            })
+            .collect())
+    }
+
+    fn replace_aggregations(
+        &self,
+        user_rows: &[UserAggregation],
+        global_rows: &[GlobalAggregation],
+    ) -> Result<(), CrawlerError> {
+        self.with_client(|client| {
+            let mut tx = client.transaction()?;
+            tx.batch_execute("DELETE FROM user_aggregations; DELETE FROM global_aggregations")?;
+
+            let user_stmt = tx.prepare(
+                "INSERT INTO user_aggregations
+                 (twitter_user_id, llm_model_id, time_bucket, bucket_start_date,
+                  emotion_averages, emotion_medians, emotion_modes, tweet_count)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            )?;
+            for row in user_rows {
+                tx.execute(
+                    &user_stmt,
+                    &[
+                        &row.twitter_user_id,
+                        &row.llm_model_id,
+                        &row.time_bucket,
+                        &row.bucket_start_date,
+                        &row.emotion_averages,
+                        &row.emotion_medians,
+                        &row.emotion_modes,
+                        &row.tweet_count,
+                    ],
+                )?;
+            }
+
+            let global_stmt = tx.prepare(
+                "INSERT INTO global_aggregations
+                 (llm_model_id, time_bucket, bucket_start_date, emotion_averages, gauge_values)
+                 VALUES ($1, $2, $3, $4, $5)",
+            )?;
+            for row in global_rows {
+                tx.execute(
+                    &global_stmt,
+                    &[
+                        &row.llm_model_id,
+                        &row.time_bucket,
+                        &row.bucket_start_date,
+                        &row.emotion_averages,
+                        &row.gauge_values,
+                    ],
+                )?;
+            }
+
+            tx.commit()
+        })
+    }
+
+    fn prune_batch(
+        &self,
+        target: PruneTarget,
+        cutoff: DateTime<Utc>,
+        batch_size: i64,
+    ) -> Result<u64, CrawlerError> {
+        // Each statement is its own transaction; deleted tweets take their
+        // analyses and jobs with them through `ON DELETE CASCADE`.
+        let sql = match target {
+            PruneTarget::Tweets => {
+                "DELETE FROM tweets WHERE id IN (
+                     SELECT id FROM tweets
+                     WHERE tweet_timestamp < $1
+                     ORDER BY id LIMIT $2
+                 )"
+            }
+            PruneTarget::ResolvedApiErrors => {
+                "DELETE FROM api_errors WHERE id IN (
+                     SELECT id FROM api_errors
+                     WHERE resolved AND occurred_at < $1
+                     ORDER BY id LIMIT $2
+                 )"
+            }
+            PruneTarget::RunErrorDetails => {
+                "UPDATE crawler_runs SET error_details = NULL WHERE id IN (
+                     SELECT id FROM crawler_runs
+                     WHERE error_details IS NOT NULL AND completed_at < $1
+                     ORDER BY id LIMIT $2
+                 )"
+            }
+            PruneTarget::CompletedJobs => {
+                "DELETE FROM analysis_queue WHERE id IN (
+                     SELECT id FROM analysis_queue
+                     WHERE status = 'completed' AND updated_at < $1
+                     ORDER BY id LIMIT $2
+                 )"
+            }
+        };
+        self.with_client(|client| client.execute(sql, &[&cutoff, &batch_size]))
+    }
+
+    fn database_size_bytes(&self) -> Result<i64, CrawlerError> {
+        self.with_client(|client| {
+            let row = client.query_one("SELECT pg_database_size(current_database())", &[])?;
+            Ok(row.get(0))
+        })
+    }
+
+    fn vacuum(&self, mode: VacuumMode) -> Result<(), CrawlerError> {
+        let sql = match mode {
+            VacuumMode::Off => return Ok(()),
+            VacuumMode::Incremental => "VACUUM (ANALYZE)",
+            VacuumMode::Full => "VACUUM (FULL, ANALYZE)",
+        };
+        // VACUUM cannot run inside a transaction block, so one table per statement.
+        self.with_client(|client| {
+            for table in PRUNED_TABLES {
+                client.batch_execute(&format!("{sql} {table}"))?;
+            }
+            Ok(())
+        })
+    }
+
+    #[cfg(test)]
+    fn execute_sql(&self, sql: &str) -> Result<(), CrawlerError> {
+        self.with_client(|client| client.batch_execute(sql))
+    }
+
+    #[cfg(test)]
+    fn query_text(&self, sql: &str) -> Result<Option<String>, CrawlerError> {
+        self.with_client(|client| {
+            Ok(client
+                .query_opt(sql, &[])?
+                .and_then(|row| row.get::<_, Option<String>>(0)))
+        })
+    }
+}
+
+/// Run `f` on a short-lived thread outside any tokio runtime
+///
+/// The synchronous client blocks on its own runtime, which tokio refuses to do
+/// on a thread that is driving async tasks (including the pipeline's
+/// `LocalSet`).
+fn off_runtime<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| match scope.spawn(f).join() {
+        Ok(value) => value,
+        Err(panic) => std::panic::resume_unwind(panic),
+    })
+}
+
+fn enqueue_jobs(
+    client: &mut impl GenericClient,
+    tweet_id: i64,
+    enabled_model_ids: &[i64],
+) -> Result<u64, postgres::Error> {
+    if enabled_model_ids.is_empty() {
+        return enqueue_job(client, tweet_id, None, false);
+    }
+    let mut jobs_enqueued = 0_u64;
+    for model_id in enabled_model_ids {
+        jobs_enqueued += enqueue_job(client, tweet_id, Some(*model_id), false)?;
+    }
+    Ok(jobs_enqueued)
+}
+
+/// With `reanalyze`, a completed or failed job for the same tweet and model
+/// goes back to `pending` instead of being skipped
+fn enqueue_job(
+    client: &mut impl GenericClient,
+    tweet_id: i64,
+    llm_model_id: Option<i64>,
+    reanalyze: bool,
+) -> Result<u64, postgres::Error> {
+    let sql = if reanalyze {
+        "INSERT INTO analysis_queue (tweet_id, llm_model_id)
+         VALUES ($1, $2)
+         ON CONFLICT (tweet_id, llm_model_id) DO UPDATE
+         SET status = 'pending',
+             attempt_count = 0,
+             last_error = NULL,
+             updated_at = now()
+         WHERE analysis_queue.status IN ('completed', 'failed')"
+    } else {
+        "INSERT INTO analysis_queue (tweet_id, llm_model_id)
+         VALUES ($1, $2)
+         ON CONFLICT DO NOTHING"
+    };
+    let changes = client.execute(sql, &[&tweet_id, &llm_model_id])?;
+    if changes > 0 {
+        telemetry::record_job_enqueued(llm_model_id);
+    }
+    Ok(changes)
+}
+
+/// Tables pruning deletes from, vacuumed afterwards
+const PRUNED_TABLES: &[&str] = &[
+    "tweets",
+    "sentiment_analyses",
+    "analysis_queue",
+    "api_errors",
+    "crawler_runs",
+];
+
+/// Tweets a reanalysis request covers, bound as `$1`..`$4`
+const REANALYSIS_SCOPE: &str = "($1::BIGINT IS NULL OR t.id = $1)
+     AND ($2::BIGINT IS NULL OR t.twitter_user_id = $2)
+     AND ($3::TIMESTAMPTZ IS NULL OR t.tweet_timestamp >= $3)
+     AND ($4::TIMESTAMPTZ IS NULL OR t.tweet_timestamp < $4)";
+
+/// Narrows tweet `t` and model `m.id` pairs to `$6` (only failed jobs) and
+/// `$7` (current analysis has no score for this emotion)
+const REANALYSIS_PAIR_FILTER: &str = "(NOT $6::BOOLEAN OR EXISTS (
+         SELECT 1 FROM analysis_queue q
+         WHERE q.tweet_id = t.id AND q.llm_model_id IS NOT DISTINCT FROM m.id
+           AND q.status = 'failed'
+     ))
+     AND ($7::TEXT IS NULL OR NOT EXISTS (
+         SELECT 1 FROM sentiment_analyses s
+         WHERE s.tweet_id = t.id AND s.llm_model_id IS NOT DISTINCT FROM m.id AND s.is_current
+           AND (s.emotion_scores::jsonb ->> $7) IS NOT NULL
+     ))";
+
+/// Tweet id and user id a reanalysis request is scoped to
+fn scope_ids(request: &ReanalysisRequest) -> (Option<i64>, Option<i64>) {
+    (
+        storage::reanalysis_tweet(request),
+        storage::reanalysis_user(request),
+    )
+}
+
+/// `YYYY-MM-DD HH:MM:SS` in UTC, matching SQLite's `datetime('now')` columns
+fn sql_time(value: DateTime<Utc>) -> String {
+    value.format("%Y-%m-%d %H:%M:%S").to_string()
+}
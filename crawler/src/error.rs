@@ -18,10 +18,22 @@ pub enum CrawlerError {
     #[error("Database error: {0}")]
     Database(#[from] rusqlite::Error),
 
+    #[error("Database error: {0}")]
+    Postgres(#[from] postgres::Error),
+
+    #[error("Database pool error: {0}")]
+    Pool(String),
+
     #[error("LLM inference error: {0}")]
     #[allow(dead_code)]
     LlmInference(String),
 
+    #[error("Backup error: {0}")]
+    Backup(String),
+
+    #[error("Crawler lock unavailable: {0}")]
+    Locked(String),
+
     #[error("Configuration error: {0}")]
     Config(String),
 
@@ -0,0 +1,94 @@
+//! Advisory lock that keeps a restore from replacing a database in use
+//!
+//! Every crawler process using a SQLite file holds a shared lock on
+//! `{database}.lock` for as long as its connection pool lives, so any number
+//! of daemons and one-shot commands can run side by side. A restore needs the
+//! lock exclusively and therefore only proceeds once they have all exited.
+//! The lock is released by the operating system when the process dies, so a
+//! crash never leaves it stale.
+
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use fs2::FileExt;
+
+use crate::error::CrawlerError;
+
+pub struct CrawlerLock {
+    _file: File,
+}
+
+impl CrawlerLock {
+    /// Join the crawler processes using `database_path`; fails while a restore holds the lock
+    pub fn shared(database_path: &Path) -> Result<Self, CrawlerError> {
+        let path = lock_path(database_path);
+        let file = open(&path)?;
+        file.try_lock_shared().map_err(|_| {
+            CrawlerError::Locked(format!(
+                "{} is held exclusively; a restore is replacing the database",
+                path.display()
+            ))
+        })?;
+        Ok(Self { _file: file })
+    }
+
+    /// Take the lock for a restore; fails while any crawler process holds it
+    pub fn exclusive(database_path: &Path) -> Result<Self, CrawlerError> {
+        let path = lock_path(database_path);
+        let file = open(&path)?;
+        file.try_lock_exclusive().map_err(|_| {
+            CrawlerError::Locked(format!(
+                "{} is held; stop every crawler process using the database first",
+                path.display()
+            ))
+        })?;
+        Ok(Self { _file: file })
+    }
+}
+
+pub fn lock_path(database_path: &Path) -> PathBuf {
+    let mut path = database_path.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+fn open(path: &Path) -> Result<File, CrawlerError> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(|error| {
+            CrawlerError::Locked(format!("Could not open {}: {error}", path.display()))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_holders_exclude_a_restore() -> Result<(), CrawlerError> {
+        let database = std::env::temp_dir().join(format!("crawler-lock-{}.db", std::process::id()));
+
+        let daemon = CrawlerLock::shared(&database)?;
+        let command = CrawlerLock::shared(&database)?;
+        assert!(matches!(
+            CrawlerLock::exclusive(&database),
+            Err(CrawlerError::Locked(_))
+        ));
+
+        drop(daemon);
+        drop(command);
+        let restore = CrawlerLock::exclusive(&database)?;
+        assert!(matches!(
+            CrawlerLock::shared(&database),
+            Err(CrawlerError::Locked(_))
+        ));
+
+        drop(restore);
+        let _ = std::fs::remove_file(lock_path(&database));
+        Ok(())
+    }
+}
@@ -3,443 +3,476 @@
 //! A Rust-based crawler that fetches tweets from Twitter/X and analyzes
 //! them for sentiment using configurable LLM models.
 
+mod aggregation;
+mod analysis;
+mod backup;
+mod cli;
 mod config;
+mod crawl;
 mod db;
+mod db_pool;
+mod db_postgres;
 mod error;
+mod lock;
+mod migrations;
 mod models;
+mod pipeline;
 mod rate_limit;
+mod restore;
+mod retention;
+mod s3;
+mod schedule;
+mod schema_compat;
+mod server;
+mod shutdown;
+mod status;
+mod storage;
+mod telemetry;
 mod twitter_api;
 
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
-};
+use std::process::ExitCode;
+use std::sync::Arc;
 
-use chrono::{Duration, Utc};
-use tokio::sync::{Mutex, Notify};
-use tracing::{info, warn};
+use chrono::{Duration, NaiveDate, NaiveTime, Utc};
+use clap::Parser;
+use tracing::{error, info};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use config::Config;
-use db::{ApiErrorDetail, Database};
-use error::CrawlerError;
-use models::TwitterApiError;
-use rate_limit::build_rate_limiter;
-use twitter_api::TwitterApiClient;
-
-/// Application state for singleton pattern
-struct AppState {
-    is_running: bool,
-}
-
-impl AppState {
-    fn new() -> Self {
-        Self { is_running: false }
-    }
-}
+use aggregation::recompute_aggregations;
+use analysis::{run_analysis_worker, AnalysisOptions, HuggingFaceAnalyzer};
+use cli::{Cli, Command, ConfigCommand, LogFormat, Outcome};
+use config::{Config, Layers};
+use crawl::{run_crawl_cycle, CrawlOptions, CrawlReport};
+use db_pool::DbPool;
+use pipeline::run_pipeline;
+use server::ServerState;
+use shutdown::{spawn_signal_handler, Shutdown};
+use status::StatusBoard;
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    // Initialize logging
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "twitter_feels_crawler=info".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
-    info!("Twitter Feels Crawler starting...");
-
-    // Load configuration
+async fn main() -> ExitCode {
+    // Load .env before parsing so LOG_FORMAT can come from it
     dotenvy::dotenv().ok();
-    let config = Config::from_env()?;
+    let cli = Cli::parse();
 
-    info!("Configuration loaded:");
-    info!("  Database: {}", config.database_url);
-    info!("  Crawl interval: {} hours", config.crawl_interval_hours);
-    info!("  History depth: {} days", config.history_depth_days);
-
-    // Initialize state with singleton pattern
-    let state = Arc::new(Mutex::new(AppState::new()));
-    let shutdown = Arc::new(AtomicBool::new(false));
-    let shutdown_notify = Arc::new(Notify::new());
+    // Initialize logging
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "twitter_feels_crawler=info".into());
+    let registry = tracing_subscriber::registry().with(filter);
+    match cli.log_format {
+        LogFormat::Text => registry.with(tracing_subscriber::fmt::layer()).init(),
+        LogFormat::Json => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true),
+            )
+            .init(),
+    }
 
-    {
-        let shutdown = shutdown.clone();
-        let shutdown_notify = shutdown_notify.clone();
-        tokio::spawn(async move {
-            if tokio::signal::ctrl_c().await.is_ok() {
-                shutdown.store(true, Ordering::SeqCst);
-                shutdown_notify.notify_waiters();
+    match run(cli).await {
+        Ok(outcome) => outcome.exit_code(),
+        Err(error) => {
+            error!("{error:#}");
+            if error.is::<config::InvalidConfig>() {
+                Outcome::InvalidConfig.exit_code()
+            } else {
+                Outcome::Failed.exit_code()
             }
-        });
+        }
     }
+}
 
-    // Main crawler loop
-    loop {
-        if shutdown.load(Ordering::SeqCst) {
-            info!("Shutdown requested, stopping crawler loop.");
-            break;
-        }
+async fn run(cli: Cli) -> anyhow::Result<Outcome> {
+    info!("Twitter Feels Crawler starting...");
 
-        {
-            let mut state_guard = state.lock().await;
-            if state_guard.is_running {
-                warn!("Crawler already running, skipping this cycle");
-            } else {
-                state_guard.is_running = true;
-                drop(state_guard);
+    let command = cli.command.unwrap_or(Command::Daemon);
+    if let Command::Config {
+        command: ConfigCommand::Check,
+    } = command
+    {
+        return check_config(cli.config.as_deref()).await;
+    }
 
-                // Run crawler
-                if let Err(e) = run_crawl_cycle(&config, &shutdown).await {
-                    warn!("Crawl cycle failed: {}", e);
-                }
+    // Load configuration
+    let config = Config::load(cli.config.as_deref())?;
 
-                // Mark as not running
-                let mut state_guard = state.lock().await;
-                state_guard.is_running = false;
+    let shutdown = Arc::new(Shutdown::new());
+    spawn_signal_handler(
+        shutdown.clone(),
+        std::time::Duration::from_secs(config.shutdown_drain_secs),
+    );
+    let board = Arc::new(StatusBoard::new());
+
+    match command {
+        Command::Crawl { user } => {
+            config.require_twitter_token()?;
+            let options = CrawlOptions {
+                username: user,
+                ..CrawlOptions::default()
+            };
+            let pool = open_database(&config).await?;
+            let config = config.refresh(&pool).await.unwrap_or(config);
+            check_models(&config, &pool).await?;
+            let report = run_crawl_cycle(&pool, &config, &options, &shutdown, &board).await?;
+            Ok(crawl_outcome(&report))
+        }
+        Command::Backfill { from, to, user } => {
+            config.require_twitter_token()?;
+            if from > to {
+                return Err(anyhow::anyhow!("--from must not be after --to"));
             }
+            let options = CrawlOptions {
+                username: user,
+                window: Some((day_start(from), day_start(to) + Duration::days(1))),
+                skip_reanalysis: true,
+            };
+            let pool = open_database(&config).await?;
+            let config = config.refresh(&pool).await.unwrap_or(config);
+            check_models(&config, &pool).await?;
+            let report = run_crawl_cycle(&pool, &config, &options, &shutdown, &board).await?;
+            Ok(crawl_outcome(&report))
         }
-
-        if shutdown.load(Ordering::SeqCst) {
-            info!("Shutdown requested, skipping sleep.");
-            break;
+        Command::Analyze { limit, model } => {
+            let pool = open_database(&config).await?;
+            let analyzer = HuggingFaceAnalyzer::new(config.huggingface_token.clone())?;
+            let options = AnalysisOptions {
+                max_jobs: limit,
+                llm_model_id: model,
+                ..AnalysisOptions::default()
+            };
+            let report = run_analysis_worker(&pool, &analyzer, &options, &shutdown).await?;
+            Ok(if report.failed > 0 {
+                Outcome::CompletedWithErrors
+            } else {
+                Outcome::Success
+            })
         }
-
-        // Wait for next cycle
-        info!(
-            "Waiting {} hours until next crawl cycle...",
-            config.crawl_interval_hours
-        );
-        tokio::select! {
-            _ = tokio::time::sleep(tokio::time::Duration::from_secs(
-                config.crawl_interval_hours as u64 * 3600,
-            )) => {}
-            _ = shutdown_notify.notified() => {
-                info!("Shutdown requested, stopping crawler loop.");
-                break;
+        Command::Aggregate => {
+            let pool = open_database(&config).await?;
+            pool.run(|database| recompute_aggregations(database)).await?;
+            Ok(Outcome::Success)
+        }
+        Command::Prune => {
+            let pool = open_database(&config).await?;
+            let policy = config.retention.clone();
+            let prune_shutdown = shutdown.clone();
+            let report = pool
+                .run(move |database| retention::prune(database, &policy, &prune_shutdown))
+                .await?;
+            Ok(if report.interrupted {
+                Outcome::Cancelled
+            } else {
+                Outcome::Success
+            })
+        }
+        Command::Backup => {
+            let Some(path) = storage::sqlite_path(&config.database_url) else {
+                return Err(anyhow::anyhow!(
+                    "Backups need a SQLite database file; back up PostgreSQL with pg_dump"
+                ));
+            };
+            let pool = open_database(&config).await?;
+            let settings = backup::load_settings(&pool, &config.backup)
+                .await?
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "S3 backup is not configured; set BACKUP_S3_BUCKET or enable it in the admin settings"
+                    )
+                })?;
+            backup::run_backup(
+                &path,
+                config.sqlite_busy_timeout(),
+                &settings,
+                "manual",
+                &shutdown,
+            )
+            .await?;
+            Ok(Outcome::Success)
+        }
+        Command::Restore { backup, latest } => {
+            let Some(path) = storage::sqlite_path(&config.database_url) else {
+                return Err(anyhow::anyhow!(
+                    "Restores need a SQLite database file; restore PostgreSQL with pg_restore"
+                ));
+            };
+            let settings = restore::load_settings(&path, &config.backup)?.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "S3 backup is not configured; set BACKUP_S3_BUCKET or enable it in the admin settings"
+                )
+            })?;
+            let snapshot = match (&backup, latest) {
+                (Some(name), _) => restore::Snapshot::Named(name),
+                (None, true) => restore::Snapshot::Latest,
+                (None, false) => return print_backups(&settings).await,
+            };
+            restore::restore(&path, config.sqlite_busy_timeout(), &settings, snapshot).await?;
+            Ok(Outcome::Success)
+        }
+        Command::Status => {
+            let pool = open_database_read_only(&config).await?;
+            print_status(&pool).await
+        }
+        Command::History { user, limit } => {
+            let pool = open_database_read_only(&config).await?;
+            print_history(&pool, user, limit).await
+        }
+        Command::Config { .. } => unreachable!("handled before the configuration is loaded"),
+        Command::Daemon => {
+            config.require_twitter_token()?;
+            let pool = open_database(&config).await?;
+            let config = config.refresh(&pool).await.unwrap_or(config);
+            check_models(&config, &pool).await?;
+
+            // The server outlives the drain so probes keep answering until the pipeline stops.
+            let server = config.status_addr.clone().map(|addr| {
+                let state = ServerState {
+                    metrics: telemetry::install()
+                        .map_err(|error| error!("Metrics disabled: {error:#}"))
+                        .ok(),
+                    board: board.clone(),
+                    shutdown: shutdown.clone(),
+                    database: pool.clone(),
+                    token_configured: !config.twitter_bearer_token.trim().is_empty(),
+                    stall_timeout: std::time::Duration::from_secs(config.stall_timeout_minutes * 60),
+                };
+                tokio::spawn(async move {
+                    if let Err(error) = server::serve(&addr, state).await {
+                        error!("Status server failed: {error:#}");
+                    }
+                })
+            });
+
+            let result = run_pipeline(config, pool, shutdown, board).await;
+            if let Some(server) = server {
+                server.abort();
             }
+            result?;
+            Ok(Outcome::Success)
         }
     }
-
-    Ok(())
 }
 
-async fn run_crawl_cycle(config: &Config, shutdown: &AtomicBool) -> anyhow::Result<()> {
-    info!("Starting crawl cycle...");
-
-    let database = Database::new(&config.database_url)?;
-    database.init_schema()?;
-
-    let run_id = database.create_crawler_run()?;
-    let mut error_details: Vec<ApiErrorDetail> = Vec::new();
-    let mut tweets_fetched = 0_u64;
-    let mut tweets_queued = 0_u64;
-    let mut status = "completed";
-
-    let rate_limiter = build_rate_limiter(config.rate_limit_per_15min)?;
-    let twitter_client = TwitterApiClient::new(config.twitter_bearer_token.clone(), rate_limiter)?;
-
-    let cycle_result = perform_crawl(
-        &database,
-        &twitter_client,
-        config,
-        shutdown,
-        &mut error_details,
-        &mut tweets_fetched,
-        &mut tweets_queued,
-        &mut status,
+/// Open the connection pool and bring the schema up to date
+async fn open_database(config: &Config) -> anyhow::Result<DbPool> {
+    let pool = DbPool::open(
+        &config.database_url,
+        config.sqlite_busy_timeout(),
+        config.db_pool_size,
     )
-    .await;
-
-    if let Err(error) = &cycle_result {
-        status = "failed";
-        record_error(
-            &database,
-            &mut error_details,
-            error_kind(error),
-            format!("Crawler cycle error: {error}"),
-            None,
-            None,
-        );
-    }
-
-    database.complete_crawler_run(
-        run_id,
-        status,
-        tweets_fetched,
-        tweets_queued,
-        &error_details,
-    )?;
-
-    if let Err(error) = cycle_result {
-        return Err(error.into());
-    }
-
-    Ok(())
+    .await?;
+    pool.run(|database| database.init_schema()).await?;
+    Ok(pool)
 }
 
-async fn perform_crawl(
-    database: &Database,
-    twitter_client: &TwitterApiClient,
-    config: &Config,
-    shutdown: &AtomicBool,
-    error_details: &mut Vec<ApiErrorDetail>,
-    tweets_fetched: &mut u64,
-    tweets_queued: &mut u64,
-    status: &mut &str,
-) -> Result<(), CrawlerError> {
-    let enabled_models = database.get_enabled_model_ids()?;
-    process_reanalysis_requests(database, &enabled_models, shutdown, error_details)?;
-
-    let active_users = database.load_active_users()?;
-    if active_users.is_empty() {
-        info!("No active users to crawl.");
-        return Ok(());
-    }
-
-    let usernames: Vec<String> = active_users
-        .iter()
-        .map(|user| user.username.clone())
-        .collect();
-
-    let users_response = twitter_client.fetch_users_by_usernames(&usernames).await?;
-    if let Some(errors) = users_response.errors {
-        for api_error in errors {
-            record_twitter_api_error(
-                database,
-                error_details,
-                "api_change",
-                &api_error,
-                Some("/2/users/by"),
-            );
-        }
+/// Fail with every unknown `DEFAULT_MODEL` and per-user model, like any other
+/// invalid setting
+async fn check_models(config: &Config, pool: &DbPool) -> anyhow::Result<()> {
+    let models = pool.run(|database| database.load_models()).await?;
+    let invalid = config.check_models(&models);
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(config::InvalidConfig(invalid).into())
     }
+}
 
-    let mut api_users = std::collections::HashMap::new();
-    if let Some(users) = users_response.data {
-        for user in users {
-            api_users.insert(user.username.clone(), user);
+/// Open the connection pool for a command that only reads, checking the
+/// schema without migrating it
+async fn open_database_read_only(config: &Config) -> anyhow::Result<DbPool> {
+    if let Some(path) = storage::sqlite_path(&config.database_url) {
+        if !path.is_file() {
+            return Err(anyhow::anyhow!("{} does not exist yet", path.display()));
         }
     }
+    let pool = DbPool::open(&config.database_url, config.sqlite_busy_timeout(), 1).await?;
+    pool.run(|database| database.check_schema())
+        .await
+        .map_err(|error| anyhow::anyhow!("{error}; run `crawl` or `daemon` once to migrate it"))?;
+    Ok(pool)
+}
 
-    let base_start_time = Utc::now() - Duration::days(config.history_depth_days as i64);
-
-    for tracked_user in active_users {
-        if shutdown.load(Ordering::SeqCst) {
-            *status = "failed";
-            record_error(
-                database,
-                error_details,
-                "other",
-                "Shutdown requested".to_string(),
-                None,
-                None,
-            );
-            break;
-        }
-
-        let Some(api_user) = api_users.get(&tracked_user.username) else {
-            record_error(
-                database,
-                error_details,
-                "api_change",
-                format!("Twitter user not found: @{}", tracked_user.username),
-                None,
-                Some("/2/users/by"),
-            );
-            continue;
-        };
-
-        database.update_user_from_api(
-            &tracked_user.username,
-            &api_user.id,
-            Some(&api_user.name),
-            api_user.profile_image_url.as_deref(),
-            api_user
-                .public_metrics
-                .as_ref()
-                .and_then(|metrics| metrics.followers_count),
-            api_user
-                .public_metrics
-                .as_ref()
-                .and_then(|metrics| metrics.following_count),
-        )?;
-
-        let start_time = match database.get_checkpoint(tracked_user.id)? {
-            Some(checkpoint) if checkpoint > base_start_time => checkpoint + Duration::seconds(1),
-            _ => base_start_time,
+/// `config check`: every setting with its source, then every invalid one
+///
+/// The database layer and `DEFAULT_MODEL` are only checked when the database
+/// already exists, so the check never creates one.
+async fn check_config(path: Option<&std::path::Path>) -> anyhow::Result<Outcome> {
+    let layers = Layers::load(path)?;
+    let (mut config, mut invalid) = Config::resolve(layers.clone());
+
+    let exists = storage::sqlite_path(&config.database_url).is_none_or(|path| path.is_file());
+    if !invalid.iter().any(|setting| setting.name == "DATABASE_URL") && exists {
+        let pool = DbPool::open(&config.database_url, config.sqlite_busy_timeout(), 1).await;
+        let stored = match pool {
+            Ok(pool) => pool
+                .run(|database| {
+                    database.check_schema()?;
+                    Ok((
+                        database.load_stored_configuration(config::CONFIGURATION_KEY)?,
+                        database.load_models()?,
+                    ))
+                })
+                .await
+                .map_err(|error| format!("could not read settings and models: {error}")),
+            Err(error) => Err(format!("cannot be opened: {error}")),
         };
-
-        let fetched = twitter_client
-            .fetch_user_tweets(&api_user.id, start_time)
-            .await;
-
-        let fetch_result = match fetched {
-            Ok(result) => result,
-            Err(error) => {
-                record_error(
-                    database,
-                    error_details,
-                    error_kind(&error),
-                    format!("Failed fetching tweets for @{}", tracked_user.username),
-                    None,
-                    Some("/2/users/:id/tweets"),
-                );
-                if should_abort_on_error(&error) {
-                    *status = "failed";
-                    return Err(error);
-                }
-                continue;
+        match stored {
+            Ok((stored, models)) => {
+                (config, invalid) = Config::resolve(layers.with_database(stored.as_ref()));
+                invalid.extend(config.check_models(&models));
             }
-        };
-
-        for api_error in fetch_result.errors {
-            record_twitter_api_error(
-                database,
-                error_details,
-                "api_change",
-                &api_error,
-                Some("/2/users/:id/tweets"),
-            );
+            Err(reason) => invalid.push(config::InvalidSetting {
+                name: "DATABASE_URL".to_string(),
+                value: storage::redacted_url(&config.database_url),
+                source: layers.source("DATABASE_URL"),
+                reason,
+            }),
         }
+    } else if !exists {
+        println!("(the database does not exist yet; admin settings and DEFAULT_MODEL not checked)");
+    }
 
-        let (inserted, enqueued, latest) = database.insert_tweets_and_enqueue(
-            tracked_user.id,
-            &fetch_result.tweets,
-            &enabled_models,
-        )?;
-        *tweets_fetched += inserted;
-        *tweets_queued += enqueued;
-
-        if let Some(latest_timestamp) = latest {
-            database.set_checkpoint(tracked_user.id, latest_timestamp)?;
+    for (name, value, source) in config.settings() {
+        match value {
+            Some(value) => println!("{name} = {value} ({source})"),
+            None => println!("{name} unset ({source})"),
         }
     }
-
-    info!(
-        "Crawl cycle complete: {} tweets fetched, {} analysis jobs queued",
-        tweets_fetched, tweets_queued
-    );
-
-    Ok(())
+    for (username, overrides) in &config.users {
+        println!("users.{username}: {} (config file)", overrides.describe());
+    }
+    println!();
+    if invalid.is_empty() {
+        println!("Configuration is valid");
+        return Ok(Outcome::Success);
+    }
+    println!("{}", config::InvalidConfig(invalid));
+    Ok(Outcome::Failed)
 }
 
-fn process_reanalysis_requests(
-    database: &Database,
-    enabled_models: &[i64],
-    shutdown: &AtomicBool,
-    error_details: &mut Vec<ApiErrorDetail>,
-) -> Result<(), CrawlerError> {
-    let requests = database.load_pending_reanalysis_requests(25)?;
-    for request in requests {
-        if shutdown.load(Ordering::SeqCst) {
-            record_error(
-                database,
-                error_details,
-                "other",
-                "Shutdown requested".to_string(),
-                None,
-                None,
-            );
-            break;
-        }
-
-        database.mark_reanalysis_processing(request.id)?;
+async fn print_status(pool: &DbPool) -> anyhow::Result<Outcome> {
+    let (version, latest_known) = pool
+        .run(|database| Ok((database.schema_version()?, database.latest_schema_version())))
+        .await?;
+    println!(
+        "Schema version {} (latest known {})",
+        version.unwrap_or(0),
+        latest_known
+    );
 
-        let enqueue_result = match request.request_type.as_str() {
-            "tweet" => {
-                let tweet_id = request.tweet_id.ok_or_else(|| {
-                    CrawlerError::Config("Missing tweet_id for reanalysis request".to_string())
-                })?;
-                database.enqueue_reanalysis_for_tweet(tweet_id, enabled_models)
-            }
-            "user" => {
-                let user_id = request.twitter_user_id.ok_or_else(|| {
-                    CrawlerError::Config("Missing twitter_user_id for reanalysis request".to_string())
-                })?;
-                database.enqueue_reanalysis_for_user(user_id, enabled_models)
-            }
-            "all" => database.enqueue_reanalysis_for_all(enabled_models),
-            _ => Err(CrawlerError::Config(format!(
-                "Unknown reanalysis request type: {}",
-                request.request_type
-            ))),
-        };
+    let Some(run) = pool.run(|database| database.latest_crawler_run()).await? else {
+        println!("No crawler runs recorded yet.");
+        return Ok(Outcome::NothingToReport);
+    };
 
-        if let Err(error) = enqueue_result {
-            record_error(
-                database,
-                error_details,
-                error_kind(&error),
-                format!("Failed reanalysis request {}", request.id),
-                None,
-                None,
+    println!("Run #{}: {}", run.id, run.status);
+    println!("  Started:        {}", run.started_at.as_deref().unwrap_or("-"));
+    println!("  Completed:      {}", run.completed_at.as_deref().unwrap_or("-"));
+    println!("  Tweets fetched: {}", run.tweets_fetched);
+    println!("  Jobs queued:    {}", run.tweets_analyzed);
+    println!("  Errors:         {}", run.errors_count);
+
+    let run_id = run.id;
+    let problems: Vec<_> = pool
+        .run(move |database| database.run_user_outcomes(run_id))
+        .await?
+        .into_iter()
+        .filter(|user| user.status != "completed")
+        .collect();
+    if !problems.is_empty() {
+        println!("  Users not completed:");
+        for user in problems {
+            println!(
+                "    @{}: {} {}",
+                user.username,
+                user.status,
+                user.error_message.as_deref().unwrap_or("")
             );
         }
-
-        database.mark_reanalysis_completed(request.id)?;
     }
 
-    Ok(())
+    Ok(match run.status.as_str() {
+        "failed" => Outcome::Failed,
+        "cancelled" => Outcome::Cancelled,
+        _ if run.errors_count > 0 => Outcome::CompletedWithErrors,
+        _ => Outcome::Success,
+    })
 }
 
-fn record_twitter_api_error(
-    database: &Database,
-    error_details: &mut Vec<ApiErrorDetail>,
-    error_type: &str,
-    api_error: &TwitterApiError,
-    endpoint: Option<&str>,
-) {
-    let title = api_error.title.as_deref().unwrap_or("Twitter API error");
-    let detail = api_error.detail.as_deref().unwrap_or("Unknown error");
-    let message = format!("{title}: {detail}");
-    record_error(
-        database,
-        error_details,
-        error_type,
-        message,
-        api_error.type_.as_deref(),
-        endpoint,
-    );
+async fn print_backups(settings: &backup::BackupSettings) -> anyhow::Result<Outcome> {
+    let archives = restore::list(settings).await?;
+    if archives.is_empty() {
+        println!(
+            "No backups in s3://{}/{}",
+            settings.s3.bucket, settings.prefix
+        );
+        return Ok(Outcome::NothingToReport);
+    }
+    for archive in archives {
+        println!(
+            "{}  {} bytes  {}",
+            archive.key, archive.size, archive.last_modified
+        );
+    }
+    Ok(Outcome::Success)
 }
 
-fn record_error(
-    database: &Database,
-    error_details: &mut Vec<ApiErrorDetail>,
-    error_type: &str,
-    message: String,
-    code: Option<&str>,
-    endpoint: Option<&str>,
-) {
-    let detail = ApiErrorDetail {
-        error_type: error_type.to_string(),
-        message: message.clone(),
-        code: code.map(|value| value.to_string()),
-        endpoint: endpoint.map(|value| value.to_string()),
-        timestamp: Utc::now().to_rfc3339(),
+async fn print_history(pool: &DbPool, username: String, limit: i64) -> anyhow::Result<Outcome> {
+    let lookup = username.clone();
+    let history = pool
+        .run(move |database| match database.find_user_id(&lookup)? {
+            Some(user_id) => database.user_crawl_history(user_id, limit).map(Some),
+            None => Ok(None),
+        })
+        .await?;
+    let Some(history) = history else {
+        return Err(anyhow::anyhow!("@{username} is not a tracked user"));
     };
-    error_details.push(detail);
+    if history.is_empty() {
+        println!("No crawls recorded for @{username} yet.");
+        return Ok(Outcome::NothingToReport);
+    }
 
-    if let Err(error) = database.insert_api_error(error_type, &message, code, endpoint) {
-        warn!("Failed to record api error: {}", error);
+    for entry in &history {
+        println!(
+            "Run #{} {}: {} ({} tweets, {} pages, {} API calls, {}ms){}",
+            entry.run_id,
+            entry.completed_at.as_deref().unwrap_or("-"),
+            entry.status,
+            entry.tweets_fetched,
+            entry.pages_fetched,
+            entry.api_calls,
+            entry.duration_ms,
+            entry
+                .error_message
+                .as_deref()
+                .map(|message| format!(" - {message}"))
+                .unwrap_or_default()
+        );
     }
+
+    Ok(Outcome::Success)
 }
 
-fn error_kind(error: &CrawlerError) -> &'static str {
-    match error {
-        CrawlerError::RateLimitExceeded => "rate_limit",
-        CrawlerError::Authentication(_) => "auth",
-        CrawlerError::Network(_) => "network",
-        CrawlerError::TwitterApi(_) => "api_change",
-        _ => "other",
+fn crawl_outcome(report: &CrawlReport) -> Outcome {
+    info!(
+        "Run #{} finished with status {} ({} errors)",
+        report.run_id,
+        report.status,
+        report.error_details.len()
+    );
+    if report.status == "failed" {
+        Outcome::Failed
+    } else if report.status == "cancelled" {
+        Outcome::Cancelled
+    } else if !report.error_details.is_empty() {
+        Outcome::CompletedWithErrors
+    } else {
+        Outcome::Success
     }
 }
 
-fn should_abort_on_error(error: &CrawlerError) -> bool {
-    matches!(
-        error,
-        CrawlerError::RateLimitExceeded | CrawlerError::Authentication(_)
-    )
+fn day_start(date: NaiveDate) -> chrono::DateTime<Utc> {
+    date.and_time(NaiveTime::MIN).and_utc()
 }
@@ -0,0 +1,377 @@
+//! Versioned schema migrations owned by the crawler
+//!
+//! SQLite migrations live in `crawler/migrations/NNNN_name.sql` and PostgreSQL
+//! ones in `crawler/migrations/postgres/`. They are embedded at build time and
+//! applied in order, each in its own transaction. Applied versions are
+//! recorded in `schema_migrations` with a SHA-256 checksum of their SQL, so an
+//! edited migration or a database migrated by a newer crawler is refused
+//! instead of silently drifting.
+//!
+//! The Node backend creates the same tables from `schema.sql`, so migrations
+//! must tolerate objects that already exist: tables and indexes use
+//! `IF NOT EXISTS`, and `ALTER TABLE ... ADD COLUMN` statements are skipped when
+//! the column is already present. Statements are split on `;`, so migration
+//! files must not contain triggers or semicolons inside literals or comments.
+//! PostgreSQL migrations run as one batch and may use `ADD COLUMN IF NOT EXISTS`.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::error::CrawlerError;
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        sql: include_str!("../migrations/0001_baseline.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "fetch_progress_and_schedule",
+        sql: include_str!("../migrations/0002_fetch_progress_and_schedule.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "crawler_run_progress",
+        sql: include_str!("../migrations/0003_crawler_run_progress.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "crawler_run_users",
+        sql: include_str!("../migrations/0004_crawler_run_users.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "reanalysis_progress",
+        sql: include_str!("../migrations/0005_reanalysis_progress.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "analysis_history",
+        sql: include_str!("../migrations/0006_analysis_history.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "reanalysis_scope",
+        sql: include_str!("../migrations/0007_reanalysis_scope.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "reanalysis_failures",
+        sql: include_str!("../migrations/0008_reanalysis_failures.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "backups",
+        sql: include_str!("../migrations/0009_backups.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "reanalysis_leases",
+        sql: include_str!("../migrations/0010_reanalysis_leases.sql"),
+    },
+];
+
+pub const POSTGRES_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        sql: include_str!("../migrations/postgres/0001_baseline.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "reanalysis_progress",
+        sql: include_str!("../migrations/postgres/0002_reanalysis_progress.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "analysis_history",
+        sql: include_str!("../migrations/postgres/0003_analysis_history.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "reanalysis_scope",
+        sql: include_str!("../migrations/postgres/0004_reanalysis_scope.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "reanalysis_failures",
+        sql: include_str!("../migrations/postgres/0005_reanalysis_failures.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "reanalysis_leases",
+        sql: include_str!("../migrations/postgres/0006_reanalysis_leases.sql"),
+    },
+];
+
+/// Latest SQLite schema version this build knows about
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// Latest PostgreSQL schema version this build knows about
+pub fn latest_postgres_version() -> i64 {
+    POSTGRES_MIGRATIONS
+        .last()
+        .map_or(0, |migration| migration.version)
+}
+
+/// Verify applied migrations and apply pending ones; returns how many were applied
+pub fn migrate(conn: &Connection) -> Result<usize, CrawlerError> {
+    migrate_with(conn, MIGRATIONS)
+}
+
+fn migrate_with(conn: &Connection, migrations: &[Migration]) -> Result<usize, CrawlerError> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied_at TEXT DEFAULT (datetime('now'))
+        )",
+    )?;
+
+    let applied_latest: Option<i64> = conn
+        .query_row("SELECT MAX(version) FROM schema_migrations", [], |row| {
+            row.get(0)
+        })
+        .optional()?
+        .flatten();
+    check_not_newer(applied_latest, migrations)?;
+
+    let mut applied_count = 0;
+    for migration in migrations {
+        let stored: Option<String> = conn
+            .query_row(
+                "SELECT checksum FROM schema_migrations WHERE version = ?",
+                params![migration.version],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if !is_pending(migration, stored)? {
+            continue;
+        }
+
+        let expected = checksum(migration.sql);
+        let tx = conn.unchecked_transaction()?;
+        for statement in migration.sql.split(';') {
+            let statement = statement.trim();
+            if statement.lines().all(|line| {
+                let line = line.trim();
+                line.is_empty() || line.starts_with("--")
+            }) {
+                continue;
+            }
+            if let Some((table, column)) = added_column(statement) {
+                if column_exists(&tx, &table, &column)? {
+                    continue;
+                }
+            }
+            tx.execute_batch(statement)?;
+        }
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name, checksum) VALUES (?, ?, ?)",
+            params![migration.version, migration.name, expected],
+        )?;
+        tx.commit()?;
+
+        info!(
+            "Applied schema migration {:04}_{}",
+            migration.version, migration.name
+        );
+        applied_count += 1;
+    }
+
+    Ok(applied_count)
+}
+
+/// PostgreSQL counterpart of [`migrate`], applying [`POSTGRES_MIGRATIONS`]
+pub fn migrate_postgres(client: &mut postgres::Client) -> Result<usize, CrawlerError> {
+    client.batch_execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version BIGINT PRIMARY KEY,
+            name TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied_at TIMESTAMPTZ DEFAULT now()
+        )",
+    )?;
+
+    let applied_latest: Option<i64> = client
+        .query_one("SELECT MAX(version) FROM schema_migrations", &[])?
+        .get(0);
+    check_not_newer(applied_latest, POSTGRES_MIGRATIONS)?;
+
+    let mut applied_count = 0;
+    for migration in POSTGRES_MIGRATIONS {
+        let stored: Option<String> = client
+            .query_opt(
+                "SELECT checksum FROM schema_migrations WHERE version = $1",
+                &[&migration.version],
+            )?
+            .map(|row| row.get(0));
+        if !is_pending(migration, stored)? {
+            continue;
+        }
+
+        // DDL is transactional in PostgreSQL, so a failed migration leaves nothing behind.
+        let mut tx = client.transaction()?;
+        tx.batch_execute(migration.sql)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name, checksum) VALUES ($1, $2, $3)",
+            &[
+                &migration.version,
+                &migration.name,
+                &checksum(migration.sql),
+            ],
+        )?;
+        tx.commit()?;
+
+        info!(
+            "Applied PostgreSQL schema migration {:04}_{}",
+            migration.version, migration.name
+        );
+        applied_count += 1;
+    }
+
+    Ok(applied_count)
+}
+
+fn check_not_newer(
+    applied_latest: Option<i64>,
+    migrations: &[Migration],
+) -> Result<(), CrawlerError> {
+    let known_latest = migrations.last().map_or(0, |migration| migration.version);
+    match applied_latest {
+        Some(applied) if applied > known_latest => Err(CrawlerError::Config(format!(
+            "Database schema is at version {applied}, newer than the {known_latest} this crawler supports; upgrade the crawler"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Whether `migration` still needs applying, given the checksum recorded for it
+fn is_pending(migration: &Migration, stored: Option<String>) -> Result<bool, CrawlerError> {
+    match stored {
+        None => Ok(true),
+        Some(stored) if stored == checksum(migration.sql) => Ok(false),
+        Some(_) => Err(CrawlerError::Config(format!(
+            "Migration {:04}_{} was modified after it was applied (checksum mismatch)",
+            migration.version, migration.name
+        ))),
+    }
+}
+
+fn checksum(sql: &str) -> String {
+    Sha256::digest(sql.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// `(table, column)` for an `ALTER TABLE <table> ADD COLUMN <column> ...` statement
+fn added_column(statement: &str) -> Option<(String, String)> {
+    let tokens: Vec<&str> = statement
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("--"))
+        .flat_map(str::split_whitespace)
+        .collect();
+    match tokens.as_slice() {
+        [alter, table_kw, table, add, column_kw, column, ..]
+            if alter.eq_ignore_ascii_case("ALTER")
+                && table_kw.eq_ignore_ascii_case("TABLE")
+                && add.eq_ignore_ascii_case("ADD")
+                && column_kw.eq_ignore_ascii_case("COLUMN") =>
+        {
+            Some((table.to_string(), column.to_string()))
+        }
+        _ => None,
+    }
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool, CrawlerError> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let columns = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    for existing in columns {
+        if existing?.eq_ignore_ascii_case(column) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied_versions(conn: &Connection) -> Vec<i64> {
+        let mut stmt = conn
+            .prepare("SELECT version FROM schema_migrations ORDER BY version")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn migrates_empty_database_once() -> Result<(), CrawlerError> {
+        let conn = Connection::open_in_memory()?;
+        assert_eq!(migrate(&conn)?, MIGRATIONS.len());
+        assert_eq!(migrate(&conn)?, 0);
+        assert_eq!(applied_versions(&conn).last(), Some(&latest_version()));
+        assert!(column_exists(&conn, "crawler_runs", "users_total")?);
+        Ok(())
+    }
+
+    #[test]
+    fn adopts_database_with_columns_from_node_schema() -> Result<(), CrawlerError> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            "CREATE TABLE crawler_runs (id INTEGER PRIMARY KEY, status TEXT, users_total INTEGER)",
+        )?;
+        migrate(&conn)?;
+        assert!(column_exists(&conn, "crawler_runs", "users_completed")?);
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_newer_schema() -> Result<(), CrawlerError> {
+        let conn = Connection::open_in_memory()?;
+        migrate(&conn)?;
+        conn.execute(
+            "INSERT INTO schema_migrations (version, name, checksum) VALUES (?, 'future', '')",
+            params![latest_version() + 1],
+        )?;
+        assert!(matches!(migrate(&conn), Err(CrawlerError::Config(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_modified_migration() -> Result<(), CrawlerError> {
+        let conn = Connection::open_in_memory()?;
+        let original = [Migration {
+            version: 1,
+            name: "widgets",
+            sql: "CREATE TABLE widgets (id INTEGER PRIMARY KEY);",
+        }];
+        let edited = [Migration {
+            version: 1,
+            name: "widgets",
+            sql: "CREATE TABLE widgets (id INTEGER PRIMARY KEY, name TEXT);",
+        }];
+        migrate_with(&conn, &original)?;
+        assert!(matches!(
+            migrate_with(&conn, &edited),
+            Err(CrawlerError::Config(_))
+        ));
+        Ok(())
+    }
+}
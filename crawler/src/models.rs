@@ -1,7 +1,7 @@
 //! Data models for Twitter API and database mapping
 
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -89,6 +89,168 @@ pub struct ReanalysisRequest {
     pub request_type: String,
     pub tweet_id: Option<i64>,
     pub twitter_user_id: Option<i64>,
+    /// Last tweet id already enqueued by an interrupted `user`/`all` request
+    pub cursor_tweet_id: Option<i64>,
+    /// Tweets in scope, counted when processing first started
+    pub tweets_total: Option<i64>,
+    /// Only queue jobs for this model instead of every enabled one
+    pub llm_model_id: Option<i64>,
+    /// Tweets posted at or after this time
+    pub tweets_from: Option<DateTime<Utc>>,
+    /// Tweets posted before this time
+    pub tweets_to: Option<DateTime<Utc>>,
+    /// Only tweet/model pairs whose last job failed
+    pub only_failed: bool,
+    /// Only tweet/model pairs whose current analysis has no score for this emotion
+    pub missing_emotion: Option<String>,
+    /// Count the jobs the request would queue without queueing them
+    pub dry_run: bool,
+}
+
+/// What one chunk of a `user`/`all` reanalysis request enqueued
+#[derive(Debug, Clone, Copy)]
+pub struct ReanalysisChunk {
+    pub tweets: u64,
+    pub jobs_enqueued: u64,
+    /// Highest tweet id in the chunk; the next chunk starts after it
+    pub last_tweet_id: Option<i64>,
+}
+
+/// Where an interrupted timeline fetch should resume
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchProgress {
+    pub start_time: DateTime<Utc>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// Token for the next page still to fetch
+    pub pagination_token: String,
+    /// Newest tweet seen so far; becomes the checkpoint once the fetch completes
+    pub newest_timestamp: Option<DateTime<Utc>>,
+}
+
+/// Live counters written to `crawler_runs` while a cycle runs
+#[derive(Debug, Clone)]
+pub struct RunProgress {
+    pub users_total: i64,
+    pub users_completed: i64,
+    pub current_user: Option<String>,
+    pub tweets_fetched: i64,
+    pub jobs_queued: i64,
+    pub errors_count: i64,
+    pub eta: Option<DateTime<Utc>>,
+}
+
+/// How one tracked user fared during a crawl cycle
+#[derive(Debug, Clone)]
+pub struct UserCrawlOutcome {
+    /// `completed`, `failed`, `not_found` or `cancelled`
+    pub status: &'static str,
+    pub tweets_fetched: u64,
+    pub pages_fetched: u64,
+    pub api_calls: u64,
+    pub duration_ms: i64,
+    /// Most recent `api_errors` row recorded for this user
+    pub api_error_id: Option<i64>,
+    pub error_message: Option<String>,
+}
+
+/// One row of a user's crawl history (`crawler_run_users`)
+#[derive(Debug, Clone, Serialize)]
+pub struct CrawlRunUser {
+    pub run_id: i64,
+    pub twitter_user_id: i64,
+    pub username: String,
+    pub status: String,
+    pub tweets_fetched: i64,
+    pub pages_fetched: i64,
+    pub api_calls: i64,
+    pub duration_ms: i64,
+    pub api_error_id: Option<i64>,
+    pub error_message: Option<String>,
+    pub completed_at: Option<String>,
+}
+
+/// One `llm_models` row
+#[derive(Debug, Clone)]
+pub struct LlmModel {
+    pub id: i64,
+    pub name: String,
+    pub huggingface_model_id: Option<String>,
+    pub is_enabled: bool,
+}
+
+impl LlmModel {
+    /// Whether `model` names this model, by Hugging Face id or by name
+    pub fn matches(&self, model: &str) -> bool {
+        self.huggingface_model_id.as_deref() == Some(model) || self.name == model
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnalysisJob {
+    pub id: i64,
+    pub tweet_id: i64,
+    pub llm_model_id: i64,
+    /// Hugging Face model id, falling back to the model name
+    pub model_name: String,
+    pub content: String,
+    pub attempt_count: i64,
+}
+
+/// One analysed tweet, as read back for aggregation
+#[derive(Debug, Clone)]
+pub struct AnalysisScoreRow {
+    pub twitter_user_id: i64,
+    pub tweet_id: i64,
+    pub llm_model_id: i64,
+    pub tweet_timestamp: String,
+    pub emotion_scores: String,
+}
+
+/// Gauge definition stored under the `gauges` configuration key
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GaugeConfig {
+    pub name: String,
+    #[serde(default)]
+    pub emotions: Vec<String>,
+    #[serde(default)]
+    pub inverted_emotions: Vec<String>,
+}
+
+/// Pre-computed row for `user_aggregations`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserAggregation {
+    pub twitter_user_id: i64,
+    /// `None` for scores combined across models
+    pub llm_model_id: Option<i64>,
+    pub time_bucket: &'static str,
+    pub bucket_start_date: Option<String>,
+    pub emotion_averages: String,
+    pub emotion_medians: String,
+    pub emotion_modes: String,
+    pub tweet_count: i64,
+}
+
+/// Pre-computed row for `global_aggregations`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalAggregation {
+    /// `None` for scores combined across models
+    pub llm_model_id: Option<i64>,
+    pub time_bucket: &'static str,
+    pub bucket_start_date: Option<String>,
+    pub emotion_averages: String,
+    pub gauge_values: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CrawlerRunSummary {
+    pub id: i64,
+    pub status: String,
+    pub started_at: Option<String>,
+    pub completed_at: Option<String>,
+    pub tweets_fetched: i64,
+    pub tweets_analyzed: i64,
+    pub errors_count: i64,
 }
 
 #[cfg(test)]
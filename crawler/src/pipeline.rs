@@ -0,0 +1,506 @@
+//! Daemon pipeline: independently scheduled stages coordinated through the database
+//!
+//! - fetcher: crawls tracked users on the crawl schedule and enqueues analysis jobs
+//! - reanalysis dispatcher: turns `reanalysis_requests` into analysis jobs
+//! - analysis workers: drain `analysis_queue` through the configured LLMs
+//! - aggregator: recomputes aggregations when new analyses have landed
+//! - pruner: applies the retention rules
+//! - backup: uploads SQLite snapshots on schedule and when the admin panel asks
+//!
+//! Stages share a single-threaded `LocalSet`, so a slow LLM call only yields to
+//! the other stages instead of blocking tweet fetching. Database calls run on
+//! the blocking thread pool through a shared [`DbPool`].
+//!
+//! The fetcher re-reads the admin panel's settings at the start of every crawl
+//! cycle and publishes the result on a watch channel; the other stages read the
+//! latest configuration at the start of each iteration.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::watch;
+use tokio::task::{self, JoinSet, LocalSet};
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::aggregation::recompute_aggregations;
+use crate::analysis::{run_analysis_worker, AnalysisOptions, HuggingFaceAnalyzer};
+use crate::backup;
+use crate::config::Config;
+use crate::crawl::{dispatch_reanalysis_requests, run_crawl_cycle, CrawlOptions};
+use crate::db_pool::DbPool;
+use crate::retention::prune;
+use crate::shutdown::Shutdown;
+use crate::status::StatusBoard;
+use crate::storage;
+
+pub async fn run_pipeline(
+    config: Config,
+    pool: DbPool,
+    shutdown: Arc<Shutdown>,
+    board: Arc<StatusBoard>,
+) -> anyhow::Result<()> {
+    info!("Configuration loaded:");
+    info!("  Database: {}", storage::redacted_url(&config.database_url));
+    info!("  Crawl schedule: {}", config.crawl_schedule.describe());
+    info!("  History depth: {} days", config.history_depth_days);
+    info!("  Analysis workers: {}", config.analysis_workers);
+    info!("  Aggregation schedule: {}", config.aggregation_schedule.describe());
+    info!("  Database connections: up to {}", config.db_pool_size);
+    info!(
+        "  Retention: {} ({})",
+        config.retention.describe(),
+        config.prune_schedule.describe()
+    );
+
+    let analysis_workers = config.analysis_workers.max(1);
+    let sqlite_path = storage::sqlite_path(&config.database_url);
+    let (publish, config) = watch::channel(Rc::new(config));
+    let local = LocalSet::new();
+    local
+        .run_until(async move {
+            let mut stages = Stages::default();
+
+            stages.spawn(
+                "fetcher".to_string(),
+                &board,
+                run_fetcher(publish, pool.clone(), shutdown.clone(), board.clone()),
+            );
+            stages.spawn(
+                "reanalysis dispatcher".to_string(),
+                &board,
+                run_reanalysis_dispatcher(config.clone(), pool.clone(), shutdown.clone(), board.clone()),
+            );
+            for worker in 0..analysis_workers {
+                stages.spawn(
+                    analysis_stage_name(worker),
+                    &board,
+                    run_analysis_stage(worker, config.clone(), pool.clone(), shutdown.clone(), board.clone()),
+                );
+            }
+            stages.spawn(
+                "aggregator".to_string(),
+                &board,
+                run_aggregator(config.clone(), pool.clone(), shutdown.clone(), board.clone()),
+            );
+            stages.spawn(
+                "pruner".to_string(),
+                &board,
+                run_pruner(config.clone(), pool.clone(), shutdown.clone(), board.clone()),
+            );
+            match sqlite_path {
+                Some(path) => stages.spawn(
+                    "backup".to_string(),
+                    &board,
+                    run_backups(path, config.clone(), pool.clone(), shutdown.clone(), board.clone()),
+                ),
+                None => info!("Backups are only available for SQLite databases"),
+            }
+
+            // A stage that fails or panics takes the daemon down so the
+            // supervisor can restart it, after the other stages have drained.
+            let error = loop {
+                let Some(result) = stages.tasks.join_next_with_id().await else {
+                    return Ok(());
+                };
+                if let Some(error) = stages.failure(result, &board) {
+                    break error;
+                }
+            };
+            shutdown.request();
+            let drain = Duration::from_secs(config.borrow().shutdown_drain_secs);
+            let expire = shutdown.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(drain).await;
+                expire.expire_drain();
+            });
+            let drained = async {
+                while let Some(result) = stages.tasks.join_next_with_id().await {
+                    stages.failure(result, &board);
+                }
+            };
+            tokio::select! {
+                _ = drained => {}
+                _ = shutdown.drain_expired() => {
+                    warn!("Drain timeout expired, abandoning the remaining stages");
+                }
+            }
+            Err(error)
+        })
+        .await
+}
+
+/// Running stages, with their names so a panicking one can be reported
+#[derive(Default)]
+struct Stages {
+    tasks: JoinSet<anyhow::Result<()>>,
+    names: HashMap<task::Id, String>,
+}
+
+impl Stages {
+    fn spawn(
+        &mut self,
+        name: String,
+        board: &Arc<StatusBoard>,
+        stage: impl Future<Output = anyhow::Result<()>> + 'static,
+    ) {
+        let handle = self.tasks.spawn_local(run_stage(name.clone(), board.clone(), stage));
+        self.names.insert(handle.id(), name);
+    }
+
+    /// The error a finished stage ended with, if any
+    fn failure(
+        &self,
+        result: Result<(task::Id, anyhow::Result<()>), task::JoinError>,
+        board: &StatusBoard,
+    ) -> Option<anyhow::Error> {
+        match result {
+            Ok((_, result)) => result.err(),
+            Err(error) => {
+                let name = self.names.get(&error.id()).map_or("unknown", String::as_str);
+                warn!("Pipeline stage '{}' died: {}", name, error);
+                board.set_stage(name, "failed");
+                Some(anyhow::anyhow!("Pipeline stage '{name}' died: {error}"))
+            }
+        }
+    }
+}
+
+async fn run_stage(
+    name: String,
+    board: Arc<StatusBoard>,
+    stage: impl Future<Output = anyhow::Result<()>>,
+) -> anyhow::Result<()> {
+    let result = stage.instrument(info_span!("stage", stage = %name)).await;
+    match &result {
+        Ok(()) => {
+            info!("Pipeline stage '{}' stopped", name);
+            board.set_stage(&name, "stopped");
+        }
+        Err(error) => {
+            warn!("Pipeline stage '{}' failed: {:#}", name, error);
+            board.set_stage(&name, "failed");
+        }
+    }
+    result
+}
+
+fn analysis_stage_name(worker: usize) -> String {
+    format!("analysis worker {worker}")
+}
+
+async fn run_fetcher(
+    publish: watch::Sender<Rc<Config>>,
+    pool: DbPool,
+    shutdown: Arc<Shutdown>,
+    board: Arc<StatusBoard>,
+) -> anyhow::Result<()> {
+    let mut config = publish.borrow().clone();
+
+    // Runs a crashed or aborted crawler left `running` would show the crawler
+    // as busy forever. One-shot crawls may be running alongside, so only runs
+    // that have stalled are failed.
+    let stale_minutes = config.stall_timeout_minutes;
+    match pool.run(move |database| database.fail_abandoned_runs(stale_minutes)).await {
+        Ok(0) => {}
+        Ok(abandoned) => warn!("Marked {} interrupted crawler run(s) failed", abandoned),
+        Err(error) => warn!("Could not check for interrupted crawler runs: {}", error),
+    }
+
+    // The dispatcher stage owns reanalysis requests.
+    let options = CrawlOptions {
+        skip_reanalysis: true,
+        ..CrawlOptions::default()
+    };
+
+    // The first cycle runs immediately; later ones follow the schedule. The
+    // fetcher is a single task, so cycles can never overlap.
+    let mut scheduled_at = Utc::now();
+
+    loop {
+        if shutdown.is_requested() {
+            break;
+        }
+
+        // Admin panel edits to the crawler settings apply from the next cycle on,
+        // and to the other stages from their next iteration.
+        if let Some(refreshed) = config.refresh(&pool).await {
+            config = Rc::new(refreshed);
+            publish.send_replace(config.clone());
+        }
+
+        board.set_stage("fetcher", "crawling");
+        board.set_next_run(None);
+        if let Err(e) = run_crawl_cycle(&pool, &config, &options, &shutdown, &board).await {
+            warn!("Crawl cycle failed: {}", e);
+        }
+
+        if shutdown.is_requested() {
+            break;
+        }
+
+        let Some(next_run) = config.crawl_schedule.next_after(scheduled_at) else {
+            warn!("Crawl schedule has no future fire times, stopping fetcher.");
+            break;
+        };
+
+        // Smart scheduling: a cycle that overran its slot is followed by one
+        // catch-up cycle as soon as it completes, never by overlapping runs.
+        let now = Utc::now();
+        scheduled_at = if next_run <= now {
+            warn!(
+                "Crawl cycle overran the slot scheduled for {}, starting next cycle now",
+                next_run.to_rfc3339()
+            );
+            now
+        } else {
+            next_run
+        };
+
+        let label = config.crawl_schedule.describe();
+        let persisted = pool
+            .run(move |database| database.set_next_scheduled_run(&label, Some(scheduled_at)))
+            .await;
+        if let Err(error) = persisted {
+            warn!("Failed to persist next scheduled run: {}", error);
+        }
+        board.set_next_run(Some(scheduled_at));
+        board.set_stage("fetcher", "waiting");
+
+        info!("Next crawl cycle scheduled for {}", scheduled_at.to_rfc3339());
+        if shutdown.sleep((scheduled_at - now).to_std().unwrap_or_default()).await {
+            break;
+        }
+    }
+
+    let schedule_label = config.crawl_schedule.describe();
+    let cleared = pool
+        .run(move |database| database.set_next_scheduled_run(&schedule_label, None))
+        .await;
+    if let Err(error) = cleared {
+        warn!("Failed to clear next scheduled run: {}", error);
+    }
+    board.set_next_run(None);
+
+    Ok(())
+}
+
+async fn run_reanalysis_dispatcher(
+    config: watch::Receiver<Rc<Config>>,
+    pool: DbPool,
+    shutdown: Arc<Shutdown>,
+    board: Arc<StatusBoard>,
+) -> anyhow::Result<()> {
+    while !shutdown.is_requested() {
+        let poll = Duration::from_secs(config.borrow().reanalysis_poll_secs.max(1));
+        board.set_stage("reanalysis dispatcher", "dispatching");
+        let skip_analysis = config.borrow().skip_analysis;
+        let dispatch_shutdown = shutdown.clone();
+        let dispatched = pool
+            .run(move |database| {
+                dispatch_reanalysis_requests(database, skip_analysis, &dispatch_shutdown)
+            })
+            .await;
+        match dispatched {
+            Ok(0) => {}
+            Ok(processed) => info!("Dispatched {} reanalysis request(s)", processed),
+            Err(error) => warn!("Reanalysis dispatch failed: {}", error),
+        }
+        board.set_stage("reanalysis dispatcher", "waiting");
+
+        if shutdown.sleep(poll).await {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_analysis_stage(
+    worker: usize,
+    config: watch::Receiver<Rc<Config>>,
+    pool: DbPool,
+    shutdown: Arc<Shutdown>,
+    board: Arc<StatusBoard>,
+) -> anyhow::Result<()> {
+    let stage = analysis_stage_name(worker);
+    let analyzer = HuggingFaceAnalyzer::new(config.borrow().huggingface_token.clone())?;
+    let options = AnalysisOptions::default();
+
+    while !shutdown.is_requested() {
+        let poll = Duration::from_secs(config.borrow().analysis_poll_secs.max(1));
+        board.set_stage(&stage, "analyzing");
+        match run_analysis_worker(&pool, &analyzer, &options, &shutdown).await {
+            // Keep draining while there is work; only idle workers wait.
+            Ok(report) if report.completed + report.failed > 0 => continue,
+            Ok(_) => {}
+            Err(error) => warn!("Analysis worker {} failed: {}", worker, error),
+        }
+        board.set_stage(&stage, "idle");
+
+        if shutdown.sleep(poll).await {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_aggregator(
+    config: watch::Receiver<Rc<Config>>,
+    pool: DbPool,
+    shutdown: Arc<Shutdown>,
+    board: Arc<StatusBoard>,
+) -> anyhow::Result<()> {
+    let mut last_seen_analysis = None;
+
+    loop {
+        board.set_stage("aggregator", "waiting");
+        let now = Utc::now();
+        let Some(next_run) = config.borrow().aggregation_schedule.next_after(now) else {
+            break;
+        };
+        if shutdown.sleep((next_run - now).to_std().unwrap_or_default()).await {
+            break;
+        }
+
+        let latest = match pool.run(|database| database.latest_analysis_id()).await {
+            Ok(latest) => latest,
+            Err(error) => {
+                warn!("Aggregator could not read analyses: {}", error);
+                continue;
+            }
+        };
+        if latest.is_none() || latest == last_seen_analysis {
+            continue;
+        }
+
+        board.set_stage("aggregator", "aggregating");
+        match pool.run(|database| recompute_aggregations(database)).await {
+            Ok(_) => last_seen_analysis = latest,
+            Err(error) => warn!("Aggregation failed: {}", error),
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_pruner(
+    config: watch::Receiver<Rc<Config>>,
+    pool: DbPool,
+    shutdown: Arc<Shutdown>,
+    board: Arc<StatusBoard>,
+) -> anyhow::Result<()> {
+    loop {
+        board.set_stage("pruner", "waiting");
+        let now = Utc::now();
+        let Some(next_run) = config.borrow().prune_schedule.next_after(now) else {
+            break;
+        };
+        if shutdown.sleep((next_run - now).to_std().unwrap_or_default()).await {
+            break;
+        }
+
+        board.set_stage("pruner", "pruning");
+        let policy = config.borrow().retention.clone();
+        let prune_shutdown = shutdown.clone();
+        let pruned = pool
+            .run(move |database| prune(database, &policy, &prune_shutdown))
+            .await;
+        if let Err(error) = pruned {
+            warn!("Pruning failed: {}", error);
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_backups(
+    path: std::path::PathBuf,
+    config: watch::Receiver<Rc<Config>>,
+    pool: DbPool,
+    shutdown: Arc<Shutdown>,
+    board: Arc<StatusBoard>,
+) -> anyhow::Result<()> {
+    let busy_timeout = config.borrow().sqlite_busy_timeout();
+
+    // Rows a crashed crawler left `running` would block admin requests forever.
+    match backup::fail_abandoned_backups(&path, busy_timeout).await {
+        Ok(0) => {}
+        Ok(abandoned) => warn!("Marked {} interrupted backup(s) failed", abandoned),
+        Err(error) => warn!("Could not check for interrupted backups: {}", error),
+    }
+
+    // Settings are re-read every poll, so admin panel changes apply without a restart.
+    let mut described: Option<String> = None;
+    let mut next_run: Option<DateTime<Utc>> = None;
+
+    while !shutdown.is_requested() {
+        board.set_stage("backup", "waiting");
+        let current = config.borrow().clone();
+        let poll = Duration::from_secs(current.backup_poll_secs.max(1));
+        let settings = backup::load_settings(&pool, &current.backup).await;
+        let description = match &settings {
+            Ok(Some(settings)) => settings.describe(),
+            Ok(None) => "not configured".to_string(),
+            Err(error) => error.to_string(),
+        };
+        if described.as_ref() != Some(&description) {
+            info!("Backups: {}", description);
+            let schedule = match &settings {
+                Ok(Some(settings)) => settings.schedule.as_ref(),
+                _ => None,
+            };
+            next_run = schedule.and_then(|schedule| schedule.next_after(Utc::now()));
+            described = Some(description.clone());
+        }
+
+        let requested = match backup::has_requested_backup(&path, busy_timeout).await {
+            Ok(requested) => requested,
+            Err(error) => {
+                warn!("Could not check for requested backups: {}", error);
+                false
+            }
+        };
+        let due = next_run.is_some_and(|next_run| next_run <= Utc::now());
+
+        if requested || due {
+            match &settings {
+                Ok(Some(settings)) => {
+                    board.set_stage("backup", "backing up");
+                    let triggered_by = if requested { "admin" } else { "schedule" };
+                    let result =
+                        backup::run_backup(&path, busy_timeout, settings, triggered_by, &shutdown).await;
+                    if let Err(error) = result {
+                        warn!("Backup failed: {}", error);
+                    }
+                    if due {
+                        next_run = settings
+                            .schedule
+                            .as_ref()
+                            .and_then(|schedule| schedule.next_after(Utc::now()));
+                    }
+                }
+                _ => {
+                    let message = format!("Backups unavailable: {description}");
+                    let failed = backup::fail_requested_backups(&path, busy_timeout, &message).await;
+                    if let Err(error) = failed {
+                        warn!("Could not fail requested backups: {}", error);
+                    }
+                }
+            }
+        }
+
+        let wait = next_run
+            .and_then(|next_run| (next_run - Utc::now()).to_std().ok())
+            .map_or(poll, |until| until.min(poll));
+        if shutdown.sleep(wait).await {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -4,22 +4,27 @@ use std::{num::NonZeroU32, time::Duration};
 
 use governor::{
     clock::DefaultClock,
+    middleware::StateInformationMiddleware,
     state::{InMemoryState, NotKeyed},
     Quota, RateLimiter,
 };
 
 use crate::error::CrawlerError;
 
-pub type SharedRateLimiter = RateLimiter<NotKeyed, InMemoryState, DefaultClock>;
+/// Window the Twitter rate limit budget applies to
+pub const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(900);
+
+pub type SharedRateLimiter =
+    RateLimiter<NotKeyed, InMemoryState, DefaultClock, StateInformationMiddleware>;
 
 pub fn build_rate_limiter(rate_limit_per_15min: u32) -> Result<SharedRateLimiter, CrawlerError> {
     let non_zero = NonZeroU32::new(rate_limit_per_15min).ok_or_else(|| {
         CrawlerError::Config("rate_limit_per_15min must be greater than zero".to_string())
     })?;
 
-    let quota = Quota::with_period(Duration::from_secs(900))
+    let quota = Quota::with_period(RATE_LIMIT_WINDOW)
         .ok_or_else(|| CrawlerError::Config("invalid rate limit period".to_string()))?
         .allow_burst(non_zero);
 
-    Ok(RateLimiter::direct(quota))
+    Ok(RateLimiter::direct(quota).with_middleware::<StateInformationMiddleware>())
 }
@@ -0,0 +1,448 @@
+//! Restoring the SQLite database from a backup archive
+//!
+//! The archive is downloaded next to the database, checked against the SHA-256
+//! recorded in `backups` when the current database still has that row, and
+//! decompressed. The copy has to pass `PRAGMA integrity_check` and carry a
+//! schema version this build knows; it is then migrated to the current schema
+//! and renamed over the database in one step. The file it replaces is kept as
+//! `{database}.pre-restore-{timestamp}`.
+//!
+//! Nothing is downloaded unless the crawler lock is free, i.e. no crawler
+//! process has the database open. The Node backend does not take the lock, so
+//! it has to be stopped as well; the swap takes the database with an exclusive
+//! lock first and refuses while any other connection has it open.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::Utc;
+use flate2::read::GzDecoder;
+use rusqlite::{Connection, ErrorCode, OpenFlags, OptionalExtension};
+use tracing::{info, warn};
+
+use crate::backup::{self, BackupOverrides, BackupSettings, TempFile, CONFIGURATION_KEY};
+use crate::error::CrawlerError;
+use crate::lock::CrawlerLock;
+use crate::migrations;
+use crate::s3::{S3Client, S3Object};
+use crate::schema_compat;
+
+/// Which archive to restore
+#[derive(Debug, Clone, Copy)]
+pub enum Snapshot<'a> {
+    Latest,
+    /// Full object key, or just its file name
+    Named(&'a str),
+}
+
+#[derive(Debug)]
+pub struct RestoreReport {
+    pub object_key: String,
+    /// Schema version of the archive before it was migrated
+    pub schema_version: i64,
+    /// Where the replaced database was kept; `None` if there was none
+    pub previous: Option<PathBuf>,
+}
+
+/// Backup settings for a restore
+///
+/// The admin panel's settings are read from the current database when it is
+/// still readable; otherwise only the `BACKUP_*` variables apply.
+pub fn load_settings(
+    database_path: &Path,
+    overrides: &BackupOverrides,
+) -> Result<Option<BackupSettings>, CrawlerError> {
+    let stored = Connection::open_with_flags(database_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .and_then(|conn| {
+            conn.query_row(
+                "SELECT value FROM configurations WHERE key = ?1",
+                [CONFIGURATION_KEY],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+        });
+    let stored = match stored {
+        Ok(value) => value.and_then(|value| serde_json::from_str(&value).ok()),
+        Err(error) => {
+            warn!(
+                "Could not read backup settings from {} ({}); using BACKUP_* only",
+                database_path.display(),
+                error
+            );
+            None
+        }
+    };
+    backup::resolve_settings(overrides, stored.as_ref())
+}
+
+/// Archives available to restore, newest first
+pub async fn list(settings: &BackupSettings) -> Result<Vec<S3Object>, CrawlerError> {
+    let client = S3Client::new(settings.s3.clone())?;
+    backup::list_archives(&client, settings).await
+}
+
+/// Replace the database at `database_path` with a verified backup
+pub async fn restore(
+    database_path: &Path,
+    busy_timeout: Duration,
+    settings: &BackupSettings,
+    snapshot: Snapshot<'_>,
+) -> Result<RestoreReport, CrawlerError> {
+    // Taken first, so nothing is downloaded while a crawler is still running.
+    let lock = CrawlerLock::exclusive(database_path)?;
+
+    let client = S3Client::new(settings.s3.clone())?;
+    let archives = backup::list_archives(&client, settings).await?;
+    let object = match snapshot {
+        Snapshot::Latest => archives.into_iter().next(),
+        Snapshot::Named(name) => archives
+            .into_iter()
+            .find(|object| object.key == name || object.key.rsplit('/').next() == Some(name)),
+    };
+    let Some(object) = object else {
+        return Err(CrawlerError::Backup(match snapshot {
+            Snapshot::Latest => format!(
+                "No backups in s3://{}/{}",
+                settings.s3.bucket, settings.prefix
+            ),
+            Snapshot::Named(name) => format!(
+                "No backup named {name} in s3://{}/{}",
+                settings.s3.bucket, settings.prefix
+            ),
+        }));
+    };
+
+    let archive = TempFile(sibling(database_path, ".restore.gz"));
+    let restored = TempFile(sibling(database_path, ".restore"));
+    let (size, sha256) = client.get_file(&object.key, &archive.0).await?;
+    info!("Downloaded {} ({} bytes)", object.key, size);
+
+    let path = database_path.to_path_buf();
+    let (archive_path, restored_path) = (archive.0.clone(), restored.0.clone());
+    let object_key = object.key.clone();
+    let report = backup::blocking(move || {
+        if let Some(expected) = recorded_sha256(&path, &object_key) {
+            if expected != sha256 {
+                return Err(CrawlerError::Backup(format!(
+                    "{object_key} has SHA-256 {sha256}, but {expected} was recorded when it was uploaded"
+                )));
+            }
+        }
+        decompress(&archive_path, &restored_path)?;
+        let schema_version = verify(&restored_path)?;
+        let previous = swap(&path, &restored_path, busy_timeout)?;
+        Ok(RestoreReport {
+            object_key,
+            schema_version,
+            previous,
+        })
+    })
+    .await?;
+    drop(lock);
+
+    info!(
+        "Restored {} (schema version {}) to {}{}",
+        report.object_key,
+        report.schema_version,
+        database_path.display(),
+        report
+            .previous
+            .as_ref()
+            .map(|previous| format!("; the previous database is at {}", previous.display()))
+            .unwrap_or_default()
+    );
+    Ok(report)
+}
+
+/// Checksum recorded for `object_key` by the database being replaced, if it
+/// still exists and is readable
+fn recorded_sha256(database_path: &Path, object_key: &str) -> Option<String> {
+    let conn = Connection::open_with_flags(database_path, OpenFlags::SQLITE_OPEN_READ_ONLY).ok()?;
+    conn.query_row(
+        "SELECT sha256 FROM backups WHERE object_key = ?1",
+        [object_key],
+        |row| row.get::<_, Option<String>>(0),
+    )
+    .ok()
+    .flatten()
+}
+
+fn decompress(archive: &Path, target: &Path) -> Result<(), CrawlerError> {
+    let file_error = |path: &Path, error: io::Error| {
+        CrawlerError::Backup(format!(
+            "Could not decompress into {}: {error}",
+            path.display()
+        ))
+    };
+    let mut input =
+        GzDecoder::new(File::open(archive).map_err(|error| file_error(archive, error))?);
+    let mut output = File::create(target).map_err(|error| file_error(target, error))?;
+    io::copy(&mut input, &mut output).map_err(|error| file_error(target, error))?;
+    output.sync_all().map_err(|error| file_error(target, error))
+}
+
+/// Integrity and schema checks on the downloaded copy, which is then migrated;
+/// returns its schema version from before the migration
+fn verify(path: &Path) -> Result<i64, CrawlerError> {
+    let conn = Connection::open(path)?;
+
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let problems = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+    if problems != ["ok"] {
+        return Err(CrawlerError::Backup(format!(
+            "Backup failed the integrity check: {}",
+            problems
+                .iter()
+                .take(5)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("; ")
+        )));
+    }
+
+    let version: Option<i64> = conn
+        .query_row("SELECT MAX(version) FROM schema_migrations", [], |row| {
+            row.get(0)
+        })
+        .ok()
+        .flatten();
+    let Some(version) = version else {
+        return Err(CrawlerError::Backup(
+            "Backup has no schema version; it is not a crawler database".to_string(),
+        ));
+    };
+    if version > migrations::latest_version() {
+        return Err(CrawlerError::Backup(format!(
+            "Backup has schema version {version}, newer than this build's {}; restore it with a newer crawler",
+            migrations::latest_version()
+        )));
+    }
+    migrations::migrate(&conn)?;
+    schema_compat::check(&conn)?;
+
+    // The archive was taken while its own backup was running.
+    backup::fail_abandoned(&conn)?;
+    conn.pragma_update(None, "journal_mode", "DELETE")?;
+    Ok(version)
+}
+
+/// Rename `restored` over the database, keeping the old file alongside
+fn swap(
+    database_path: &Path,
+    restored: &Path,
+    busy_timeout: Duration,
+) -> Result<Option<PathBuf>, CrawlerError> {
+    let file_error = |error: io::Error| {
+        CrawlerError::Backup(format!(
+            "Could not replace {}: {error}",
+            database_path.display()
+        ))
+    };
+    if !database_path.exists() {
+        fs::rename(restored, database_path).map_err(file_error)?;
+        return Ok(None);
+    }
+
+    // An idle connection in WAL mode still holds a shared lock, so taking the
+    // database exclusively fails while the API has it open, however quiet it
+    // is. The lock is kept until the new file is in place.
+    let conn = backup::connect(database_path, busy_timeout)?;
+    let locked = conn
+        .query_row("PRAGMA locking_mode = EXCLUSIVE", [], |row| {
+            row.get::<_, String>(0)
+        })
+        .and_then(|_| conn.execute_batch("BEGIN EXCLUSIVE; COMMIT"));
+    match locked {
+        Ok(()) => {}
+        Err(rusqlite::Error::SqliteFailure(error, _))
+            if matches!(
+                error.code,
+                ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked
+            ) =>
+        {
+            return Err(CrawlerError::Locked(format!(
+                "{} is still open elsewhere; stop the API before restoring",
+                database_path.display()
+            )));
+        }
+        Err(error) => return Err(error.into()),
+    }
+    // Fold the write-ahead log into the old file, so the copy kept below is
+    // complete and no stale log is applied to the restored database.
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| {
+        row.get::<_, i64>(0)
+    })?;
+
+    let previous = sibling(
+        database_path,
+        &format!(".pre-restore-{}", Utc::now().format("%Y%m%dT%H%M%SZ")),
+    );
+    fs::hard_link(database_path, &previous)
+        .or_else(|_| fs::copy(database_path, &previous).map(|_| ()))
+        .map_err(file_error)?;
+    fs::rename(restored, database_path).map_err(file_error)?;
+    drop(conn);
+    for suffix in ["-wal", "-shm"] {
+        match fs::remove_file(sibling(database_path, suffix)) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(file_error(error)),
+            _ => {}
+        }
+    }
+    Ok(Some(previous))
+}
+
+/// `path` with `suffix` appended to its file name
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut sibling = path.as_os_str().to_owned();
+    sibling.push(suffix);
+    PathBuf::from(sibling)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::backup::run_backup;
+    use crate::db::SqliteDatabase;
+    use crate::s3::{fake, S3Settings};
+    use crate::shutdown::Shutdown;
+    use crate::storage::Storage;
+
+    async fn backed_up_database(name: &str) -> Result<(PathBuf, BackupSettings), CrawlerError> {
+        let path = std::env::temp_dir().join(format!("crawler-{name}-{}.db", std::process::id()));
+        let database = SqliteDatabase::new(&path.to_string_lossy(), Duration::from_secs(1))?;
+        database.init_schema()?;
+        database.execute_sql(
+            "INSERT INTO twitter_users (id, twitter_id, username, display_name, is_active)
+             VALUES (5, 'user_5', 'user5', 'User Five', 1)",
+        )?;
+
+        let (endpoint, _) = fake::serve().await;
+        let settings = BackupSettings {
+            s3: S3Settings {
+                endpoint,
+                bucket: "feels".to_string(),
+                region: "us-east-1".to_string(),
+                access_key_id: "minio".to_string(),
+                secret_access_key: "minio-secret".to_string(),
+            },
+            prefix: "backups/".to_string(),
+            keep: 0,
+            schedule: None,
+        };
+        Ok((path, settings))
+    }
+
+    fn remove_database(path: &Path) {
+        let mut files = vec![path.to_path_buf()];
+        if let Ok(entries) = fs::read_dir(path.parent().unwrap()) {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            files.extend(
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|file| file.to_string_lossy().contains(&format!("{name}."))),
+            );
+        }
+        for file in files {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = fs::remove_file(format!("{}{suffix}", file.display()));
+            }
+        }
+    }
+
+    fn usernames(path: &Path) -> Result<String, CrawlerError> {
+        let conn = Connection::open(path)?;
+        Ok(conn.query_row(
+            "SELECT group_concat(username, ',') FROM (SELECT username FROM twitter_users ORDER BY id)",
+            [],
+            |row| row.get(0),
+        )?)
+    }
+
+    #[tokio::test]
+    async fn restore_waits_for_the_crawler_and_the_api_and_keeps_the_old_file(
+    ) -> Result<(), CrawlerError> {
+        let (path, settings) = backed_up_database("restore").await?;
+        let timeout = Duration::from_secs(1);
+        let backup = run_backup(&path, timeout, &settings, "manual", &Shutdown::new()).await?;
+
+        let database = SqliteDatabase::new(&path.to_string_lossy(), timeout)?;
+        database.execute_sql(
+            "INSERT INTO twitter_users (id, twitter_id, username, display_name, is_active)
+             VALUES (6, 'user_6', 'user6', 'User Six', 1)",
+        )?;
+        drop(database);
+
+        let listed = list(&settings).await?;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].key, backup.object_key);
+
+        // A running crawler holds the lock shared.
+        let crawler = CrawlerLock::shared(&path)?;
+        let refused = restore(&path, timeout, &settings, Snapshot::Latest).await;
+        assert!(matches!(refused, Err(CrawlerError::Locked(_))));
+        assert_eq!(usernames(&path)?, "user5,user6");
+        drop(crawler);
+
+        // So does the API's connection, even when it is idle.
+        let api = Connection::open(&path)?;
+        api.query_row("SELECT COUNT(*) FROM twitter_users", [], |row| {
+            row.get::<_, i64>(0)
+        })?;
+        let refused = restore(&path, timeout, &settings, Snapshot::Latest).await;
+        assert!(
+            matches!(refused, Err(CrawlerError::Locked(_))),
+            "{refused:?}"
+        );
+        assert_eq!(usernames(&path)?, "user5,user6");
+        drop(api);
+
+        let file_name = backup.object_key.rsplit('/').next().unwrap();
+        let report = restore(&path, timeout, &settings, Snapshot::Named(file_name)).await?;
+        assert_eq!(report.schema_version, migrations::latest_version());
+        assert_eq!(usernames(&path)?, "user5");
+        let previous = report.previous.expect("the old database is kept");
+        assert_eq!(usernames(&previous)?, "user5,user6");
+
+        // The backup's own row was still running when the snapshot was taken.
+        let conn = Connection::open(&path)?;
+        let status: String = conn.query_row("SELECT status FROM backups", [], |row| row.get(0))?;
+        assert_eq!(status, "failed");
+        assert!(!sibling(&path, ".restore").exists());
+        assert!(!sibling(&path, ".restore.gz").exists());
+
+        drop(conn);
+        remove_database(&path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn restore_refuses_a_schema_newer_than_this_build() -> Result<(), CrawlerError> {
+        let (path, settings) = backed_up_database("restore-newer").await?;
+        let timeout = Duration::from_secs(1);
+        let database = SqliteDatabase::new(&path.to_string_lossy(), timeout)?;
+        database.execute_sql(
+            "INSERT INTO schema_migrations (version, name, checksum) VALUES (999, 'future', 'x')",
+        )?;
+        run_backup(&path, timeout, &settings, "manual", &Shutdown::new()).await?;
+        database.execute_sql("DELETE FROM schema_migrations WHERE version = 999")?;
+        drop(database);
+
+        let refused = restore(&path, timeout, &settings, Snapshot::Latest).await;
+        let Err(CrawlerError::Backup(message)) = refused else {
+            panic!("expected the restore to be refused, got {refused:?}");
+        };
+        assert!(message.contains("schema version 999"), "{message}");
+        // The live database was left alone.
+        assert_eq!(usernames(&path)?, "user5");
+
+        remove_database(&path);
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+//! Retention rules and the pruning that applies them
+//!
+//! Each rule deletes rows older than its cutoff in small batches, one
+//! transaction per batch, so the Node backend and the other pipeline stages
+//! only ever wait for one batch. An optional vacuum afterwards returns the
+//! freed pages to the filesystem.
+
+use chrono::{Duration, Utc};
+use tracing::info;
+
+use crate::error::CrawlerError;
+use crate::shutdown::Shutdown;
+use crate::storage::Storage;
+use crate::telemetry;
+
+/// What one retention rule removes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruneTarget {
+    /// Tweets posted before the cutoff, with their analyses and jobs
+    Tweets,
+    /// Resolved `api_errors` rows
+    ResolvedApiErrors,
+    /// The `error_details` blob of finished `crawler_runs`; the run row stays
+    RunErrorDetails,
+    /// Completed `analysis_queue` rows
+    CompletedJobs,
+}
+
+impl PruneTarget {
+    pub fn label(self) -> &'static str {
+        match self {
+            PruneTarget::Tweets => "tweets",
+            PruneTarget::ResolvedApiErrors => "api_errors",
+            PruneTarget::RunErrorDetails => "crawler_runs.error_details",
+            PruneTarget::CompletedJobs => "analysis_queue",
+        }
+    }
+}
+
+/// Space reclaimed after pruning (`PRUNE_VACUUM`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VacuumMode {
+    Off,
+    /// SQLite `incremental_vacuum`, PostgreSQL plain `VACUUM`; neither blocks readers
+    Incremental,
+    /// SQLite `VACUUM`, PostgreSQL `VACUUM FULL`; both rewrite the tables under an exclusive lock
+    Full,
+}
+
+impl VacuumMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "off" | "none" | "" => Some(VacuumMode::Off),
+            "incremental" => Some(VacuumMode::Incremental),
+            "full" => Some(VacuumMode::Full),
+            _ => None,
+        }
+    }
+}
+
+/// How long each kind of row is kept; `None` keeps it forever
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    pub tweets_days: Option<u32>,
+    pub api_errors_days: Option<u32>,
+    pub run_errors_days: Option<u32>,
+    pub completed_jobs_days: Option<u32>,
+    /// Rows deleted per transaction
+    pub batch_size: i64,
+    pub vacuum: VacuumMode,
+}
+
+impl RetentionPolicy {
+    /// Enabled rules with their retention in days
+    pub fn rules(&self) -> Vec<(PruneTarget, u32)> {
+        [
+            (PruneTarget::Tweets, self.tweets_days),
+            (PruneTarget::ResolvedApiErrors, self.api_errors_days),
+            (PruneTarget::RunErrorDetails, self.run_errors_days),
+            (PruneTarget::CompletedJobs, self.completed_jobs_days),
+        ]
+        .into_iter()
+        .filter_map(|(target, days)| days.map(|days| (target, days)))
+        .collect()
+    }
+
+    pub fn describe(&self) -> String {
+        let rules: Vec<_> = self
+            .rules()
+            .iter()
+            .map(|(target, days)| format!("{} {}d", target.label(), days))
+            .collect();
+        if rules.is_empty() {
+            "keep everything".to_string()
+        } else {
+            rules.join(", ")
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    /// Rows deleted (or blobs cleared) per rule
+    pub pruned: Vec<(PruneTarget, u64)>,
+    pub size_before: i64,
+    /// Only measured when a vacuum ran
+    pub size_after: Option<i64>,
+    /// Shutdown stopped pruning before every rule finished
+    pub interrupted: bool,
+}
+
+pub fn prune(
+    database: &dyn Storage,
+    policy: &RetentionPolicy,
+    shutdown: &Shutdown,
+) -> Result<PruneReport, CrawlerError> {
+    let mut report = PruneReport {
+        size_before: database.database_size_bytes()?,
+        ..PruneReport::default()
+    };
+    telemetry::set_database_size(report.size_before);
+
+    let batch_size = policy.batch_size.max(1);
+    for (target, days) in policy.rules() {
+        let cutoff = Utc::now() - Duration::days(i64::from(days));
+        let mut pruned = 0_u64;
+        loop {
+            if shutdown.is_requested() {
+                report.interrupted = true;
+                break;
+            }
+            let deleted = database.prune_batch(target, cutoff, batch_size)?;
+            pruned += deleted;
+            if deleted < batch_size as u64 {
+                break;
+            }
+        }
+        telemetry::record_rows_pruned(target.label(), pruned);
+        report.pruned.push((target, pruned));
+        if report.interrupted {
+            break;
+        }
+    }
+
+    if policy.vacuum != VacuumMode::Off && !report.interrupted {
+        database.vacuum(policy.vacuum)?;
+        let size_after = database.database_size_bytes()?;
+        telemetry::set_database_size(size_after);
+        report.size_after = Some(size_after);
+    }
+
+    let pruned: Vec<_> = report
+        .pruned
+        .iter()
+        .map(|(target, rows)| format!("{} {}", target.label(), rows))
+        .collect();
+    let summary = if pruned.is_empty() {
+        "nothing".to_string()
+    } else {
+        pruned.join(", ")
+    };
+    match report.size_after {
+        Some(after) => info!(
+            "Pruned {}; database size {} bytes before vacuum, {} after",
+            summary, report.size_before, after
+        ),
+        None => info!(
+            "Pruned {}; database size {} bytes",
+            summary, report.size_before
+        ),
+    }
+    Ok(report)
+}
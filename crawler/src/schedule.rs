@@ -0,0 +1,101 @@
+//! Crawl scheduling: fixed wall-clock intervals or cron expressions
+
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use crate::error::CrawlerError;
+
+/// When the daemon should start crawl cycles (all times in UTC)
+#[derive(Debug, Clone)]
+pub enum CrawlSchedule {
+    /// Every `Duration`, aligned to multiples of the interval since the Unix epoch
+    /// (a 15 minute interval fires at :00, :15, :30 and :45)
+    Interval(Duration),
+
+    /// A cron expression (5 fields, or 6/7 with seconds and year)
+    Cron(Box<cron::Schedule>),
+}
+
+impl CrawlSchedule {
+    pub fn every_minutes(minutes: u32) -> Result<Self, CrawlerError> {
+        if minutes == 0 {
+            return Err(CrawlerError::Config(
+                "crawl interval must be greater than zero".to_string(),
+            ));
+        }
+        Ok(Self::Interval(Duration::minutes(minutes as i64)))
+    }
+
+    pub fn cron(expression: &str) -> Result<Self, CrawlerError> {
+        let trimmed = expression.trim();
+        // Accept classic 5-field crontab syntax by pinning seconds to zero.
+        let normalized = if trimmed.split_whitespace().count() == 5 {
+            format!("0 {trimmed}")
+        } else {
+            trimmed.to_string()
+        };
+
+        let schedule = cron::Schedule::from_str(&normalized).map_err(|err| {
+            CrawlerError::Config(format!("invalid cron expression '{trimmed}': {err}"))
+        })?;
+        Ok(Self::Cron(Box::new(schedule)))
+    }
+
+    /// First fire time strictly after `after`
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Interval(interval) => {
+                let step = interval.num_seconds().max(1);
+                let next = (after.timestamp().div_euclid(step) + 1) * step;
+                Utc.timestamp_opt(next, 0).single()
+            }
+            Self::Cron(schedule) => schedule.after(&after).next(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Interval(interval) if interval.num_minutes() % 60 == 0 => {
+                format!("every {} hour(s)", interval.num_hours())
+            }
+            Self::Interval(interval) => format!("every {} minute(s)", interval.num_minutes()),
+            Self::Cron(schedule) => format!("cron '{}'", schedule.source()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 10, hour, minute, second).unwrap()
+    }
+
+    #[test]
+    fn interval_aligns_to_wall_clock() {
+        let schedule = CrawlSchedule::every_minutes(15).unwrap();
+        assert_eq!(schedule.next_after(at(10, 7, 30)), Some(at(10, 15, 0)));
+        assert_eq!(schedule.next_after(at(10, 15, 0)), Some(at(10, 30, 0)));
+
+        let hourly = CrawlSchedule::every_minutes(60).unwrap();
+        assert_eq!(hourly.next_after(at(10, 59, 59)), Some(at(11, 0, 0)));
+    }
+
+    #[test]
+    fn five_field_cron_fires_past_the_hour_and_skips_nights() {
+        let schedule = CrawlSchedule::cron("5 8-22 * * *").unwrap();
+        assert_eq!(schedule.next_after(at(10, 7, 0)), Some(at(11, 5, 0)));
+        assert_eq!(
+            schedule.next_after(at(22, 30, 0)),
+            Some(Utc.with_ymd_and_hms(2024, 1, 11, 8, 5, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_schedules() {
+        assert!(CrawlSchedule::every_minutes(0).is_err());
+        assert!(CrawlSchedule::cron("not a cron").is_err());
+    }
+}
@@ -0,0 +1,332 @@
+//! Startup check that the shared SQLite schema has every column the crawler uses
+//!
+//! Most of these tables are created by the Node backend's `schema.sql`, which
+//! the crawler's migrations cannot change once the tables exist. Checking them
+//! up front turns a missing column into one configuration error listing every
+//! mismatch, instead of an opaque rusqlite error halfway through a cycle.
+//!
+//! Keep [`EXPECTED_COLUMNS`] in sync with the SQL in `db.rs` and `db_postgres.rs`,
+//! and [`SQLITE_COLUMNS`] with the SQL in `backup.rs` and `restore.rs`.
+
+use std::collections::HashSet;
+
+use rusqlite::Connection;
+
+use crate::error::CrawlerError;
+
+/// Every table and column that [`Storage`](crate::storage::Storage) methods read or write
+pub const EXPECTED_COLUMNS: &[(&str, &[&str])] = &[
+    (
+        "twitter_users",
+        &[
+            "id",
+            "twitter_id",
+            "username",
+            "display_name",
+            "avatar_url",
+            "follower_count",
+            "following_count",
+            "is_active",
+            "updated_at",
+        ],
+    ),
+    (
+        "tweets",
+        &[
+            "id",
+            "twitter_user_id",
+            "tweet_id",
+            "content",
+            "tweet_timestamp",
+            "engagement_metrics",
+            "is_retweet",
+            "is_reply",
+        ],
+    ),
+    (
+        "llm_models",
+        &[
+            "id",
+            "name",
+            "version",
+            "huggingface_model_id",
+            "is_enabled",
+        ],
+    ),
+    (
+        "sentiment_analyses",
+        &[
+            "id",
+            "tweet_id",
+            "llm_model_id",
+            "emotion_scores",
+            "raw_llm_response",
+            "analysis_duration_ms",
+            "model_version",
+            "analysis_version",
+            "is_current",
+        ],
+    ),
+    (
+        "user_aggregations",
+        &[
+            "twitter_user_id",
+            "llm_model_id",
+            "time_bucket",
+            "bucket_start_date",
+            "emotion_averages",
+            "emotion_medians",
+            "emotion_modes",
+            "tweet_count",
+        ],
+    ),
+    (
+        "global_aggregations",
+        &[
+            "llm_model_id",
+            "time_bucket",
+            "bucket_start_date",
+            "emotion_averages",
+            "gauge_values",
+        ],
+    ),
+    (
+        "crawler_runs",
+        &[
+            "id",
+            "started_at",
+            "completed_at",
+            "status",
+            "tweets_fetched",
+            "tweets_analyzed",
+            "errors_count",
+            "error_details",
+            "users_total",
+            "users_completed",
+            "current_user",
+            "jobs_queued",
+            "eta_at",
+            "progress_updated_at",
+        ],
+    ),
+    (
+        "api_errors",
+        &[
+            "id",
+            "error_type",
+            "error_message",
+            "error_code",
+            "endpoint",
+            "occurred_at",
+            "resolved",
+        ],
+    ),
+    ("configurations", &["key", "value", "updated_by"]),
+    (
+        "analysis_queue",
+        &[
+            "id",
+            "tweet_id",
+            "llm_model_id",
+            "status",
+            "attempt_count",
+            "last_error",
+            "updated_at",
+        ],
+    ),
+    (
+        "crawler_checkpoints",
+        &["twitter_user_id", "last_tweet_timestamp", "updated_at"],
+    ),
+    (
+        "reanalysis_requests",
+        &[
+            "id",
+            "request_type",
+            "tweet_id",
+            "twitter_user_id",
+            "status",
+            "requested_at",
+            "processed_at",
+            "cursor_tweet_id",
+            "tweets_total",
+            "tweets_processed",
+            "progress_percent",
+            "llm_model_id",
+            "tweets_from",
+            "tweets_to",
+            "only_failed",
+            "missing_emotion",
+            "dry_run",
+            "jobs_estimate",
+            "error_message",
+            "jobs_enqueued",
+            "lease_expires_at",
+        ],
+    ),
+    (
+        "crawler_fetch_progress",
+        &[
+            "twitter_user_id",
+            "start_time",
+            "end_time",
+            "pagination_token",
+            "newest_timestamp",
+            "updated_at",
+        ],
+    ),
+    (
+        "crawler_schedule",
+        &["id", "schedule", "next_scheduled_run", "updated_at"],
+    ),
+    (
+        "crawler_run_users",
+        &[
+            "id",
+            "run_id",
+            "twitter_user_id",
+            "status",
+            "tweets_fetched",
+            "pages_fetched",
+            "api_calls",
+            "duration_ms",
+            "api_error_id",
+            "error_message",
+            "completed_at",
+        ],
+    ),
+];
+
+/// Tables only a SQLite database has, used by backups and restores
+pub const SQLITE_COLUMNS: &[(&str, &[&str])] = &[(
+    "backups",
+    &[
+        "id",
+        "status",
+        "triggered_by",
+        "started_at",
+        "completed_at",
+        "progress_percent",
+        "object_key",
+        "database_bytes",
+        "compressed_bytes",
+        "sha256",
+        "error_message",
+    ],
+)];
+
+/// Verify [`EXPECTED_COLUMNS`] and [`SQLITE_COLUMNS`], reporting every missing
+/// table and column at once
+pub fn check(conn: &Connection) -> Result<(), CrawlerError> {
+    check_with(EXPECTED_COLUMNS.iter().chain(SQLITE_COLUMNS), |table| {
+        table_columns(conn, table)
+    })
+}
+
+/// [`check`] for a PostgreSQL database, looking in the connection's current schema
+pub fn check_postgres(client: &mut postgres::Client) -> Result<(), CrawlerError> {
+    check_with(EXPECTED_COLUMNS, |table| {
+        let rows = client.query(
+            "SELECT column_name FROM information_schema.columns
+             WHERE table_schema = current_schema() AND table_name = $1",
+            &[&table],
+        )?;
+        Ok(rows
+            .iter()
+            .map(|row| row.get::<_, String>(0).to_ascii_lowercase())
+            .collect())
+    })
+}
+
+/// `table_columns` returns the lower-cased columns of a table, empty if it does not exist
+fn check_with<'a>(
+    expected: impl IntoIterator<Item = &'a (&'a str, &'a [&'a str])>,
+    mut table_columns: impl FnMut(&str) -> Result<HashSet<String>, CrawlerError>,
+) -> Result<(), CrawlerError> {
+    let mut problems = Vec::new();
+
+    for (table, columns) in expected {
+        let existing = table_columns(table)?;
+        if existing.is_empty() {
+            problems.push(format!("missing table {table}"));
+            continue;
+        }
+
+        let missing: Vec<&str> = columns
+            .iter()
+            .copied()
+            .filter(|column| !existing.contains(&column.to_ascii_lowercase()))
+            .collect();
+        if !missing.is_empty() {
+            problems.push(format!("{table} is missing {}", missing.join(", ")));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(CrawlerError::Config(format!(
+            "Database schema is incompatible with this crawler: {}",
+            problems.join("; ")
+        )))
+    }
+}
+
+fn table_columns(conn: &Connection, table: &str) -> Result<HashSet<String>, CrawlerError> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    let mut columns = HashSet::new();
+    for row in rows {
+        columns.insert(row?.to_ascii_lowercase());
+    }
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+
+    #[test]
+    fn migrated_database_is_compatible() -> Result<(), CrawlerError> {
+        let conn = Connection::open_in_memory()?;
+        migrations::migrate(&conn)?;
+        check(&conn)
+    }
+
+    #[test]
+    fn reports_every_mismatch() -> Result<(), CrawlerError> {
+        let conn = Connection::open_in_memory()?;
+        // An old Node schema without the profile columns, created before the crawler ran.
+        conn.execute_batch(
+            "CREATE TABLE twitter_users (
+                id INTEGER PRIMARY KEY,
+                twitter_id TEXT,
+                username TEXT,
+                display_name TEXT,
+                is_active INTEGER
+            );
+            CREATE TABLE api_errors (
+                id INTEGER PRIMARY KEY,
+                error_type TEXT,
+                error_message TEXT,
+                error_code TEXT,
+                endpoint TEXT,
+                resolved INTEGER
+            );",
+        )?;
+        migrations::migrate(&conn)?;
+        conn.execute_batch("DROP TABLE configurations; DROP TABLE backups;")?;
+
+        let Err(CrawlerError::Config(message)) = check(&conn) else {
+            panic!("expected a config error");
+        };
+        assert!(message.contains(
+            "twitter_users is missing avatar_url, follower_count, following_count, updated_at"
+        ));
+        assert!(message.contains("api_errors is missing occurred_at"));
+        assert!(message.contains("missing table configurations"));
+        assert!(message.contains("missing table backups"));
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+//! Embedded HTTP server for health checks and crawler status
+//!
+//! - `GET /healthz`: the process is up and serving
+//! - `GET /readyz`: the database is reachable, a Twitter token is configured and
+//!   the current crawl cycle (if any) is still making progress
+//! - `GET /status`: stages, live run progress, rate-limit budgets and next run
+//! - `GET /metrics`: Prometheus metrics
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::Utc;
+use metrics_exporter_prometheus::PrometheusHandle;
+use serde::Serialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::db_pool::DbPool;
+use crate::shutdown::Shutdown;
+use crate::status::StatusBoard;
+use crate::telemetry;
+
+#[derive(Clone)]
+pub struct ServerState {
+    pub board: Arc<StatusBoard>,
+    pub metrics: Option<PrometheusHandle>,
+    pub shutdown: Arc<Shutdown>,
+    pub database: DbPool,
+    pub token_configured: bool,
+    pub stall_timeout: Duration,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReadinessChecks {
+    database: bool,
+    twitter_token: bool,
+    cycle_progressing: bool,
+    accepting_work: bool,
+}
+
+impl ReadinessChecks {
+    fn ready(&self) -> bool {
+        self.database && self.twitter_token && self.cycle_progressing && self.accepting_work
+    }
+}
+
+pub fn router(state: ServerState) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/status", get(status))
+        .route("/metrics", get(metrics))
+        .with_state(state)
+}
+
+/// Serve until the task is aborted; the caller stops it once the pipeline has drained
+pub async fn serve(addr: &str, state: ServerState) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("Status server listening on {}", listener.local_addr()?);
+    axum::serve(listener, router(state)).await?;
+    Ok(())
+}
+
+async fn healthz() -> impl IntoResponse {
+    Json(json!({ "status": "ok" }))
+}
+
+async fn readyz(State(state): State<ServerState>) -> impl IntoResponse {
+    let database = state.database.run(|database| database.ping()).await;
+    if let Err(error) = &database {
+        warn!("Readiness check could not reach the database: {}", error);
+    }
+
+    let checks = ReadinessChecks {
+        database: database.is_ok(),
+        twitter_token: state.token_configured,
+        cycle_progressing: !state.board.is_cycle_stalled(Utc::now(), state.stall_timeout),
+        accepting_work: !state.shutdown.is_requested(),
+    };
+    let code = if checks.ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (code, Json(json!({ "ready": checks.ready(), "checks": checks })))
+}
+
+async fn status(State(state): State<ServerState>) -> impl IntoResponse {
+    Json(state.board.snapshot())
+}
+
+async fn metrics(State(state): State<ServerState>) -> impl IntoResponse {
+    let Some(handle) = state.metrics else {
+        return (StatusCode::NOT_FOUND, String::new());
+    };
+
+    // Queue depth is sampled at scrape time rather than tracked on every transition.
+    let depth = state
+        .database
+        .run(|database| database.analysis_queue_depth())
+        .await;
+    match depth {
+        Ok(depth) => {
+            // Statuses with no rows drop out of the query; report them as empty.
+            for status in ["pending", "processing", "completed", "failed"] {
+                telemetry::set_queue_depth(status, 0);
+            }
+            for (status, count) in depth {
+                telemetry::set_queue_depth(&status, count);
+            }
+        }
+        Err(error) => warn!("Could not sample analysis queue depth: {}", error),
+    }
+
+    (StatusCode::OK, handle.render())
+}
@@ -0,0 +1,162 @@
+//! Shutdown signalling shared by the crawler's long-running tasks
+//!
+//! Shutdown happens in two phases. Once requested, stages stop picking up new
+//! work but let in-flight work (the current user, page or analysis job) finish.
+//! When the drain timeout expires, in-flight work is abandoned at the next safe
+//! point after persisting its progress.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+#[derive(Default)]
+pub struct Shutdown {
+    requested: AtomicBool,
+    notify: Notify,
+    drain_expired: AtomicBool,
+    drain_notify: Notify,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    /// Stop draining: in-flight work should checkpoint and return
+    pub fn expire_drain(&self) {
+        self.request();
+        self.drain_expired.store(true, Ordering::SeqCst);
+        self.drain_notify.notify_waiters();
+    }
+
+    pub fn is_drain_expired(&self) -> bool {
+        self.drain_expired.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once the drain timeout has expired
+    pub async fn drain_expired(&self) {
+        let notified = self.drain_notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if self.is_drain_expired() {
+            return;
+        }
+        notified.await;
+    }
+
+    /// Sleep for `duration`; returns `true` if shutdown was requested meanwhile
+    pub async fn sleep(&self, duration: Duration) -> bool {
+        let notified = self.notify.notified();
+        tokio::pin!(notified);
+        // Register interest before checking the flag so a concurrent request is never missed.
+        notified.as_mut().enable();
+        if self.is_requested() {
+            return true;
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => self.is_requested(),
+            _ = notified => true,
+        }
+    }
+}
+
+/// Translate SIGINT/SIGTERM into shutdown requests
+///
+/// The first signal starts draining; the drain expires after `drain_timeout`
+/// or immediately on a second signal.
+pub fn spawn_signal_handler(shutdown: Arc<Shutdown>, drain_timeout: Duration) {
+    tokio::spawn(async move {
+        if wait_for_signal().await.is_err() {
+            warn!("Failed to install shutdown signal handlers");
+            return;
+        }
+        info!(
+            "Shutdown requested, draining in-flight work for up to {}s",
+            drain_timeout.as_secs()
+        );
+        shutdown.request();
+
+        tokio::select! {
+            _ = tokio::time::sleep(drain_timeout) => {
+                warn!("Drain timeout expired, checkpointing in-flight work");
+            }
+            _ = wait_for_signal() => {
+                warn!("Second shutdown signal received, checkpointing in-flight work");
+            }
+        }
+        shutdown.expire_drain();
+    });
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    // Docker stops containers with SIGTERM, interactive sessions use SIGINT.
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result,
+        _ = terminate.recv() => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> std::io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sleep_returns_early_on_request() {
+        let shutdown = Arc::new(Shutdown::new());
+        let waiter = {
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move { shutdown.sleep(Duration::from_secs(3600)).await })
+        };
+
+        tokio::task::yield_now().await;
+        shutdown.request();
+
+        let interrupted = tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .expect("sleep did not wake up")
+            .expect("task panicked");
+        assert!(interrupted);
+    }
+
+    #[tokio::test]
+    async fn sleep_after_request_returns_immediately() {
+        let shutdown = Shutdown::new();
+        shutdown.request();
+        assert!(shutdown.sleep(Duration::from_secs(3600)).await);
+    }
+
+    #[tokio::test]
+    async fn request_does_not_expire_drain() {
+        let shutdown = Shutdown::new();
+        shutdown.request();
+        assert!(!shutdown.is_drain_expired());
+
+        shutdown.expire_drain();
+        assert!(shutdown.is_requested());
+        tokio::time::timeout(Duration::from_secs(5), shutdown.drain_expired())
+            .await
+            .expect("drain_expired did not resolve");
+    }
+}
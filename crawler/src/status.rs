@@ -0,0 +1,254 @@
+//! In-process view of what the crawler is doing, served by the status endpoint
+//!
+//! Stages report into a shared [`StatusBoard`] as they work. Unlike the
+//! `crawler_runs` rows, the board is updated on every progress event and also
+//! knows about stages, rate-limit budgets and the next scheduled run.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::models::RunProgress;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageStatus {
+    pub state: &'static str,
+    pub since: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunStatus {
+    pub run_id: i64,
+    pub started_at: DateTime<Utc>,
+    pub users_total: i64,
+    pub users_completed: i64,
+    pub current_user: Option<String>,
+    pub tweets_fetched: i64,
+    pub jobs_queued: i64,
+    pub errors_count: i64,
+    pub eta_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FinishedRun {
+    pub run_id: i64,
+    pub status: String,
+    pub finished_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateBudget {
+    pub remaining: Option<u32>,
+    pub limit: u32,
+    pub window_seconds: u64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Point-in-time copy of the board, serialized by `/status`
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusSnapshot {
+    pub started_at: Option<DateTime<Utc>>,
+    pub stages: BTreeMap<String, StageStatus>,
+    pub current_run: Option<RunStatus>,
+    pub last_run: Option<FinishedRun>,
+    pub rate_limits: BTreeMap<String, RateBudget>,
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+pub struct StatusBoard {
+    state: Mutex<StatusSnapshot>,
+}
+
+impl Default for StatusBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatusBoard {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(StatusSnapshot {
+                started_at: Some(Utc::now()),
+                ..StatusSnapshot::default()
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, StatusSnapshot> {
+        // The board only holds plain data, so a panicked writer cannot leave it inconsistent.
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn snapshot(&self) -> StatusSnapshot {
+        self.lock().clone()
+    }
+
+    pub fn set_stage(&self, stage: &str, state: &'static str) {
+        let mut board = self.lock();
+        let unchanged = board
+            .stages
+            .get(stage)
+            .is_some_and(|current| current.state == state);
+        if !unchanged {
+            board.stages.insert(
+                stage.to_string(),
+                StageStatus {
+                    state,
+                    since: Utc::now(),
+                },
+            );
+        }
+    }
+
+    pub fn run_started(&self, run_id: i64) {
+        let now = Utc::now();
+        self.lock().current_run = Some(RunStatus {
+            run_id,
+            started_at: now,
+            users_total: 0,
+            users_completed: 0,
+            current_user: None,
+            tweets_fetched: 0,
+            jobs_queued: 0,
+            errors_count: 0,
+            eta_at: None,
+            updated_at: now,
+        });
+    }
+
+    pub fn run_progress(&self, run_id: i64, progress: &RunProgress) {
+        let mut board = self.lock();
+        let Some(run) = board.current_run.as_mut().filter(|run| run.run_id == run_id) else {
+            return;
+        };
+        run.users_total = progress.users_total;
+        run.users_completed = progress.users_completed;
+        run.current_user = progress.current_user.clone();
+        run.tweets_fetched = progress.tweets_fetched;
+        run.jobs_queued = progress.jobs_queued;
+        run.errors_count = progress.errors_count;
+        run.eta_at = progress.eta;
+        run.updated_at = Utc::now();
+    }
+
+    pub fn run_finished(&self, run_id: i64, status: &str) {
+        let mut board = self.lock();
+        if board
+            .current_run
+            .as_ref()
+            .is_some_and(|run| run.run_id == run_id)
+        {
+            board.current_run = None;
+        }
+        board.last_run = Some(FinishedRun {
+            run_id,
+            status: status.to_string(),
+            finished_at: Utc::now(),
+        });
+    }
+
+    pub fn configure_rate_limit(&self, name: &str, limit: u32, window: Duration) {
+        self.lock().rate_limits.insert(
+            name.to_string(),
+            RateBudget {
+                remaining: None,
+                limit,
+                window_seconds: window.as_secs(),
+                updated_at: Utc::now(),
+            },
+        );
+    }
+
+    pub fn set_rate_remaining(&self, name: &str, remaining: u32) {
+        if let Some(budget) = self.lock().rate_limits.get_mut(name) {
+            budget.remaining = Some(remaining);
+            budget.updated_at = Utc::now();
+        }
+    }
+
+    pub fn set_next_run(&self, next_run: Option<DateTime<Utc>>) {
+        self.lock().next_run = next_run;
+    }
+
+    /// Whether a running cycle has made no progress for longer than `timeout`
+    pub fn is_cycle_stalled(&self, now: DateTime<Utc>, timeout: Duration) -> bool {
+        let Some(run) = self.lock().current_run.clone() else {
+            return false;
+        };
+        (now - run.updated_at)
+            .to_std()
+            .is_ok_and(|idle| idle > timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(users_completed: i64) -> RunProgress {
+        RunProgress {
+            users_total: 3,
+            users_completed,
+            current_user: Some("tester".to_string()),
+            tweets_fetched: 10,
+            jobs_queued: 20,
+            errors_count: 0,
+            eta: None,
+        }
+    }
+
+    #[test]
+    fn run_lifecycle_moves_current_run_to_last_run() {
+        let board = StatusBoard::new();
+        board.run_started(7);
+        board.run_progress(7, &progress(1));
+        // Progress for a run that is not current is ignored.
+        board.run_progress(6, &progress(2));
+
+        let snapshot = board.snapshot();
+        let run = snapshot.current_run.expect("current run");
+        assert_eq!(run.users_completed, 1);
+        assert_eq!(run.current_user.as_deref(), Some("tester"));
+
+        board.run_finished(7, "completed");
+        let snapshot = board.snapshot();
+        assert!(snapshot.current_run.is_none());
+        assert_eq!(snapshot.last_run.expect("last run").status, "completed");
+    }
+
+    #[test]
+    fn stalled_only_when_running_without_progress() {
+        let board = StatusBoard::new();
+        let timeout = Duration::from_secs(60);
+        let later = Utc::now() + chrono::Duration::minutes(5);
+        assert!(!board.is_cycle_stalled(later, timeout));
+
+        board.run_started(1);
+        assert!(!board.is_cycle_stalled(Utc::now(), timeout));
+        assert!(board.is_cycle_stalled(later, timeout));
+    }
+
+    #[test]
+    fn rate_remaining_requires_configured_limit() {
+        let board = StatusBoard::new();
+        board.set_rate_remaining("twitter", 5);
+        assert!(board.snapshot().rate_limits.is_empty());
+
+        board.configure_rate_limit("twitter", 450, Duration::from_secs(900));
+        board.set_rate_remaining("twitter", 449);
+        let budget = board.snapshot().rate_limits["twitter"].clone();
+        assert_eq!(budget.remaining, Some(449));
+        assert_eq!(budget.limit, 450);
+    }
+}
@@ -0,0 +1,1144 @@
+//! Storage abstraction over the crawler's database backends
+//!
+//! [`Storage`] covers everything the crawler reads and writes. [`open`] picks
+//! the backend from the `DATABASE_URL` scheme: `postgres://` and
+//! `postgresql://` URLs connect to PostgreSQL, anything else is a path to the
+//! SQLite file shared with the Node backend (an optional `sqlite://` prefix is
+//! stripped).
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::db::SqliteDatabase;
+use crate::db_postgres::PostgresDatabase;
+use crate::error::CrawlerError;
+use crate::models::{
+    AnalysisJob, AnalysisScoreRow, CrawlRunUser, CrawlerRunSummary, FetchProgress, GaugeConfig,
+    GlobalAggregation, LlmModel, ReanalysisChunk, ReanalysisRequest, RunProgress, TrackedUser,
+    TwitterApiTweet, UserAggregation, UserCrawlOutcome,
+};
+use crate::retention::{PruneTarget, VacuumMode};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiErrorDetail {
+    pub error_type: String,
+    pub message: String,
+    pub code: Option<String>,
+    pub endpoint: Option<String>,
+    pub timestamp: String,
+}
+
+/// Open the backend named by `database_url`
+///
+/// `busy_timeout` only applies to SQLite, where it bounds the wait for another
+/// writer's lock.
+pub fn open(database_url: &str, busy_timeout: Duration) -> Result<Box<dyn Storage>, CrawlerError> {
+    if is_postgres_url(database_url) {
+        return Ok(Box::new(PostgresDatabase::connect(database_url)?));
+    }
+    let path = database_url
+        .strip_prefix("sqlite://")
+        .unwrap_or(database_url);
+    Ok(Box::new(SqliteDatabase::new(path, busy_timeout)?))
+}
+
+pub fn is_postgres_url(database_url: &str) -> bool {
+    database_url.starts_with("postgres://") || database_url.starts_with("postgresql://")
+}
+
+/// Path of the SQLite file behind `database_url`; `None` for PostgreSQL and
+/// in-memory databases
+pub fn sqlite_path(database_url: &str) -> Option<PathBuf> {
+    if is_postgres_url(database_url) {
+        return None;
+    }
+    let path = database_url
+        .strip_prefix("sqlite://")
+        .unwrap_or(database_url);
+    (path != ":memory:").then(|| PathBuf::from(path))
+}
+
+/// `database_url` with any password masked, for logging
+pub fn redacted_url(database_url: &str) -> String {
+    let Some((scheme, rest)) = database_url.split_once("://") else {
+        return database_url.to_string();
+    };
+    match rest.split_once('@') {
+        Some((credentials, host)) if credentials.contains(':') => {
+            let user = credentials.split(':').next().unwrap_or_default();
+            format!("{scheme}://{user}:***@{host}")
+        }
+        _ => database_url.to_string(),
+    }
+}
+
+/// `crawler_runs.error_details` JSON, or `None` when the run had no errors
+pub(crate) fn error_details_json(
+    error_details: &[ApiErrorDetail],
+) -> Result<Option<String>, CrawlerError> {
+    if error_details.is_empty() {
+        return Ok(None);
+    }
+    serde_json::to_string(error_details)
+        .map(Some)
+        .map_err(|err| CrawlerError::Config(format!("Could not encode error details: {err}")))
+}
+
+/// How long a claimed reanalysis request is left to the process walking it;
+/// every chunk renews the lease
+pub const REANALYSIS_LEASE_MINUTES: i32 = 10;
+
+/// Everything the crawler persists, implemented once per database backend
+///
+/// Every method blocks; async code goes through [`crate::db_pool::DbPool`].
+pub trait Storage: Send {
+    /// Bring the schema up to date (see [`crate::migrations`]) and verify every
+    /// table and column the crawler uses (see [`crate::schema_compat`])
+    fn init_schema(&self) -> Result<(), CrawlerError>;
+
+    /// Verify the schema like [`Storage::init_schema`], without migrating it
+    fn check_schema(&self) -> Result<(), CrawlerError>;
+
+    /// Highest applied migration version
+    fn schema_version(&self) -> Result<Option<i64>, CrawlerError>;
+
+    /// Latest migration version this build ships for the backend
+    fn latest_schema_version(&self) -> i64;
+
+    /// Cheap round trip used by the readiness probe
+    fn ping(&self) -> Result<(), CrawlerError>;
+
+    fn create_crawler_run(&self) -> Result<i64, CrawlerError>;
+
+    fn complete_crawler_run(
+        &self,
+        run_id: i64,
+        status: &str,
+        tweets_fetched: u64,
+        tweets_analyzed: u64,
+        error_details: &[ApiErrorDetail],
+    ) -> Result<(), CrawlerError>;
+
+    fn update_crawler_run_progress(
+        &self,
+        run_id: i64,
+        progress: &RunProgress,
+    ) -> Result<(), CrawlerError>;
+
+    /// Fail runs left `running` by a crawler that died mid-cycle, i.e. whose
+    /// progress has not moved for `stale_minutes`; returns how many
+    fn fail_abandoned_runs(&self, stale_minutes: u64) -> Result<u64, CrawlerError>;
+
+    /// Persist the daemon's next fire time so the admin dashboard can show it
+    fn set_next_scheduled_run(
+        &self,
+        schedule: &str,
+        next_run: Option<DateTime<Utc>>,
+    ) -> Result<(), CrawlerError>;
+
+    /// Returns the id of the new `api_errors` row
+    fn insert_api_error(
+        &self,
+        error_type: &str,
+        error_message: &str,
+        error_code: Option<&str>,
+        endpoint: Option<&str>,
+    ) -> Result<i64, CrawlerError>;
+
+    fn record_run_user(
+        &self,
+        run_id: i64,
+        twitter_user_id: i64,
+        outcome: &UserCrawlOutcome,
+    ) -> Result<(), CrawlerError>;
+
+    /// A user's most recent crawl outcomes, newest run first
+    fn user_crawl_history(
+        &self,
+        twitter_user_id: i64,
+        limit: i64,
+    ) -> Result<Vec<CrawlRunUser>, CrawlerError>;
+
+    /// Per-user outcomes of one crawl run
+    fn run_user_outcomes(&self, run_id: i64) -> Result<Vec<CrawlRunUser>, CrawlerError>;
+
+    /// When the user's most recent completed crawl started
+    fn last_crawl_started(
+        &self,
+        twitter_user_id: i64,
+    ) -> Result<Option<DateTime<Utc>>, CrawlerError>;
+
+    fn load_active_users(&self) -> Result<Vec<TrackedUser>, CrawlerError>;
+
+    /// Case-insensitive lookup by username
+    fn find_user_id(&self, username: &str) -> Result<Option<i64>, CrawlerError>;
+
+    fn update_user_from_api(
+        &self,
+        username: &str,
+        twitter_id: &str,
+        display_name: Option<&str>,
+        avatar_url: Option<&str>,
+        follower_count: Option<u64>,
+        following_count: Option<u64>,
+    ) -> Result<(), CrawlerError>;
+
+    fn get_enabled_model_ids(&self) -> Result<Vec<i64>, CrawlerError>;
+
+    /// Every `llm_models` row, by id
+    fn load_models(&self) -> Result<Vec<LlmModel>, CrawlerError>;
+
+    fn get_checkpoint(&self, twitter_user_id: i64) -> Result<Option<DateTime<Utc>>, CrawlerError>;
+
+    fn set_checkpoint(
+        &self,
+        twitter_user_id: i64,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), CrawlerError>;
+
+    fn get_fetch_progress(
+        &self,
+        twitter_user_id: i64,
+    ) -> Result<Option<FetchProgress>, CrawlerError>;
+
+    fn save_fetch_progress(
+        &self,
+        twitter_user_id: i64,
+        progress: &FetchProgress,
+    ) -> Result<(), CrawlerError>;
+
+    fn clear_fetch_progress(&self, twitter_user_id: i64) -> Result<(), CrawlerError>;
+
+    /// Insert a page of tweets and queue analysis jobs for the new ones, in one
+    /// transaction; returns `(tweets inserted, jobs queued, newest timestamp)`
+    ///
+    /// New tweets of a type in `skip_analysis` are stored without jobs.
+    fn insert_tweets_and_enqueue(
+        &self,
+        twitter_user_id: i64,
+        tweets: &[TwitterApiTweet],
+        enabled_model_ids: &[i64],
+        skip_analysis: TweetTypes,
+    ) -> Result<(u64, u64, Option<DateTime<Utc>>), CrawlerError>;
+
+    /// Tweets covered by a reanalysis request's tweet, user and time range scope
+    ///
+    /// Like the other reanalysis queries it leaves out tweets of a type in
+    /// `skip_analysis`, unless the request names a single tweet.
+    fn count_reanalysis_tweets(
+        &self,
+        request: &ReanalysisRequest,
+        skip_analysis: TweetTypes,
+    ) -> Result<i64, CrawlerError>;
+
+    /// Jobs the request would queue or reset right now, across its whole scope
+    fn estimate_reanalysis_jobs(
+        &self,
+        request: &ReanalysisRequest,
+        model_ids: &[i64],
+        skip_analysis: TweetTypes,
+    ) -> Result<i64, CrawlerError>;
+
+    /// Queue jobs for up to `chunk_size` tweets in the request's scope with ids
+    /// above `after_tweet_id`, advancing its cursor, progress and job count and
+    /// renewing its lease in the same transaction. Reanalysis resets completed or failed jobs to `pending`
+    /// rather than skipping them, and tweet/model pairs outside the request's
+    /// failed-only or missing-emotion filters are left alone.
+    fn enqueue_reanalysis_chunk(
+        &self,
+        request: &ReanalysisRequest,
+        after_tweet_id: i64,
+        chunk_size: i64,
+        model_ids: &[i64],
+        skip_analysis: TweetTypes,
+    ) -> Result<ReanalysisChunk, CrawlerError>;
+
+    /// Claim up to `limit` pending or `processing` requests, oldest first, for
+    /// [`REANALYSIS_LEASE_MINUTES`]; a request another process holds a live
+    /// lease on is left to it. A row that cannot be read is marked `failed` and
+    /// skipped.
+    fn claim_reanalysis_requests(&self, limit: i64)
+        -> Result<Vec<ReanalysisRequest>, CrawlerError>;
+
+    /// Give up the lease on a request interrupted by shutdown, so the next
+    /// dispatcher resumes it right away
+    fn release_reanalysis_request(&self, request_id: i64) -> Result<(), CrawlerError>;
+
+    /// `tweets_total` is only recorded the first time a request starts
+    fn mark_reanalysis_processing(
+        &self,
+        request_id: i64,
+        tweets_total: Option<i64>,
+    ) -> Result<(), CrawlerError>;
+
+    fn mark_reanalysis_completed(&self, request_id: i64) -> Result<(), CrawlerError>;
+
+    /// Close a request that could not be processed, keeping the reason
+    fn mark_reanalysis_failed(
+        &self,
+        request_id: i64,
+        error_message: &str,
+    ) -> Result<(), CrawlerError>;
+
+    /// Close a dry run as `estimated`; an admin confirms it by setting it back
+    /// to `pending` with `dry_run` cleared
+    fn record_reanalysis_estimate(
+        &self,
+        request_id: i64,
+        jobs_estimate: i64,
+    ) -> Result<(), CrawlerError>;
+
+    fn latest_crawler_run(&self) -> Result<Option<CrawlerRunSummary>, CrawlerError>;
+
+    /// Parsed JSON value of a `configurations` row
+    fn load_configuration(&self, key: &str) -> Result<Option<serde_json::Value>, CrawlerError> {
+        Ok(self
+            .load_stored_configuration(key)?
+            .map(|stored| stored.value))
+    }
+
+    /// A `configurations` row with who last saved it
+    fn load_stored_configuration(
+        &self,
+        key: &str,
+    ) -> Result<Option<StoredConfiguration>, CrawlerError>;
+
+    /// Emotion keys from the `emotions` configuration, in a stable order
+    fn load_emotion_names(&self) -> Result<Vec<String>, CrawlerError> {
+        let value = self.load_configuration("emotions")?;
+        let names = value
+            .as_ref()
+            .and_then(|value| value.as_object())
+            .map(|object| object.keys().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        Ok(names)
+    }
+
+    fn load_gauges(&self) -> Result<Vec<GaugeConfig>, CrawlerError> {
+        let Some(value) = self.load_configuration("gauges")? else {
+            return Ok(Vec::new());
+        };
+        serde_json::from_value(value)
+            .map_err(|err| CrawlerError::Config(format!("Invalid gauges configuration: {err}")))
+    }
+
+    /// Claim pending jobs for enabled models and mark them `processing`
+    ///
+    /// Concurrent workers never pick up the same job.
+    fn claim_analysis_jobs(
+        &self,
+        limit: i64,
+        llm_model_id: Option<i64>,
+    ) -> Result<Vec<AnalysisJob>, CrawlerError>;
+
+    /// Store the analysis as the current version for its tweet and model; the
+    /// previous one is kept as history with `is_current` cleared
+    fn complete_analysis_job(
+        &self,
+        job: &AnalysisJob,
+        emotion_scores: &str,
+        raw_llm_response: &str,
+        analysis_duration_ms: i64,
+    ) -> Result<(), CrawlerError>;
+
+    /// Record a failed attempt; the job goes back to `pending` when `retry` is set
+    fn fail_analysis_job(
+        &self,
+        job_id: i64,
+        error_message: &str,
+        retry: bool,
+    ) -> Result<(), CrawlerError>;
+
+    /// Analysis queue row counts by status
+    fn analysis_queue_depth(&self) -> Result<Vec<(String, i64)>, CrawlerError>;
+
+    /// Highest `sentiment_analyses.id`, used to skip aggregation when nothing changed
+    fn latest_analysis_id(&self) -> Result<Option<i64>, CrawlerError>;
+
+    /// Hand a claimed job back untouched (e.g. on shutdown) without counting the attempt
+    fn release_analysis_job(&self, job_id: i64) -> Result<(), CrawlerError>;
+
+    /// Scores of current analyses only
+    fn load_analysis_scores(&self) -> Result<Vec<AnalysisScoreRow>, CrawlerError>;
+
+    /// Swap in freshly computed aggregations in one transaction
+    fn replace_aggregations(
+        &self,
+        user_rows: &[UserAggregation],
+        global_rows: &[GlobalAggregation],
+    ) -> Result<(), CrawlerError>;
+
+    /// Delete (or, for run error details, clear) up to `batch_size` rows of
+    /// `target` older than `cutoff` in one transaction; returns the rows touched
+    fn prune_batch(
+        &self,
+        target: PruneTarget,
+        cutoff: DateTime<Utc>,
+        batch_size: i64,
+    ) -> Result<u64, CrawlerError>;
+
+    /// Bytes the database occupies on disk
+    fn database_size_bytes(&self) -> Result<i64, CrawlerError>;
+
+    /// Return space freed by pruning; `VacuumMode::Off` does nothing
+    fn vacuum(&self, mode: VacuumMode) -> Result<(), CrawlerError>;
+
+    /// Run raw SQL, for test fixtures
+    #[cfg(test)]
+    fn execute_sql(&self, sql: &str) -> Result<(), CrawlerError>;
+
+    /// First column of the first row as text, for test assertions
+    #[cfg(test)]
+    fn query_text(&self, sql: &str) -> Result<Option<String>, CrawlerError>;
+}
+
+/// A `configurations` row
+#[derive(Debug, Clone)]
+pub struct StoredConfiguration {
+    pub value: serde_json::Value,
+    /// The admin who saved the row; `None` for values seeded by `schema.sql`
+    pub updated_by: Option<String>,
+}
+
+/// Whether any referenced tweet has the given type (`retweeted`, `replied_to`)
+pub(crate) fn references(tweet: &TwitterApiTweet, reference_type: &str) -> bool {
+    tweet
+        .referenced_tweets
+        .as_ref()
+        .map(|refs| refs.iter().any(|r| r.reference_type == reference_type))
+        .unwrap_or(false)
+}
+
+/// Tweet types that can be filtered out (`replies`, `retweets`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TweetTypes {
+    pub replies: bool,
+    pub retweets: bool,
+}
+
+impl TweetTypes {
+    /// Parse a comma-separated list such as `replies,retweets`; blank or
+    /// `none` is the empty set
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut types = Self::default();
+        for name in value.split(',').map(str::trim) {
+            match name.to_ascii_lowercase().as_str() {
+                "replies" => types.replies = true,
+                "retweets" => types.retweets = true,
+                "none" | "" => {}
+                _ => return None,
+            }
+        }
+        Some(types)
+    }
+
+    /// The types as the timeline endpoint's `exclude` parameter names them
+    pub fn names(self) -> Vec<&'static str> {
+        [(self.replies, "replies"), (self.retweets, "retweets")]
+            .into_iter()
+            .filter(|(included, _)| *included)
+            .map(|(_, name)| name)
+            .collect()
+    }
+
+    /// Whether `tweet` is of one of the types
+    pub fn contains(self, tweet: &TwitterApiTweet) -> bool {
+        (self.replies && references(tweet, "replied_to"))
+            || (self.retweets && references(tweet, "retweeted"))
+    }
+}
+
+/// SQL appended to a reanalysis scope over tweets `t` that leaves out the
+/// types in `skip`; a request for one tweet is never narrowed
+pub(crate) fn skipped_tweet_types(request: &ReanalysisRequest, skip: TweetTypes) -> String {
+    if reanalysis_tweet(request).is_some() {
+        return String::new();
+    }
+    let mut filter = String::new();
+    if skip.replies {
+        filter.push_str(" AND NOT COALESCE(t.is_reply, FALSE)");
+    }
+    if skip.retweets {
+        filter.push_str(" AND NOT COALESCE(t.is_retweet, FALSE)");
+    }
+    filter
+}
+
+/// Tweet a `tweet` reanalysis request is scoped to
+pub(crate) fn reanalysis_tweet(request: &ReanalysisRequest) -> Option<i64> {
+    if request.request_type == "tweet" {
+        request.tweet_id
+    } else {
+        None
+    }
+}
+
+/// User a `user` reanalysis request is scoped to; `None` covers every tweet
+pub(crate) fn reanalysis_user(request: &ReanalysisRequest) -> Option<i64> {
+    if request.request_type == "user" {
+        request.twitter_user_id
+    } else {
+        None
+    }
+}
+
+/// `tweets.engagement_metrics` JSON
+pub(crate) fn engagement_json(tweet: &TwitterApiTweet) -> String {
+    let metrics = tweet.public_metrics.as_ref();
+    serde_json::json!({
+        "likes": metrics.and_then(|m| m.like_count).unwrap_or(0),
+        "retweets": metrics.and_then(|m| m.retweet_count).unwrap_or(0),
+        "replies": metrics.and_then(|m| m.reply_count).unwrap_or(0),
+        "quotes": metrics.and_then(|m| m.quote_count).unwrap_or(0),
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// A migrated in-memory SQLite database, plus PostgreSQL in a throwaway
+    /// schema when `TEST_POSTGRES_URL` is set; both have tracked user 5
+    fn backends() -> Result<Vec<Box<dyn Storage>>, CrawlerError> {
+        let mut backends: Vec<Box<dyn Storage>> = vec![Box::new(SqliteDatabase::new(
+            ":memory:",
+            Duration::from_secs(1),
+        )?)];
+        match std::env::var("TEST_POSTGRES_URL") {
+            Ok(url) => backends.push(Box::new(PostgresDatabase::connect_isolated(&url)?)),
+            Err(_) => eprintln!("TEST_POSTGRES_URL is not set; skipping PostgreSQL"),
+        }
+
+        for backend in &backends {
+            backend.init_schema()?;
+            backend.execute_sql(
+                "INSERT INTO twitter_users (id, twitter_id, username, display_name, is_active)
+                 VALUES (5, 'user_5', 'user5', 'User Five', TRUE)",
+            )?;
+        }
+        Ok(backends)
+    }
+
+    fn tweet(id: &str) -> TwitterApiTweet {
+        TwitterApiTweet {
+            id: id.to_string(),
+            text: "hello".to_string(),
+            created_at: Utc.with_ymd_and_hms(2024, 1, 10, 12, 0, 0).unwrap(),
+            public_metrics: None,
+            referenced_tweets: None,
+        }
+    }
+
+    #[test]
+    fn postgres_urls_select_postgres() {
+        assert!(is_postgres_url("postgres://crawler@localhost/feels"));
+        assert!(is_postgres_url("postgresql://localhost/feels"));
+        assert!(!is_postgres_url("../backend/data/twitter_feels.db"));
+        assert!(!is_postgres_url("sqlite://data/postgres.db"));
+        assert_eq!(
+            redacted_url("postgres://crawler:secret@db:5432/feels"),
+            "postgres://crawler:***@db:5432/feels"
+        );
+        assert_eq!(redacted_url("data/feels.db"), "data/feels.db");
+    }
+
+    #[test]
+    fn insert_tweets_ignores_duplicates() -> Result<(), CrawlerError> {
+        for db in backends()? {
+            let (first_inserted, first_jobs, latest) =
+                db.insert_tweets_and_enqueue(5, &[tweet("tweet_1")], &[], TweetTypes::default())?;
+            let (second_inserted, second_jobs, _) =
+                db.insert_tweets_and_enqueue(5, &[tweet("tweet_1")], &[], TweetTypes::default())?;
+
+            assert_eq!(first_inserted, 1);
+            assert_eq!(first_jobs, 1);
+            assert_eq!(latest, Some(tweet("tweet_1").created_at));
+            assert_eq!(second_inserted, 0);
+            assert_eq!(second_jobs, 0);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn skipped_tweet_types_are_stored_without_jobs() -> Result<(), CrawlerError> {
+        let referencing = |id: &str, reference_type: &str| TwitterApiTweet {
+            referenced_tweets: Some(vec![crate::models::TwitterReferencedTweet {
+                reference_type: reference_type.to_string(),
+                id: "tweet_0".to_string(),
+            }]),
+            ..tweet(id)
+        };
+        let tweets = [
+            tweet("tweet_1"),
+            referencing("tweet_2", "retweeted"),
+            referencing("tweet_3", "replied_to"),
+        ];
+        let skip = TweetTypes {
+            retweets: true,
+            ..TweetTypes::default()
+        };
+        for db in backends()? {
+            let (inserted, jobs, _) = db.insert_tweets_and_enqueue(5, &tweets, &[], skip)?;
+            assert_eq!((inserted, jobs), (3, 2));
+            assert_eq!(
+                db.query_text(
+                    "SELECT CAST(COUNT(*) AS TEXT) FROM tweets t JOIN analysis_queue q ON q.tweet_id = t.id
+                     WHERE t.tweet_id = 'tweet_2'"
+                )?
+                .as_deref(),
+                Some("0")
+            );
+        }
+        assert_eq!(
+            TweetTypes::parse(" Retweets, replies"),
+            Some(TweetTypes {
+                replies: true,
+                retweets: true
+            })
+        );
+        assert_eq!(TweetTypes::parse("none"), Some(TweetTypes::default()));
+        assert_eq!(TweetTypes::parse("quotes"), None);
+        assert_eq!(skip.names(), ["retweets"]);
+        Ok(())
+    }
+
+    #[test]
+    fn checkpoint_updates_with_latest_timestamp() -> Result<(), CrawlerError> {
+        for db in backends()? {
+            let older = Utc::now() - chrono::Duration::days(2);
+            let newer = Utc::now() - chrono::Duration::days(1);
+
+            db.set_checkpoint(5, older)?;
+            db.set_checkpoint(5, newer)?;
+
+            let stored = db.get_checkpoint(5)?.expect("checkpoint missing");
+            assert_eq!(stored.timestamp(), newer.timestamp());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn fetch_progress_round_trips_and_clears() -> Result<(), CrawlerError> {
+        for db in backends()? {
+            let progress = FetchProgress {
+                start_time: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+                end_time: None,
+                pagination_token: "next_page".to_string(),
+                newest_timestamp: Some(Utc.with_ymd_and_hms(2024, 1, 10, 12, 0, 0).unwrap()),
+            };
+
+            db.save_fetch_progress(5, &progress)?;
+            assert_eq!(db.get_fetch_progress(5)?, Some(progress));
+
+            db.clear_fetch_progress(5)?;
+            assert_eq!(db.get_fetch_progress(5)?, None);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn run_progress_is_written_live() -> Result<(), CrawlerError> {
+        for db in backends()? {
+            let run_id = db.create_crawler_run()?;
+            db.update_crawler_run_progress(
+                run_id,
+                &RunProgress {
+                    users_total: 4,
+                    users_completed: 1,
+                    current_user: Some("tester".to_string()),
+                    tweets_fetched: 12,
+                    jobs_queued: 24,
+                    errors_count: 0,
+                    eta: Some(Utc.with_ymd_and_hms(2024, 1, 1, 1, 0, 0).unwrap()),
+                },
+            )?;
+
+            let column = |name: &str| {
+                db.query_text(&format!(
+                    "SELECT CAST({name} AS TEXT) FROM crawler_runs WHERE id = {run_id}"
+                ))
+            };
+            assert_eq!(column("users_completed")?.as_deref(), Some("1"));
+            assert_eq!(column("\"current_user\"")?.as_deref(), Some("tester"));
+            assert_eq!(column("jobs_queued")?.as_deref(), Some("24"));
+            assert!(column("eta_at")?.is_some_and(|eta| eta.starts_with("2024-01-01")));
+
+            db.complete_crawler_run(run_id, "completed", 12, 24, &[])?;
+            let run = db.latest_crawler_run()?.expect("run missing");
+            assert_eq!(run.status, "completed");
+            assert!(run.completed_at.is_some());
+            assert_eq!(column("\"current_user\"")?, None);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn only_stalled_runs_are_failed_as_abandoned() -> Result<(), CrawlerError> {
+        for db in backends()? {
+            let abandoned = db.create_crawler_run()?;
+            let live = db.create_crawler_run()?;
+            db.execute_sql(&format!(
+                "UPDATE crawler_runs SET progress_updated_at = '2000-01-01 00:00:00'
+                 WHERE id = {abandoned}"
+            ))?;
+
+            assert_eq!(db.fail_abandoned_runs(30)?, 1);
+            let status = |id: i64| {
+                db.query_text(&format!("SELECT status FROM crawler_runs WHERE id = {id}"))
+            };
+            assert_eq!(status(abandoned)?.as_deref(), Some("failed"));
+            assert_eq!(status(live)?.as_deref(), Some("running"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn crawl_history_lists_newest_run_first() -> Result<(), CrawlerError> {
+        for db in backends()? {
+            let first_run = db.create_crawler_run()?;
+            let second_run = db.create_crawler_run()?;
+            let error_id = db.insert_api_error("network", "timeout", None, None)?;
+
+            let mut outcome = UserCrawlOutcome {
+                status: "completed",
+                tweets_fetched: 30,
+                pages_fetched: 1,
+                api_calls: 1,
+                duration_ms: 120,
+                api_error_id: None,
+                error_message: None,
+            };
+            db.record_run_user(first_run, 5, &outcome)?;
+            outcome.status = "failed";
+            outcome.api_error_id = Some(error_id);
+            outcome.error_message = Some("timeout".to_string());
+            db.record_run_user(second_run, 5, &outcome)?;
+
+            assert_eq!(db.find_user_id("USER5")?, Some(5));
+            let history = db.user_crawl_history(5, 10)?;
+            assert_eq!(history.len(), 2);
+            assert_eq!(history[0].run_id, second_run);
+            assert_eq!(history[0].status, "failed");
+            assert_eq!(history[0].api_error_id, Some(error_id));
+            assert_eq!(history[1].tweets_fetched, 30);
+
+            assert_eq!(db.run_user_outcomes(first_run)?.len(), 1);
+
+            // Only the completed crawl counts, dated from when it started.
+            let started = db.last_crawl_started(5)?.expect("a completed crawl");
+            assert!((Utc::now() - started).num_seconds().abs() < 60);
+            assert_eq!(db.last_crawl_started(6)?, None);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn reanalysis_leaves_skipped_tweet_types_alone() -> Result<(), CrawlerError> {
+        let mut retweet = tweet("tweet_2");
+        retweet.referenced_tweets = Some(vec![crate::models::TwitterReferencedTweet {
+            reference_type: "retweeted".to_string(),
+            id: "tweet_0".to_string(),
+        }]);
+        let skip = TweetTypes {
+            retweets: true,
+            ..TweetTypes::default()
+        };
+        for db in backends()? {
+            db.execute_sql(
+                "INSERT INTO llm_models (id, name, huggingface_model_id, is_enabled)
+                 VALUES (1, 'Model', 'org/model', TRUE);
+                 INSERT INTO reanalysis_requests (request_type) VALUES ('all')",
+            )?;
+            db.insert_tweets_and_enqueue(5, &[tweet("tweet_1"), retweet.clone()], &[1], skip)?;
+            db.execute_sql("UPDATE analysis_queue SET status = 'completed'")?;
+            let retweet_jobs = || {
+                db.query_text(
+                    "SELECT CAST(COUNT(*) AS TEXT) FROM analysis_queue q
+                     JOIN tweets t ON t.id = q.tweet_id
+                     WHERE t.tweet_id = 'tweet_2'",
+                )
+            };
+
+            let request = db.claim_reanalysis_requests(10)?.remove(0);
+            assert_eq!(db.count_reanalysis_tweets(&request, skip)?, 1);
+            assert_eq!(
+                db.count_reanalysis_tweets(&request, TweetTypes::default())?,
+                2
+            );
+            let chunk = db.enqueue_reanalysis_chunk(&request, 0, 10, &[1], skip)?;
+            assert_eq!((chunk.tweets, chunk.jobs_enqueued), (1, 1));
+            assert_eq!(retweet_jobs()?.as_deref(), Some("0"));
+
+            // A request for the retweet itself is honoured.
+            let tweet_id = db
+                .query_text("SELECT CAST(id AS TEXT) FROM tweets WHERE tweet_id = 'tweet_2'")?
+                .unwrap_or_default();
+            db.execute_sql(&format!(
+                "UPDATE reanalysis_requests SET status = 'completed';
+                 INSERT INTO reanalysis_requests (request_type, tweet_id) VALUES ('tweet', {tweet_id})"
+            ))?;
+            let request = db.claim_reanalysis_requests(10)?.remove(0);
+            db.enqueue_reanalysis_chunk(&request, 0, 10, &[1], skip)?;
+            assert_eq!(retweet_jobs()?.as_deref(), Some("1"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn reanalysis_chunks_resume_from_stored_cursor() -> Result<(), CrawlerError> {
+        for db in backends()? {
+            db.execute_sql(
+                "INSERT INTO llm_models (id, name, huggingface_model_id, is_enabled)
+                 VALUES (1, 'Model', 'org/model', TRUE);
+                 INSERT INTO reanalysis_requests (request_type, twitter_user_id) VALUES ('user', 5)",
+            )?;
+            let tweets = [tweet("tweet_1"), tweet("tweet_2"), tweet("tweet_3")];
+            db.insert_tweets_and_enqueue(5, &tweets, &[], TweetTypes::default())?;
+
+            let request = db.claim_reanalysis_requests(10)?.remove(0);
+            let total = db.count_reanalysis_tweets(&request, TweetTypes::default())?;
+            assert_eq!(total, 3);
+            db.mark_reanalysis_processing(request.id, Some(total))?;
+            let first = db.enqueue_reanalysis_chunk(&request, 0, 2, &[1], TweetTypes::default())?;
+            assert_eq!((first.tweets, first.jobs_enqueued), (2, 2));
+
+            // Another process cannot take the request while the lease runs...
+            assert!(db.claim_reanalysis_requests(10)?.is_empty());
+            // ...but one whose walker died is picked up again where it stopped
+            // once the lease has expired, and a released one straight away.
+            db.execute_sql(
+                "UPDATE reanalysis_requests SET lease_expires_at = '2000-01-01 00:00:00'",
+            )?;
+            let expired = db.claim_reanalysis_requests(10)?.remove(0);
+            assert_eq!(expired.cursor_tweet_id, first.last_tweet_id);
+            db.release_reanalysis_request(expired.id)?;
+            let resumed = db.claim_reanalysis_requests(10)?.remove(0);
+            assert_eq!(resumed.cursor_tweet_id, first.last_tweet_id);
+            assert_eq!(resumed.tweets_total, Some(3));
+            assert_eq!(
+                db.query_text(
+                    "SELECT CAST(COUNT(*) AS TEXT) FROM reanalysis_requests
+                     WHERE tweets_processed = 2 AND jobs_enqueued = 2
+                       AND progress_percent BETWEEN 66 AND 67"
+                )?
+                .as_deref(),
+                Some("1")
+            );
+
+            let cursor = resumed.cursor_tweet_id.unwrap_or_default();
+            let second =
+                db.enqueue_reanalysis_chunk(&resumed, cursor, 2, &[1], TweetTypes::default())?;
+            assert_eq!((second.tweets, second.jobs_enqueued), (1, 1));
+            let last = db.enqueue_reanalysis_chunk(
+                &resumed,
+                second.last_tweet_id.unwrap(),
+                2,
+                &[1],
+                TweetTypes::default(),
+            )?;
+            assert_eq!((last.tweets, last.last_tweet_id), (0, None));
+
+            db.mark_reanalysis_completed(request.id)?;
+            assert!(db.claim_reanalysis_requests(10)?.is_empty());
+
+            db.execute_sql("INSERT INTO reanalysis_requests (request_type) VALUES ('all')")?;
+            let failed = db.claim_reanalysis_requests(10)?.remove(0);
+            db.mark_reanalysis_failed(failed.id, "boom")?;
+            assert!(db.claim_reanalysis_requests(10)?.is_empty());
+            assert_eq!(
+                db.query_text(&format!(
+                    "SELECT status || ':' || error_message FROM reanalysis_requests WHERE id = {}",
+                    failed.id
+                ))?
+                .as_deref(),
+                Some("failed:boom")
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn reanalysis_supersedes_the_current_analysis() -> Result<(), CrawlerError> {
+        for db in backends()? {
+            db.execute_sql(
+                "INSERT INTO llm_models (id, name, version, huggingface_model_id, is_enabled)
+                 VALUES (1, 'Model', 'v1', 'org/model', TRUE)",
+            )?;
+            db.insert_tweets_and_enqueue(5, &[tweet("tweet_1")], &[1], TweetTypes::default())?;
+            let job = db.claim_analysis_jobs(10, None)?.remove(0);
+            db.complete_analysis_job(&job, r#"{"joy":10}"#, "{}", 10)?;
+            db.execute_sql(&format!(
+                "INSERT INTO reanalysis_requests (request_type, tweet_id) VALUES ('tweet', {})",
+                job.tweet_id
+            ))?;
+            let request = db.claim_reanalysis_requests(10)?.remove(0);
+
+            // The completed job goes back to pending once; a repeat is a no-op while it waits.
+            assert_eq!(
+                db.enqueue_reanalysis_chunk(&request, 0, 10, &[1], TweetTypes::default())?
+                    .jobs_enqueued,
+                1
+            );
+            assert_eq!(
+                db.enqueue_reanalysis_chunk(&request, 0, 10, &[1], TweetTypes::default())?
+                    .jobs_enqueued,
+                0
+            );
+
+            db.execute_sql("UPDATE llm_models SET version = 'v2' WHERE id = 1")?;
+            let rerun = db.claim_analysis_jobs(10, Some(1))?.remove(0);
+            assert_eq!(rerun.id, job.id);
+            assert_eq!(rerun.attempt_count, 1);
+            db.complete_analysis_job(&rerun, r#"{"joy":90}"#, "{}", 10)?;
+
+            let text = |sql: &str| db.query_text(sql);
+            assert_eq!(
+                text("SELECT CAST(COUNT(*) AS TEXT) FROM sentiment_analyses")?.as_deref(),
+                Some("2")
+            );
+            assert_eq!(
+                text(
+                    "SELECT model_version || ':' || CAST(analysis_version AS TEXT)
+                     FROM sentiment_analyses WHERE is_current = TRUE"
+                )?
+                .as_deref(),
+                Some("v2:2")
+            );
+            let scores = db.load_analysis_scores()?;
+            assert_eq!(scores.len(), 1);
+            assert_eq!(scores[0].emotion_scores, r#"{"joy":90}"#);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn reanalysis_scope_narrows_the_jobs_it_queues() -> Result<(), CrawlerError> {
+        for db in backends()? {
+            db.execute_sql(
+                "INSERT INTO llm_models (id, name, huggingface_model_id, is_enabled)
+                 VALUES (1, 'Model', 'org/model', TRUE), (2, 'Other', 'org/other', TRUE);
+                 INSERT INTO reanalysis_requests
+                     (request_type, llm_model_id, tweets_from, only_failed, dry_run)
+                 VALUES ('all', 1, '2024-02-01', TRUE, TRUE)",
+            )?;
+            let mut later = tweet("tweet_2");
+            later.created_at = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+            db.insert_tweets_and_enqueue(
+                5,
+                &[tweet("tweet_1"), later],
+                &[1, 2],
+                TweetTypes::default(),
+            )?;
+
+            // Model 1 analysed tweet_1 before "anger" existed and failed on tweet_2.
+            let jobs = db.claim_analysis_jobs(10, Some(1))?;
+            db.complete_analysis_job(&jobs[0], r#"{"joy":10}"#, "{}", 10)?;
+            db.fail_analysis_job(jobs[1].id, "timeout", false)?;
+            db.execute_sql(
+                "UPDATE analysis_queue SET status = 'completed' WHERE llm_model_id = 2",
+            )?;
+
+            let request = db.claim_reanalysis_requests(10)?.remove(0);
+            assert_eq!(request.llm_model_id, Some(1));
+            assert_eq!(
+                request.tweets_from,
+                Some(Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap())
+            );
+            assert!(request.only_failed && request.dry_run);
+            assert_eq!(
+                db.count_reanalysis_tweets(&request, TweetTypes::default())?,
+                1
+            );
+            assert_eq!(
+                db.estimate_reanalysis_jobs(&request, &[1], TweetTypes::default())?,
+                1
+            );
+
+            let all = |change: fn(&mut ReanalysisRequest)| {
+                let mut request = ReanalysisRequest {
+                    id: 0,
+                    request_type: "all".to_string(),
+                    tweet_id: None,
+                    twitter_user_id: None,
+                    cursor_tweet_id: None,
+                    tweets_total: None,
+                    llm_model_id: None,
+                    tweets_from: None,
+                    tweets_to: None,
+                    only_failed: false,
+                    missing_emotion: None,
+                    dry_run: false,
+                };
+                change(&mut request);
+                request
+            };
+            assert_eq!(
+                db.estimate_reanalysis_jobs(&all(|_| {}), &[1, 2], TweetTypes::default())?,
+                4
+            );
+            assert_eq!(
+                db.estimate_reanalysis_jobs(
+                    &all(|r| r.only_failed = true),
+                    &[1, 2],
+                    TweetTypes::default()
+                )?,
+                1
+            );
+            assert_eq!(
+                db.estimate_reanalysis_jobs(
+                    &all(|r| r.tweets_to = Some(Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap())),
+                    &[1, 2],
+                    TweetTypes::default(),
+                )?,
+                2
+            );
+            let missing_joy = all(|r| r.missing_emotion = Some("joy".to_string()));
+            let missing_anger = all(|r| r.missing_emotion = Some("anger".to_string()));
+            assert_eq!(
+                db.estimate_reanalysis_jobs(&missing_joy, &[1], TweetTypes::default())?,
+                1
+            );
+            assert_eq!(
+                db.estimate_reanalysis_jobs(&missing_anger, &[1], TweetTypes::default())?,
+                2
+            );
+
+            // A dry run only records its estimate and is not picked up again.
+            db.record_reanalysis_estimate(request.id, 1)?;
+            assert!(db.claim_reanalysis_requests(10)?.is_empty());
+            assert_eq!(
+                db.query_text(
+                    "SELECT status || ':' || CAST(jobs_estimate AS TEXT) FROM reanalysis_requests"
+                )?
+                .as_deref(),
+                Some("estimated:1")
+            );
+
+            // Confirmed, it resets just the failed model 1 job on tweet_2.
+            db.execute_sql("UPDATE reanalysis_requests SET status = 'pending', dry_run = FALSE")?;
+            let confirmed = db.claim_reanalysis_requests(10)?.remove(0);
+            assert!(!confirmed.dry_run);
+            let chunk =
+                db.enqueue_reanalysis_chunk(&confirmed, 0, 10, &[1], TweetTypes::default())?;
+            assert_eq!((chunk.tweets, chunk.jobs_enqueued), (1, 1));
+            assert_eq!(
+                db.query_text(
+                    "SELECT CAST(COUNT(*) AS TEXT) FROM analysis_queue WHERE status = 'pending'"
+                )?
+                .as_deref(),
+                Some("1")
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn pruning_deletes_expired_rows_in_batches() -> Result<(), CrawlerError> {
+        use crate::retention::{prune, RetentionPolicy, VacuumMode};
+        use crate::shutdown::Shutdown;
+
+        for db in backends()? {
+            db.execute_sql(
+                "INSERT INTO llm_models (id, name, huggingface_model_id, is_enabled)
+                 VALUES (1, 'Model', 'org/model', TRUE);
+                 INSERT INTO api_errors (error_type, occurred_at, resolved) VALUES
+                     ('network', '2020-01-01 00:00:00', TRUE),
+                     ('network', '2020-01-01 00:00:00', TRUE),
+                     ('network', '2020-01-01 00:00:00', FALSE),
+                     ('network', CURRENT_TIMESTAMP, TRUE);
+                 INSERT INTO crawler_runs (status, completed_at, error_details) VALUES
+                     ('completed', '2020-01-01 00:00:00', '[]'),
+                     ('completed', CURRENT_TIMESTAMP, '[]')",
+            )?;
+            let mut recent = tweet("tweet_2");
+            recent.created_at = Utc::now();
+            db.insert_tweets_and_enqueue(
+                5,
+                &[tweet("tweet_1"), recent],
+                &[1],
+                TweetTypes::default(),
+            )?;
+            let jobs = db.claim_analysis_jobs(10, None)?;
+            db.complete_analysis_job(&jobs[0], r#"{"joy":10}"#, "{}", 10)?;
+            db.complete_analysis_job(&jobs[1], r#"{"joy":10}"#, "{}", 10)?;
+            db.execute_sql(
+                "UPDATE analysis_queue SET updated_at = '2020-01-01 00:00:00'
+                 WHERE tweet_id = (SELECT id FROM tweets WHERE tweet_id = 'tweet_2')",
+            )?;
+
+            let policy = RetentionPolicy {
+                tweets_days: Some(365),
+                api_errors_days: Some(30),
+                run_errors_days: Some(30),
+                completed_jobs_days: Some(30),
+                batch_size: 1,
+                vacuum: VacuumMode::Full,
+            };
+            let report = prune(db.as_ref(), &policy, &Shutdown::new())?;
+            let pruned: Vec<u64> = report.pruned.iter().map(|(_, rows)| *rows).collect();
+            assert_eq!(pruned, [1, 2, 1, 1]);
+            assert!(report.size_before > 0);
+            assert!(report.size_after.is_some());
+
+            let count = |sql: &str| db.query_text(sql);
+            // The old tweet's analysis went with it; the recent one's stays.
+            assert_eq!(
+                count("SELECT CAST(COUNT(*) AS TEXT) FROM sentiment_analyses")?.as_deref(),
+                Some("1")
+            );
+            assert_eq!(
+                count("SELECT CAST(COUNT(*) AS TEXT) FROM analysis_queue")?.as_deref(),
+                Some("0")
+            );
+            assert_eq!(
+                count("SELECT CAST(COUNT(*) AS TEXT) FROM api_errors")?.as_deref(),
+                Some("2")
+            );
+            assert_eq!(
+                count(
+                    "SELECT CAST(COUNT(*) AS TEXT) FROM crawler_runs WHERE error_details IS NULL"
+                )?
+                .as_deref(),
+                Some("1")
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn analysis_jobs_are_claimed_once() -> Result<(), CrawlerError> {
+        for db in backends()? {
+            db.execute_sql(
+                "INSERT INTO llm_models (id, name, huggingface_model_id, is_enabled)
+                 VALUES (1, 'Model', 'org/model', TRUE)",
+            )?;
+            db.insert_tweets_and_enqueue(5, &[tweet("tweet_1")], &[1], TweetTypes::default())?;
+
+            let jobs = db.claim_analysis_jobs(10, None)?;
+            assert_eq!(jobs.len(), 1);
+            assert_eq!(jobs[0].model_name, "org/model");
+            assert_eq!(jobs[0].attempt_count, 1);
+            assert!(db.claim_analysis_jobs(10, None)?.is_empty());
+
+            db.complete_analysis_job(&jobs[0], r#"{"joy":0.5}"#, "{}", 10)?;
+            assert!(db.latest_analysis_id()?.is_some());
+            assert_eq!(
+                db.analysis_queue_depth()?,
+                vec![("completed".to_string(), 1)]
+            );
+
+            let scores = db.load_analysis_scores()?;
+            assert_eq!(scores.len(), 1);
+            assert_eq!(
+                scores[0].tweet_timestamp,
+                tweet("tweet_1").created_at.to_rfc3339()
+            );
+        }
+        Ok(())
+    }
+}
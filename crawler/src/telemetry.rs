@@ -0,0 +1,143 @@
+//! Prometheus metrics
+//!
+//! Instrumentation goes through the `metrics` facade, so the helpers below are
+//! no-ops until [`install`] registers the Prometheus recorder (daemon only).
+//! The status server renders the recorder's handle at `/metrics`.
+
+use std::time::Duration;
+
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+const API_REQUESTS: &str = "crawler_api_requests_total";
+const API_REQUEST_DURATION: &str = "crawler_api_request_duration_seconds";
+const RATE_LIMIT_WAITS: &str = "crawler_rate_limit_waits_total";
+const RATE_LIMIT_WAIT_DURATION: &str = "crawler_rate_limit_wait_seconds";
+const TWEETS_INSERTED: &str = "crawler_tweets_inserted_total";
+const JOBS_ENQUEUED: &str = "crawler_analysis_jobs_enqueued_total";
+const JOBS_COMPLETED: &str = "crawler_analysis_jobs_completed_total";
+const JOBS_FAILED: &str = "crawler_analysis_jobs_failed_total";
+const INFERENCE_DURATION: &str = "crawler_inference_duration_seconds";
+const QUEUE_DEPTH: &str = "crawler_analysis_queue_depth";
+const CYCLES: &str = "crawler_cycles_total";
+const CYCLE_DURATION: &str = "crawler_cycle_duration_seconds";
+const ROWS_PRUNED: &str = "crawler_rows_pruned_total";
+const DATABASE_SIZE: &str = "crawler_database_size_bytes";
+const BACKUPS: &str = "crawler_backups_total";
+const BACKUP_SIZE: &str = "crawler_backup_size_bytes";
+
+/// Register the global Prometheus recorder
+pub fn install() -> anyhow::Result<PrometheusHandle> {
+    let handle = builder()?.install_recorder()?;
+    Ok(handle)
+}
+
+fn builder() -> anyhow::Result<PrometheusBuilder> {
+    // Requests and inference calls take milliseconds to seconds; cycles and
+    // rate-limit waits can take most of a 15 minute window.
+    let builder = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(API_REQUEST_DURATION.to_string()),
+            &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0],
+        )?
+        .set_buckets_for_metric(
+            Matcher::Full(INFERENCE_DURATION.to_string()),
+            &[0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0],
+        )?
+        .set_buckets_for_metric(
+            Matcher::Full(RATE_LIMIT_WAIT_DURATION.to_string()),
+            &[0.1, 1.0, 5.0, 30.0, 60.0, 300.0, 900.0],
+        )?
+        .set_buckets_for_metric(
+            Matcher::Full(CYCLE_DURATION.to_string()),
+            &[1.0, 10.0, 30.0, 60.0, 300.0, 900.0, 1800.0, 3600.0, 7200.0],
+        )?;
+    Ok(builder)
+}
+
+/// `status` is the HTTP status code, or `error` when no response arrived
+pub fn record_api_request(endpoint: &str, status: &str, elapsed: Duration) {
+    counter!(API_REQUESTS, "endpoint" => endpoint.to_string(), "status" => status.to_string())
+        .increment(1);
+    histogram!(API_REQUEST_DURATION, "endpoint" => endpoint.to_string())
+        .record(elapsed.as_secs_f64());
+}
+
+pub fn record_rate_limit_wait(waited: Duration) {
+    counter!(RATE_LIMIT_WAITS).increment(1);
+    histogram!(RATE_LIMIT_WAIT_DURATION).record(waited.as_secs_f64());
+}
+
+pub fn record_tweets_inserted(count: u64) {
+    counter!(TWEETS_INSERTED).increment(count);
+}
+
+pub fn record_job_enqueued(llm_model_id: Option<i64>) {
+    counter!(JOBS_ENQUEUED, "model_id" => model_label(llm_model_id)).increment(1);
+}
+
+pub fn record_job_completed(llm_model_id: i64, inference: Duration) {
+    counter!(JOBS_COMPLETED, "model_id" => llm_model_id.to_string()).increment(1);
+    histogram!(INFERENCE_DURATION, "model_id" => llm_model_id.to_string())
+        .record(inference.as_secs_f64());
+}
+
+pub fn record_job_failed(llm_model_id: i64) {
+    counter!(JOBS_FAILED, "model_id" => llm_model_id.to_string()).increment(1);
+}
+
+pub fn set_queue_depth(status: &str, depth: i64) {
+    gauge!(QUEUE_DEPTH, "status" => status.to_string()).set(depth as f64);
+}
+
+pub fn record_cycle(status: &str, elapsed: Duration) {
+    counter!(CYCLES, "status" => status.to_string()).increment(1);
+    histogram!(CYCLE_DURATION, "status" => status.to_string()).record(elapsed.as_secs_f64());
+}
+
+/// `table` is the retention rule's label, e.g. `api_errors`
+pub fn record_rows_pruned(table: &str, count: u64) {
+    counter!(ROWS_PRUNED, "table" => table.to_string()).increment(count);
+}
+
+pub fn set_database_size(bytes: i64) {
+    gauge!(DATABASE_SIZE).set(bytes as f64);
+}
+
+/// `status` is `completed` or `failed`; `compressed_bytes` is only recorded on success
+pub fn record_backup(status: &str, compressed_bytes: u64) {
+    counter!(BACKUPS, "status" => status.to_string()).increment(1);
+    if status == "completed" {
+        gauge!(BACKUP_SIZE).set(compressed_bytes as f64);
+    }
+}
+
+fn model_label(llm_model_id: Option<i64>) -> String {
+    llm_model_id.map_or_else(|| "default".to_string(), |id| id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_recorded_metrics() {
+        let recorder = builder().expect("build recorder").build_recorder();
+        let handle = recorder.handle();
+
+        metrics::with_local_recorder(&recorder, || {
+            record_api_request("/2/users/by", "200", Duration::from_millis(120));
+            record_job_enqueued(Some(2));
+            record_job_enqueued(None);
+            set_queue_depth("pending", 7);
+        });
+
+        let rendered = handle.render();
+        assert!(rendered
+            .contains(r#"crawler_api_requests_total{endpoint="/2/users/by",status="200"} 1"#));
+        assert!(rendered.contains(r#"crawler_analysis_jobs_enqueued_total{model_id="2"} 1"#));
+        assert!(rendered.contains(r#"crawler_analysis_jobs_enqueued_total{model_id="default"} 1"#));
+        assert!(rendered.contains(r#"crawler_analysis_queue_depth{status="pending"} 7"#));
+        assert!(rendered.contains("crawler_api_request_duration_seconds_bucket"));
+    }
+}
@@ -1,5 +1,8 @@
 //! Twitter API v2 client
 
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
+
 use chrono::{DateTime, Utc};
 use governor::clock::{Clock, DefaultClock};
 use reqwest::{Client, StatusCode};
@@ -8,6 +11,7 @@ use serde::de::DeserializeOwned;
 use crate::error::CrawlerError;
 use crate::models::{TwitterApiError, TwitterApiTweet, TwitterUserTweetsResponse, TwitterUsersResponse};
 use crate::rate_limit::SharedRateLimiter;
+use crate::telemetry;
 
 const BASE_URL: &str = "https://api.twitter.com/2";
 
@@ -15,11 +19,14 @@ pub struct TwitterApiClient {
     client: Client,
     bearer_token: String,
     rate_limiter: SharedRateLimiter,
+    /// Requests left in the current window as of the last request (`u32::MAX` before any)
+    remaining_requests: AtomicU32,
 }
 
-pub struct TweetFetchResult {
+pub struct TweetPage {
     pub tweets: Vec<TwitterApiTweet>,
     pub errors: Vec<TwitterApiError>,
+    pub next_token: Option<String>,
 }
 
 impl TwitterApiClient {
@@ -32,9 +39,16 @@ impl TwitterApiClient {
             client,
             bearer_token,
             rate_limiter,
+            remaining_requests: AtomicU32::new(u32::MAX),
         })
     }
 
+    /// Rate limit budget left after the most recent request
+    pub fn remaining_requests(&self) -> Option<u32> {
+        let remaining = self.remaining_requests.load(Ordering::Relaxed);
+        (remaining != u32::MAX).then_some(remaining)
+    }
+
     pub async fn fetch_users_by_usernames(
         &self,
         usernames: &[String],
@@ -81,49 +95,49 @@ impl TwitterApiClient {
         })
     }
 
-    pub async fn fetch_user_tweets(
+    /// Fetch one page of a user's timeline; pass the previous page's
+    /// `next_token` to continue
+    ///
+    /// `exclude` lists the tweet types (`replies`, `retweets`) to leave out.
+    pub async fn fetch_user_tweets_page(
         &self,
         user_id: &str,
         start_time: DateTime<Utc>,
-    ) -> Result<TweetFetchResult, CrawlerError> {
-        let mut tweets = Vec::new();
-        let mut errors = Vec::new();
-        let mut next_token: Option<String> = None;
-
-        loop {
-            let mut request = self
-                .client
-                .get(format!("{BASE_URL}/users/{user_id}/tweets"))
-                .query(&[
-                    ("tweet.fields", "created_at,public_metrics,referenced_tweets"),
-                    ("start_time", start_time.to_rfc3339().as_str()),
-                    ("max_results", "100"),
-                ]);
-
-            if let Some(token) = &next_token {
-                request = request.query(&[("pagination_token", token.as_str())]);
-            }
-
-            let response: TwitterUserTweetsResponse = self
-                .send_request(request, "/2/users/:id/tweets")
-                .await?;
-
-            if let Some(page_tweets) = response.data {
-                tweets.extend(page_tweets);
-            }
-            if let Some(page_errors) = response.errors {
-                errors.extend(page_errors);
-            }
-
-            next_token = response.meta.and_then(|meta| meta.next_token);
-            if next_token.is_none() {
-                break;
-            }
+        end_time: Option<DateTime<Utc>>,
+        exclude: &[&str],
+        pagination_token: Option<&str>,
+    ) -> Result<TweetPage, CrawlerError> {
+        let mut request = self
+            .client
+            .get(format!("{BASE_URL}/users/{user_id}/tweets"))
+            .query(&[
+                ("tweet.fields", "created_at,public_metrics,referenced_tweets"),
+                ("start_time", start_time.to_rfc3339().as_str()),
+                ("max_results", "100"),
+            ]);
+
+        if let Some(end_time) = end_time {
+            request = request.query(&[("end_time", end_time.to_rfc3339().as_str())]);
+        }
+        if !exclude.is_empty() {
+            request = request.query(&[("exclude", exclude.join(",").as_str())]);
+        }
+        if let Some(token) = pagination_token {
+            request = request.query(&[("pagination_token", token)]);
         }
 
-        Ok(TweetFetchResult { tweets, errors })
+        let response: TwitterUserTweetsResponse = self
+            .send_request(request, "/2/users/:id/tweets")
+            .await?;
+
+        Ok(TweetPage {
+            tweets: response.data.unwrap_or_default(),
+            errors: response.errors.unwrap_or_default(),
+            next_token: response.meta.and_then(|meta| meta.next_token),
+        })
     }
 
+    #[tracing::instrument(name = "twitter_request", skip(self, request))]
     async fn send_request<T: DeserializeOwned>(
         &self,
         request: reqwest::RequestBuilder,
@@ -131,13 +145,19 @@ impl TwitterApiClient {
     ) -> Result<T, CrawlerError> {
         self.wait_for_rate_limit().await?;
 
-        let response = request
-            .bearer_auth(&self.bearer_token)
-            .send()
-            .await?;
+        let started = Instant::now();
+        let response = match request.bearer_auth(&self.bearer_token).send().await {
+            Ok(response) => response,
+            Err(error) => {
+                telemetry::record_api_request(endpoint, "error", started.elapsed());
+                return Err(error.into());
+            }
+        };
 
         let status = response.status();
-        let body = response.text().await?;
+        let body = response.text().await;
+        telemetry::record_api_request(endpoint, status.as_str(), started.elapsed());
+        let body = body?;
 
         if !status.is_success() {
             return Err(match status {
@@ -157,10 +177,19 @@ impl TwitterApiClient {
     }
 
     async fn wait_for_rate_limit(&self) -> Result<(), CrawlerError> {
+        let mut waited: Option<Instant> = None;
         loop {
             match self.rate_limiter.check() {
-                Ok(_) => return Ok(()),
+                Ok(snapshot) => {
+                    self.remaining_requests
+                        .store(snapshot.remaining_burst_capacity(), Ordering::Relaxed);
+                    if let Some(started) = waited {
+                        telemetry::record_rate_limit_wait(started.elapsed());
+                    }
+                    return Ok(());
+                }
                 Err(negative) => {
+                    waited.get_or_insert_with(Instant::now);
                     let wait = negative.wait_time_from(DefaultClock::default().now());
                     tokio::time::sleep(wait).await;
                 }
@@ -1,7 +1,7 @@
 // Admin Dashboard - Overview with crawler status and quick stats
 import { useEffect, useState } from 'react';
 import { useNavigate } from 'react-router-dom';
-import { Activity, Clock, AlertTriangle, CheckCircle2, Loader2, Play, RefreshCw, RotateCcw } from 'lucide-react';
+import { Activity, Ban, Clock, AlertTriangle, CheckCircle2, Loader2, Play, RefreshCw, RotateCcw } from 'lucide-react';
 import { api } from '@/lib/api';
 import { useToast } from '@/hooks/useToast';
 import { ConfirmDialog } from '@/components/ui/ConfirmDialog';
@@ -11,7 +11,7 @@ interface CrawlerRun {
   id: number;
   startedAt: string;
   completedAt: string | null;
-  status: 'running' | 'completed' | 'failed';
+  status: 'running' | 'completed' | 'failed' | 'cancelled';
   tweetsFetched: number;
   tweetsAnalyzed: number;
   errorsCount: number;
@@ -71,12 +71,13 @@ function formatTimeUntil(timestamp: string | null): string {
 }
 
 // Status badge component
-function StatusBadge({ status }: { status: 'running' | 'idle' | 'completed' | 'failed' }) {
+function StatusBadge({ status }: { status: 'running' | 'idle' | 'completed' | 'failed' | 'cancelled' }) {
   const config = {
     running: { icon: Loader2, color: 'text-primary', bg: 'bg-primary/10', label: 'Running', animate: true },
     idle: { icon: Clock, color: 'text-muted-foreground', bg: 'bg-muted', label: 'Idle', animate: false },
     completed: { icon: CheckCircle2, color: 'text-success', bg: 'bg-success/10', label: 'Completed', animate: false },
     failed: { icon: AlertTriangle, color: 'text-destructive', bg: 'bg-destructive/10', label: 'Failed', animate: false },
+    cancelled: { icon: Ban, color: 'text-muted-foreground', bg: 'bg-muted', label: 'Cancelled', animate: false },
   };
 
   const { icon: Icon, color, bg, label, animate } = config[status];